@@ -0,0 +1,210 @@
+use std::cmp::Ordering;
+
+use crate::kdtree::KdTree;
+use crate::punto::*;
+
+// Dos triples a menos de esto (relativo) cuentan como empate
+pub const TOLERANCIA: f64 = 8.0 * f64::EPSILON;
+
+// hi + lo con |lo| <= ulp(hi) / 2, ~106 bits de mantisa. Las restas de
+// coordenadas son exactas y el resto de operaciones pierde unos pocos ulp
+// de los 106 bits, muy por debajo de lo que separa dos sumas de f64.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DobleDoble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+// Solo si |a| >= |b|
+fn quick_two_sum(a: f64, b: f64) -> DobleDoble {
+    let s = a + b;
+    DobleDoble {
+        hi: s,
+        lo: b - (s - a),
+    }
+}
+
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    (p, a.mul_add(b, -p))
+}
+
+#[allow(unused)]
+impl DobleDoble {
+    pub fn new(x: f64) -> Self {
+        Self { hi: x, lo: 0.0 }
+    }
+
+    // a - b sin redondeo
+    pub fn diferencia(a: f64, b: f64) -> Self {
+        let (hi, lo) = two_sum(a, -b);
+        Self { hi, lo }
+    }
+
+    pub fn suma(self, other: Self) -> Self {
+        let (s, e) = two_sum(self.hi, other.hi);
+        let (t, f) = two_sum(self.lo, other.lo);
+        let a = quick_two_sum(s, e + t);
+        quick_two_sum(a.hi, a.lo + f)
+    }
+
+    pub fn negado(self) -> Self {
+        Self {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+
+    pub fn producto(self, other: Self) -> Self {
+        let (p, e) = two_prod(self.hi, other.hi);
+        quick_two_sum(p, e + (self.hi * other.lo + self.lo * other.hi))
+    }
+
+    // Un paso de Newton sobre la raiz de f64
+    pub fn raiz(self) -> Self {
+        if self.hi <= 0.0 {
+            return Self::default();
+        }
+        let x = self.hi.sqrt();
+        let (p, e) = two_prod(x, x);
+        let resto = self.suma(DobleDoble { hi: p, lo: e }.negado());
+        quick_two_sum(x, resto.hi / (2.0 * x))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        self.hi
+            .total_cmp(&other.hi)
+            .then(self.lo.total_cmp(&other.lo))
+    }
+}
+
+pub fn distancia_dd(a: &Punto, b: &Punto) -> DobleDoble {
+    let dx = DobleDoble::diferencia(a.x, b.x);
+    let dy = DobleDoble::diferencia(a.y, b.y);
+    dx.producto(dx).suma(dy.producto(dy)).raiz()
+}
+
+// Camino i-j-k, j el central, como lo devuelven los get_points
+pub fn longitud_dd(puntos: &[Punto], [i, j, k]: [usize; 3]) -> DobleDoble {
+    distancia_dd(&puntos[i], &puntos[j]).suma(distancia_dd(&puntos[j], &puntos[k]))
+}
+
+#[derive(Clone, Debug)]
+pub struct Certificado {
+    // El mejor camino recalculado en doble-doble
+    pub indices: [usize; 3],
+    pub distancia: f64,
+    // Caminos con longitud f64 <= best * (1 + tolerancia) que se han recalculado
+    pub candidatos: usize,
+    // Diferencia en doble-doble con el segundo mejor, si lo hay
+    pub margen: Option<f64>,
+    // Ningun otro camino a menos de tolerancia * distancia
+    pub unico: bool,
+}
+
+// Recalcula en doble-doble todos los caminos casi empatados con `best`, el
+// resultado en f64 de cualquier solver. None si no hay ningun camino de
+// longitud `best`, es decir, si el solver se ha equivocado por abajo.
+#[allow(unused)]
+pub fn certifica(puntos: &[Punto], best: f64, tolerancia: f64) -> Option<Certificado> {
+    if best == 0.0 {
+        return coincidentes(puntos);
+    }
+
+    // Holgura para los redondeos de la suma en f64 del propio solver
+    let limite = best * (1.0 + tolerancia + 4.0 * f64::EPSILON);
+    let tree = KdTree::new(puntos);
+
+    let mut candidatos: Vec<([usize; 3], DobleDoble)> = Vec::new();
+    for (j, punto_j) in puntos.iter().enumerate() {
+        let mut vecinos = tree.within_radius(punto_j, limite);
+        vecinos.retain(|&(i, _)| i != j);
+        let vecinos = sin_repetidos(puntos, vecinos);
+        for (n, &(i, distancia_ij)) in vecinos.iter().enumerate() {
+            // Vienen ordenados por distancia: a partir de aqui no hay k que quepa
+            if distancia_ij + distancia_ij > limite {
+                break;
+            }
+            for &(k, distancia_jk) in &vecinos[n + 1..] {
+                if distancia_ij + distancia_jk > limite {
+                    break;
+                }
+                // [i, j, k] y [k, j, i] son el mismo camino
+                let indices = if i < k { [i, j, k] } else { [k, j, i] };
+                candidatos.push((indices, longitud_dd(puntos, indices)));
+            }
+        }
+    }
+
+    candidatos.sort_unstable_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+    let (indices, mejor) = *candidatos.first()?;
+
+    let margen = candidatos
+        .get(1)
+        .map(|(_, segundo)| segundo.suma(mejor.negado()).to_f64());
+    Some(Certificado {
+        indices,
+        distancia: mejor.to_f64(),
+        candidatos: candidatos.len(),
+        margen,
+        unico: margen.is_none_or(|margen| margen > tolerancia * mejor.to_f64()),
+    })
+}
+
+// Con best 0 el camino son tres puntos en el mismo sitio, y cualquier trio de
+// ellos vale: no hace falta buscar vecinos, que serian todas las copias
+fn coincidentes(puntos: &[Punto]) -> Option<Certificado> {
+    // + 0.0 para que -0.0 y 0.0 sean el mismo sitio
+    let sitio = |i: usize| (puntos[i].x + 0.0, puntos[i].y + 0.0);
+    let mut orden: Vec<usize> = (0..puntos.len()).collect();
+    orden.sort_unstable_by(|&a, &b| {
+        let ((ax, ay), (bx, by)) = (sitio(a), sitio(b));
+        ax.total_cmp(&bx).then(ay.total_cmp(&by)).then(a.cmp(&b))
+    });
+
+    let trio = orden.windows(3).find(|w| sitio(w[0]) == sitio(w[2]))?;
+    // Los otros dos centros del mismo trio miden lo mismo
+    Some(Certificado {
+        indices: [trio[0], trio[1], trio[2]],
+        distancia: 0.0,
+        candidatos: 1,
+        margen: Some(0.0),
+        unico: false,
+    })
+}
+
+// Como mucho dos vecinos en cada sitio: bastan para que i y k esten juntos y
+// para ver los empates entre copias, y con muchos puntos repetidos el bucle
+// de k no pasa por todas las copias
+fn sin_repetidos(puntos: &[Punto], vecinos: Vec<(usize, f64)>) -> Vec<(usize, f64)> {
+    let mismo_sitio = |a: usize, b: usize| puntos[a].x == puntos[b].x && puntos[a].y == puntos[b].y;
+    let mut quedan: Vec<(usize, f64)> = Vec::with_capacity(vecinos.len());
+    // Las copias estan a la misma distancia: solo se miran las de la racha actual
+    let mut racha = 0;
+    for (i, distancia) in vecinos {
+        if quedan
+            .last()
+            .is_some_and(|&(_, anterior)| anterior != distancia)
+        {
+            racha = quedan.len();
+        }
+        let copias = quedan[racha..]
+            .iter()
+            .filter(|&&(q, _)| mismo_sitio(q, i))
+            .count();
+        if copias < 2 {
+            quedan.push((i, distancia));
+        }
+    }
+    quedan
+}
@@ -0,0 +1,142 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+const GZIP: &[u8] = &[0x1f, 0x8b];
+const ZSTD: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compresion {
+    #[default]
+    Ninguna,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+#[allow(unused)]
+impl Compresion {
+    // Por los magic bytes del principio del fichero
+    pub fn detecta(cabecera: &[u8]) -> Self {
+        if cabecera.starts_with(GZIP) {
+            Compresion::Gzip
+        } else if cabecera.starts_with(ZSTD) {
+            Compresion::Zstd
+        } else if cabecera.starts_with(XZ) {
+            Compresion::Xz
+        } else {
+            Compresion::Ninguna
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compresion::Ninguna => "",
+            Compresion::Gzip => ".gz",
+            Compresion::Zstd => ".zst",
+            Compresion::Xz => ".xz",
+        }
+    }
+
+    pub fn decoder<'a, R: Read + 'a>(&self, reader: R) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compresion::Ninguna => Box::new(reader),
+            Compresion::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Compresion::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            Compresion::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+
+    pub fn encoder<W: Write>(&self, writer: W) -> io::Result<Encoder<W>> {
+        Ok(match self {
+            Compresion::Ninguna => Encoder::Ninguna(writer),
+            Compresion::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            Compresion::Zstd => Encoder::Zstd(zstd::stream::write::Encoder::new(writer, 0)?),
+            Compresion::Xz => Encoder::Xz(xz2::write::XzEncoder::new(writer, 6)),
+        })
+    }
+}
+
+// Hay que cerrarlo con finish(): el drop de gzip y xz tambien termina el
+// stream pero se traga los errores, y el de zstd no lo termina.
+#[allow(unused)]
+pub enum Encoder<W: Write> {
+    Ninguna(W),
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Xz(xz2::write::XzEncoder<W>),
+}
+
+#[allow(unused)]
+impl<W: Write> Encoder<W> {
+    // Termina el stream y vacia el writer de debajo
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = match self {
+            Encoder::Ninguna(writer) => writer,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+            Encoder::Xz(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Ninguna(writer) => writer.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+            Encoder::Xz(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Ninguna(writer) => writer.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+            Encoder::Xz(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl FromStr for Compresion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" | "ninguna" => Ok(Compresion::Ninguna),
+            "gzip" | "gz" => Ok(Compresion::Gzip),
+            "zstd" | "zst" => Ok(Compresion::Zstd),
+            "xz" => Ok(Compresion::Xz),
+            _ => Err(format!("compresion desconocida: {s}")),
+        }
+    }
+}
+
+// Descomprime `datos` si empiezan con un magic conocido
+#[allow(unused)]
+pub fn descomprime(datos: &[u8]) -> io::Result<Vec<u8>> {
+    // Sin adivinar el ratio: al menos lo que ocupa comprimido y que crezca
+    let mut salida = Vec::with_capacity(datos.len());
+    Compresion::detecta(datos)
+        .decoder(datos)?
+        .read_to_end(&mut salida)?;
+    Ok(salida)
+}
+
+#[allow(unused)]
+pub fn crea_fichero<I: AsRef<Path>>(
+    name: I,
+    compresion: Compresion,
+) -> io::Result<Encoder<BufWriter<File>>> {
+    compresion.encoder(BufWriter::new(File::create(name)?))
+}
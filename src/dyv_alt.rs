@@ -1,23 +1,29 @@
+use crate::escalar::{Escalar, PorCarriles};
+use crate::kernel::Kernel;
 use crate::punto::*;
+use crate::soa::PuntosSoa;
+use crate::stats::{self, Contador, Fase};
 
 const FIXED_POINTS: usize = 130;
 const MAX: f64 = f64::MAX;
 
 #[allow(unused)]
-pub struct DyVAlt<'a> {
-    puntos: &'a [Punto],
+pub struct DyVAlt<'a, T: Escalar = f64> {
+    puntos: &'a [Punto<T>],
+    soa: PuntosSoa<T>,
     best_option: BestPoint,
-    best_points: [Punto; 3],
+    best_points: [Punto<T>; 3],
     pub fixed_points: usize,
     f_cf: bool,
 }
 
 #[allow(unused)]
-impl<'a> DyVAlt<'a> {
+impl<'a, T: Escalar> DyVAlt<'a, T> {
     #[allow(unused)]
-    pub fn new_with_fixed(puntos: &'a [Punto], fixed_points: usize) -> Self {
+    pub fn new_with_fixed(puntos: &'a [Punto<T>], fixed_points: usize) -> Self {
         Self {
             puntos,
+            soa: PuntosSoa::new(puntos),
             best_option: MAX,
             best_points: [Punto::default(), Punto::default(), Punto::default()],
             fixed_points,
@@ -26,26 +32,28 @@ impl<'a> DyVAlt<'a> {
     }
 
     #[allow(unused)]
-    pub fn new(puntos: &'a [Punto]) -> Self {
+    pub fn new(puntos: &'a [Punto<T>]) -> Self {
         Self {
             puntos,
-            best_option: puntos[0].distancia3(&puntos[1], &puntos[2]),
+            soa: PuntosSoa::new(puntos),
+            best_option: MAX,
             best_points: [Punto::default(), Punto::default(), Punto::default()],
-            fixed_points: FIXED_POINTS,
+            fixed_points: crate::perfil::fixed_points::<T>("dyv_alt", FIXED_POINTS),
             f_cf: false,
         }
     }
 
     pub fn start(&mut self) -> BestPoint {
-        self.divide_venceras_it();
+        // Sin target_feature: los carriles de T en el target por defecto
+        T::con_carriles(Kernel::Sse2, Corrida(self));
         self.best_option
     }
 
     #[inline]
     fn get_next_point(
         &'a self,
-        puntos: &'a [Punto],
-        punto_i: &'a Punto,
+        puntos: &'a [Punto<T>],
+        punto_i: &'a Punto<T>,
         mut start: usize,
     ) -> Option<usize> {
         use std::ops::Not;
@@ -57,28 +65,29 @@ impl<'a> DyVAlt<'a> {
             return None;
         }
 
-        let vec_punto_i = f64x4::splat(punto_i.y);
         let vec_distancia = f64x4::splat(self.best_option);
+        // Las diferencias en f64 con Escalar::diferencia, exactas en todos los tipos
+        let dy = |punto: &Punto<T>| punto.y.diferencia(punto_i.y);
 
-        let mut it = puntos[start..].array_chunks::<8>();
-        let max_x = punto_i.x + self.best_option;
+        let mut it = puntos[start..].chunks_exact(8);
 
-        while let Some(chunk) = it.next() {
-            //for chunk in it.by_ref() {
-            if chunk[0].x >= max_x {
+        for chunk in it.by_ref() {
+            if chunk[0].x.diferencia(punto_i.x) >= self.best_option {
                 return None;
             }
 
-            let vector_y = f64x4::from_array([chunk[0].y, chunk[1].y, chunk[2].y, chunk[3].y]);
-            let res = (vector_y - vec_punto_i).abs().simd_le(vec_distancia);
+            let vector_y =
+                f64x4::from_array([dy(&chunk[0]), dy(&chunk[1]), dy(&chunk[2]), dy(&chunk[3])]);
+            let res = vector_y.abs().simd_le(vec_distancia);
             match res.first_set() {
                 None => start += 4,
                 Some(i) => return Some(start + i),
             }
             // Unroll
 
-            let vector_y = f64x4::from_array([chunk[4].y, chunk[5].y, chunk[6].y, chunk[7].y]);
-            let res = (vector_y - vec_punto_i).abs().simd_le(vec_distancia);
+            let vector_y =
+                f64x4::from_array([dy(&chunk[4]), dy(&chunk[5]), dy(&chunk[6]), dy(&chunk[7])]);
+            let res = vector_y.abs().simd_le(vec_distancia);
             match res.first_set() {
                 None => start += 4,
                 Some(i) => return Some(start + i),
@@ -88,131 +97,168 @@ impl<'a> DyVAlt<'a> {
         it.remainder()
             .iter()
             .position(|punto_y| {
-                (punto_y.y - punto_i.y).abs() < self.best_option
-                    && (punto_y.x - punto_i.x).abs() < self.best_option
+                dy(punto_y).abs() < self.best_option
+                    && punto_y.x.diferencia(punto_i.x).abs() < self.best_option
             })
             .map(|val| val + start)
     }
 
     #[inline(always)]
-    fn calcula_fixed_range(&mut self, slice: &'a [Punto], mid: usize) {
-        self.calcula_fixed(slice, Some(mid));
+    fn calcula_fixed_range<const N: usize>(&mut self, start: usize, end: usize, mid: usize) {
+        self.calcula_fixed::<N>(start, end, Some(mid));
+    }
+
+    // Los k de un i-j con j ya elegido, filtrados por candidatos_k como en
+    // DyVSIMD: caminos i-j-k y j-i-k con k en i + 1..end. El de k en el centro
+    // sale cuando el j es ese k.
+    #[inline(always)]
+    fn calcula_k<const N: usize>(&mut self, i: usize, j: usize, distancia_ij: f64, end: usize) {
+        let (punto_i, punto_j) = (self.puntos[i], self.puntos[j]);
+        // Con k a best_option de i en x ya no hay nada
+        let end = i
+            + 1
+            + self.soa.xs()[i + 1..end]
+                .partition_point(|x| x.diferencia(punto_i.x) < self.best_option);
+        let mut mejor = self.best_option - distancia_ij;
+        let mut visitados = 0;
+
+        T::candidatos_k::<N>(&self.soa, i, j, self.best_option, i + 1, end, |k| {
+            if k == j {
+                return self.best_option;
+            }
+            visitados += 1;
+            let punto_k = self.puntos[k];
+            // El filtro se hizo con un best_option que puede haber bajado
+            if punto_k.y.diferencia(punto_i.y).abs() >= self.best_option
+                && punto_k.y.diferencia(punto_j.y).abs() >= self.best_option
+            {
+                stats::cuenta(Contador::RechazosY);
+                return self.best_option;
+            }
+
+            let (jk, ki) = (punto_j.distancia(&punto_k), punto_k.distancia(&punto_i));
+            let (t1, t3) = (distancia_ij + jk, ki + distancia_ij);
+
+            if jk < mejor {
+                self.best_option = t1;
+                stats::cuenta(Contador::Mejoras);
+                mejor = self.best_option - distancia_ij;
+                self.best_points = [punto_i, punto_j, punto_k];
+            }
+            if t3 < self.best_option {
+                self.best_option = t3;
+                stats::cuenta(Contador::Mejoras);
+                mejor = self.best_option - distancia_ij;
+                self.best_points = [punto_k, punto_i, punto_j];
+            }
+            self.best_option
+        });
+
+        // Los que no ha visitado los ha quitado el filtro vectorial
+        if stats::ACTIVAS {
+            stats::suma(Contador::K, visitados);
+            stats::suma(Contador::RechazosY, (end - i - 2) as u64 - visitados);
+        }
     }
 
+    // i en start..end, o en start..start + mid en una franja; j y k hasta end
     #[inline(always)]
-    fn calcula_fixed(&mut self, slice: &'a [Punto], rec: Option<usize>) {
-        let mut end = slice.len();
+    fn calcula_fixed<const N: usize>(&mut self, start: usize, end: usize, rec: Option<usize>) {
+        let puntos = self.puntos;
+        let slice = &puntos[start..end];
+        let mut fin_i = slice.len();
         let (mut is, mut js) = (0, 0);
 
-        if end < 2 {
+        if fin_i < 2 {
             return;
         }
 
         if let Some(mid) = rec {
-            end = mid + 2;
+            fin_i = mid + 2;
         }
 
-        for punto_i in slice[..end - 2].iter() {
-            let max_ij = punto_i.x + self.best_option;
-
-            while let Some(punto_j_index) =
-                self.get_next_point(&slice[..slice.len() - 1], punto_i, js + 1)
-            {
-                // for punto_j in slice[is + 1..end - 1].iter() {
-                let punto_j: &'a Punto = &slice[punto_j_index];
+        stats::suma(Contador::I, (fin_i - 2) as u64);
+        for punto_i in slice[..fin_i - 2].iter() {
+            while let Some(punto_j_index) = self.get_next_point(slice, punto_i, js.max(is + 1)) {
+                let punto_j = &slice[punto_j_index];
                 js = punto_j_index + 1;
 
-                if punto_j.x >= max_ij {
+                if punto_j.x.diferencia(punto_i.x) >= self.best_option {
+                    stats::cuenta(Contador::CortesX);
                     break;
                 }
 
-                if (punto_j.y - punto_i.y).abs() >= self.best_option {
+                if punto_j.y.diferencia(punto_i.y).abs() >= self.best_option {
+                    stats::cuenta(Contador::RechazosY);
                     continue;
                 }
+                stats::cuenta(Contador::J);
 
-                for punto_k in slice.iter().skip(js) {
-                    let (ij, jk, ki) = (
-                        punto_i.distancia(punto_j),
-                        punto_j.distancia(punto_k),
-                        punto_k.distancia(punto_i),
-                    );
-
-                    if ij >= self.best_option && jk >= self.best_option {
-                        continue;
-                    }
-
-                    let (t1, t2, t3) = (ij + jk, jk + ki, ki + ij);
-                    let min = t1.min(t2).min(t3);
-
-                    if min < self.best_option {
-                        self.best_option = min;
-                        match min {
-                            t1 => self.best_points = [*punto_i, *punto_j, *punto_k],
-                            t2 => self.best_points = [*punto_j, *punto_k, *punto_i],
-                            t3 => self.best_points = [*punto_k, *punto_i, *punto_j],
-                            _ => unreachable!(),
-                        }
-                    }
+                let distancia_ij = punto_i.distancia(punto_j);
+                if distancia_ij >= self.best_option {
+                    continue;
                 }
+                self.calcula_k::<N>(start + is, start + punto_j_index, distancia_ij, end);
             }
             is += 1;
             js = is;
         }
     }
 
-    fn divide_venceras_it(&mut self) {
-        let v = self.puntos.len() / self.fixed_points;
+    #[inline(always)]
+    fn divide_venceras_it<const N: usize>(&mut self) {
+        let len = self.puntos.len();
+        if len < 3 {
+            return;
+        }
 
-        for chunk in self.puntos.chunks(self.fixed_points) {
-            //let end = (i + 1) * self.fixed_points;
-            //let slice = &self.puntos.get(self.fixed_points * i..end).unwrap();
-            self.calcula_fixed(chunk, None)
+        for start in (0..len).step_by(self.fixed_points) {
+            self.calcula_fixed::<N>(start, (start + self.fixed_points).min(len), None)
         }
 
-        // Merge respuestas
-        let mut start = 0;
-        while start < self.puntos.len() {
-            let mut end = start + self.fixed_points * 2;
-            if start + self.fixed_points * 2 > self.puntos.len() {
-                end = self.puntos.len()
-            }
-            let slice = self.puntos.get(start..end).unwrap();
-            self.recheck_actual_best(slice);
-            start += self.fixed_points
+        // Un camino que no esta dentro de un bloque cruza alguna frontera, y
+        // sus tres puntos estan a menos de best_option de ella en x. La
+        // franja se busca en todo el slice, no solo en los dos bloques de al
+        // lado: con best_option grande puede pasar de uno a otro.
+        for frontera in (self.fixed_points..len).step_by(self.fixed_points) {
+            self.recheck_actual_best::<N>(frontera);
         }
     }
 
-    fn recheck_actual_best(&mut self, s_slice: &'a [Punto]) {
-        let mitad_index = s_slice.len() / 2;
-        let mitad = s_slice[mitad_index].x;
+    // Los caminos cuyo primer punto (en orden de x) esta en el bloque de justo
+    // antes de `frontera` y alguno de los otros despues. Los que empiezan en
+    // un bloque anterior ya salieron en su frontera: con todas las x iguales
+    // la franja es siempre todo y sin esto cada frontera lo repasaria entero
+    fn recheck_actual_best<const N: usize>(&mut self, frontera: usize) {
+        let puntos = self.puntos;
+        let mitad = puntos[frontera].x.to_f64();
         let (new_start, new_end) =
-            Self::get_points_between(mitad - self.best_option, mitad + self.best_option, s_slice);
+            Self::get_points_between(mitad - self.best_option, mitad + self.best_option, puntos);
+        let new_start = new_start.max(frontera - self.fixed_points);
 
-        let mid = mitad_index - new_start;
-        self.calcula_fixed_range(&s_slice[new_start..new_end + 1], mid);
+        let mid = frontera - new_start;
+        let _fase = stats::en_fase(Fase::Franja);
+        stats::franja(new_end + 1 - new_start);
+        self.calcula_fixed_range::<N>(new_start, new_end + 1, mid);
     }
 
-    fn get_points_between(start: f64, end: f64, puntos: &[Punto]) -> (usize, usize) {
-        let start_index = match puntos.binary_search_by(|p| p.x_comparef64(&start)) {
-            Ok(index) | Err(index) => index,
-        };
-
-        let end_index = match puntos.binary_search_by(|p| p.x_comparef64(&end)) {
-            Ok(index) => index,
-            Err(index) => index - 1,
-        };
+    fn get_points_between(start: f64, end: f64, puntos: &[Punto<T>]) -> (usize, usize) {
+        // Con x repetidas binary_search devuelve cualquiera de las iguales
+        let start_index = puntos.partition_point(|p| p.x_comparef64(&start).is_lt());
+        let end_index = puntos.partition_point(|p| p.x_comparef64(&end).is_le()) - 1;
 
         (start_index, end_index)
     }
 
     pub fn get_points(&self) -> [usize; 3] {
-        let mut indexes = [0; 3];
-        for (i, point) in self.best_points.iter().enumerate() {
-            // SAFETY:
-            // El punto que estoy buscando siempre va a existir
-            let index = self.puntos.binary_search_by(|p| p.cmp(point)).unwrap();
-            indexes[i] = index;
-        }
-        indexes
+        indices_de(self.puntos, &self.best_points)
+    }
+}
+
+struct Corrida<'s, 'a, T: Escalar>(&'s mut DyVAlt<'a, T>);
+
+impl<T: Escalar> PorCarriles for Corrida<'_, '_, T> {
+    fn corre<const N: usize>(self) {
+        self.0.divide_venceras_it::<N>()
     }
 }
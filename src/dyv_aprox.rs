@@ -0,0 +1,292 @@
+use std::ops::Range;
+
+use rand::{Rng, SeedableRng};
+
+use crate::dyv_st::DyV;
+use crate::punto::*;
+use crate::stats::{self, Contador};
+
+const FIXED_POINTS: usize = 108;
+const MAX: f64 = f64::MAX;
+// La cota de arriba sale de resolver 1 de cada MUESTRA puntos (potencia de
+// dos). Con puntos uniformes sale unas MUESTRA^(3/4) veces mas larga que el
+// optimo, y la primera pasada de la cuadricula usa un radio 2^PASOS veces
+// mas corto.
+const MUESTRA: usize = 16;
+const PASOS: u32 = 3;
+// Con menos puntos se resuelve exacto directamente
+const MIN_PUNTOS: usize = 1 << 12;
+// Si la muestra da una cota muy floja la cuadricula deja de ser lineal: con
+// mas filas que esto, o mirando mas de PRESUPUESTO puntos por punto de la
+// entrada, se resuelve exacto
+const MAX_FILAS: usize = 1 << 24;
+const PRESUPUESTO: usize = 64;
+
+// Solver (1 + eps)-aproximado:
+//
+// 1. U: el mejor camino de una muestra al azar de los puntos. Es un camino de
+//    la entrada, asi que el optimo L cumple L <= U.
+// 2. Una pasada con radio r busca, exactos, los caminos de longitud < r en
+//    una cuadricula de lado r. En un camino asi los otros dos puntos estan a
+//    menos de r del central, en las 3x3 celdas alrededor de la suya, y solo
+//    hace falta mirar como central los puntos cuyo bloque tiene al menos 3.
+//    Si sale alguno, el mejor es el optimo exacto. Si no, L >= r.
+// 3. El coste crece muy deprisa con r, asi que se empieza con U / 2^PASOS y
+//    se multiplica por 4 hasta U / (1 + eps). Si esa ultima pasada tampoco encuentra
+//    nada, el camino de la muestra mide U = (1 + eps) r <= (1 + eps) L.
+//
+// lower_bound() es L si alguna pasada lo ha encontrado y si no el ultimo r.
+#[allow(unused)]
+pub struct DyVAprox<'a> {
+    puntos: &'a [Punto],
+    eps: f64,
+    best_option: BestPoint,
+    best_points: [usize; 3],
+    cota: f64,
+    // Puntos que se han mirado como central en la cuadricula
+    centrales: usize,
+    pub fixed_points: usize,
+}
+
+#[allow(unused)]
+impl<'a> DyVAprox<'a> {
+    pub fn new(puntos: &'a [Punto], eps: f64) -> Self {
+        Self::new_with_fixed(puntos, eps, FIXED_POINTS)
+    }
+
+    // fixed_points es el de los DyV exactos (la muestra y el caso pequeño)
+    pub fn new_with_fixed(puntos: &'a [Punto], eps: f64, fixed_points: usize) -> Self {
+        assert!(eps >= 0.0, "eps negativo: {eps}");
+        Self {
+            puntos,
+            eps,
+            best_option: MAX,
+            best_points: [0; 3],
+            cota: MAX,
+            centrales: 0,
+            fixed_points: fixed_points.max(3),
+        }
+    }
+
+    pub fn start(&mut self) -> BestPoint {
+        let n = self.puntos.len();
+        self.best_option = MAX;
+        self.best_points = [0; 3];
+        self.cota = MAX;
+        self.centrales = 0;
+
+        if n < 3 {
+            return MAX;
+        }
+        if n < MIN_PUNTOS {
+            return self.exacto();
+        }
+
+        // Cada punto entra con probabilidad 1 / MUESTRA: log2(MUESTRA) bits
+        // al azar por punto, todos a cero. En orden, asi que la muestra sigue
+        // ordenada por x.
+        let bits = MUESTRA.trailing_zeros();
+        let por_numero = (u64::BITS / bits) as usize;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(n as u64);
+        let mut indices = Vec::with_capacity(2 * n / MUESTRA);
+        for trozo in (0..n).step_by(por_numero) {
+            let mut azar = rng.gen::<u64>();
+            for i in trozo..(trozo + por_numero).min(n) {
+                if azar & (MUESTRA as u64 - 1) == 0 {
+                    indices.push(i);
+                }
+                azar >>= bits;
+            }
+        }
+        let muestra: Vec<Punto> = indices.iter().map(|&i| self.puntos[i]).collect();
+
+        let mut dyv = DyV::new_with_fixed(&muestra, self.fixed_points);
+        self.best_option = dyv.start();
+        self.best_points = dyv.get_points().map(|i| indices[i]);
+
+        if self.best_option == 0.0 {
+            self.cota = 0.0;
+            return self.best_option;
+        }
+
+        let alto = self.puntos.iter().fold((MAX, f64::MIN), |(min, max), p| {
+            (min.min(p.y), max.max(p.y))
+        });
+        let mut radio = self.best_option / (1 << PASOS) as f64;
+        loop {
+            let objetivo = self.best_option / (1.0 + self.eps);
+            let ultima = radio >= objetivo;
+            let radio_pasada = radio.min(objetivo);
+            if !self.cuadricula(radio_pasada, alto) {
+                return self.exacto();
+            }
+
+            if self.best_option < radio_pasada {
+                self.cota = self.best_option;
+                return self.best_option;
+            }
+            if ultima {
+                self.cota = radio_pasada;
+                return self.best_option;
+            }
+            radio *= 4.0;
+        }
+    }
+
+    // Cota inferior demostrada del optimo exacto
+    pub fn lower_bound(&self) -> f64 {
+        self.cota
+    }
+
+    // Indices del camino en orden de recorrido
+    pub fn get_points(&self) -> [usize; 3] {
+        self.best_points
+    }
+
+    pub fn centrales(&self) -> usize {
+        self.centrales
+    }
+
+    fn exacto(&mut self) -> BestPoint {
+        let mut dyv = DyV::new_with_fixed(self.puntos, self.fixed_points);
+        self.best_option = dyv.start();
+        self.best_points = dyv.get_points();
+        self.cota = self.best_option;
+        self.best_option
+    }
+
+    // Todos los caminos de longitud < radio. Las columnas de la cuadricula son
+    // rangos contiguos del slice ordenado por x; de cada una solo se guarda
+    // cuantos puntos hay en cada fila, y solo de la anterior, la actual y la
+    // siguiente. false si la cuadricula o la busqueda se van de presupuesto.
+    fn cuadricula(&mut self, radio: f64, (min_y, max_y): (f64, f64)) -> bool {
+        let puntos = self.puntos;
+        // Un poco mas que radio para que el redondeo no separe en dos
+        // columnas (o filas) de distancia dos puntos a menos de radio
+        let lado = radio * (1.0 + 1e-9);
+        let inverso = 1.0 / lado;
+        let alto = (max_y - min_y) * inverso;
+        // Con un radio subnormal el inverso no es finito y alto tampoco
+        if !alto.is_finite() || alto >= MAX_FILAS as f64 {
+            return false;
+        }
+        let filas = alto as usize + 3;
+        // Con una fila vacia por arriba y otra por abajo
+        let fila = |p: &Punto| ((p.y - min_y) * inverso) as usize + 1;
+        let columna = |p: &Punto| (p.x * inverso).floor() as i64;
+
+        let mut inicio = 0;
+        let mut siguiente_columna = || {
+            let x = columna(puntos.get(inicio)?);
+            let fin = inicio
+                + puntos[inicio..]
+                    .iter()
+                    .take_while(|p| columna(p) == x)
+                    .count();
+            let rango = inicio..fin;
+            inicio = fin;
+            Some((x, rango))
+        };
+
+        // Cuantos puntos hay en cada fila de la columna anterior, la actual y
+        // la siguiente. Van juntas en la misma fila para que mirar un bloque
+        // sea leer una sola linea de cache; la columna k usa el byte k % 3.
+        let mut cuentas = vec![[0u8; 4]; filas];
+        let mut columnas: [Option<(i64, Range<usize>)>; 3] = [None, None, None];
+        // La fila de cada punto de esas tres columnas
+        let mut filas_de: [Vec<usize>; 3] = Default::default();
+        let mut presupuesto = PRESUPUESTO * puntos.len();
+        for k in 0.. {
+            // Tras rotar, columnas[c] es la columna k + c. La que sale deja
+            // libre su byte para la que entra.
+            let hueco = |c: usize| (k + c) % 3;
+            for &f in &filas_de[0] {
+                cuentas[f][hueco(2)] = 0;
+            }
+            columnas.rotate_left(1);
+            filas_de.rotate_left(1);
+            columnas[2] = siguiente_columna();
+            filas_de[2].clear();
+            if let Some((_, rango)) = &columnas[2] {
+                filas_de[2].extend(puntos[rango.clone()].iter().map(fila));
+                for &f in &filas_de[2] {
+                    let cuenta = &mut cuentas[f][hueco(2)];
+                    *cuenta = cuenta.saturating_add(1);
+                }
+            }
+
+            // En la primera vuelta aun no hay columna actual
+            let Some((x, rango)) = columnas[1].clone() else {
+                if columnas[2].is_some() {
+                    continue;
+                }
+                break;
+            };
+            // Las columnas vacias no estan: la de al lado puede estar lejos
+            let mut mascara = 0u32;
+            for (c, columna) in columnas.iter().enumerate() {
+                if matches!(columna, Some((v, _)) if v.abs_diff(x) <= 1) {
+                    mascara |= 0xff << (8 * hueco(c));
+                }
+            }
+
+            for (j, &f) in rango.zip(&filas_de[1]) {
+                let bloque: u32 = cuentas[f - 1..=f + 1]
+                    .iter()
+                    .map(|cuenta| u32::from_le_bytes(*cuenta) & mascara)
+                    .map(|c| (c & 0xff) + (c >> 8 & 0xff) + (c >> 16))
+                    .sum();
+                if bloque < 3 {
+                    continue;
+                }
+                match presupuesto.checked_sub(self.central(j, radio)) {
+                    Some(resto) => presupuesto = resto,
+                    None => return false,
+                }
+            }
+        }
+        true
+    }
+
+    // El mejor camino con j en el centro son sus dos vecinos mas cercanos; si
+    // mide menos que el radio los dos estan a menos de radio en x y en y.
+    // Devuelve cuantos puntos ha mirado.
+    fn central(&mut self, j: usize, radio: f64) -> usize {
+        self.centrales += 1;
+        stats::cuenta(Contador::I);
+
+        let punto_j = &self.puntos[j];
+        // Hacia los lados y no con partition_point: la ventana es pequeña y
+        // esta al lado, la busqueda binaria salta por todo el slice
+        let mut desde = j;
+        while desde > 0 && punto_j.x - self.puntos[desde - 1].x < radio {
+            desde -= 1;
+        }
+        let mut hasta = j + 1;
+        while hasta < self.puntos.len() && self.puntos[hasta].x - punto_j.x < radio {
+            hasta += 1;
+        }
+
+        let (mut primero, mut segundo) = ((MAX, j), (MAX, j));
+        for (i, punto) in self.puntos[desde..hasta].iter().enumerate() {
+            if (punto.y - punto_j.y).abs() >= radio || i + desde == j {
+                continue;
+            }
+            stats::cuenta(Contador::J);
+            let distancia = punto_j.distancia(punto);
+            if distancia < primero.0 {
+                segundo = primero;
+                primero = (distancia, i + desde);
+            } else if distancia < segundo.0 {
+                segundo = (distancia, i + desde);
+            }
+        }
+
+        if primero.0 + segundo.0 < self.best_option {
+            stats::cuenta(Contador::Mejoras);
+            self.best_option = primero.0 + segundo.0;
+            self.best_points = [primero.1, j, segundo.1];
+        }
+        hasta - desde
+    }
+}
@@ -0,0 +1,207 @@
+use crate::punto::*;
+use crate::stats::{self, Contador, Fase};
+
+const FIXED_POINTS: usize = 108;
+const MAX: f64 = f64::MAX;
+const SIN_CLASE: u8 = 3;
+
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orden {
+    // Un punto de cada clase en cualquier orden
+    Cualquiera,
+    // Camino clases[0] - clases[1] - clases[2]
+    Fijo,
+}
+
+// Mejor camino de 3 puntos con exactamente un punto de cada clase.
+// Un camino de longitud L tiene sus tres puntos a menos de L entre si,
+// asi que la poda por franjas en x/y de DyV sigue siendo valida.
+#[allow(unused)]
+pub struct DyVClases<'a, L> {
+    puntos: &'a [PuntoEtiquetado<L>],
+    clases: Vec<u8>,
+    orden: Orden,
+    best_option: BestPoint,
+    best_points: [usize; 3],
+    pub fixed_points: usize,
+}
+
+#[allow(unused)]
+impl<'a, L: PartialEq> DyVClases<'a, L> {
+    pub fn new(puntos: &'a [PuntoEtiquetado<L>], clases: [L; 3], orden: Orden) -> Self {
+        Self::new_with_fixed(puntos, clases, orden, FIXED_POINTS)
+    }
+
+    pub fn new_with_fixed(
+        puntos: &'a [PuntoEtiquetado<L>],
+        clases: [L; 3],
+        orden: Orden,
+        fixed_points: usize,
+    ) -> Self {
+        // Con dos iguales no hay camino con un punto de cada clase
+        assert!(
+            clases[0] != clases[1] && clases[1] != clases[2] && clases[0] != clases[2],
+            "las tres clases tienen que ser distintas"
+        );
+        let clases = puntos
+            .iter()
+            .map(|p| {
+                clases
+                    .iter()
+                    .position(|c| *c == p.etiqueta)
+                    .map_or(SIN_CLASE, |c| c as u8)
+            })
+            .collect();
+
+        Self {
+            puntos,
+            clases,
+            orden,
+            best_option: MAX,
+            best_points: [0; 3],
+            fixed_points: fixed_points.max(3),
+        }
+    }
+
+    pub fn start(&mut self) -> BestPoint {
+        self.divide_venceras(0, self.puntos.len());
+        self.best_option
+    }
+
+    // Indices del camino en orden de recorrido
+    pub fn get_points(&self) -> [usize; 3] {
+        self.best_points
+    }
+
+    #[inline(always)]
+    fn punto(&self, i: usize) -> &'a Punto {
+        &self.puntos[i].punto
+    }
+
+    #[inline(always)]
+    fn evalua(&mut self, i: usize, j: usize, k: usize) {
+        let (ci, cj, ck) = (self.clases[i], self.clases[j], self.clases[k]);
+        if ck == SIN_CLASE || ck == ci || ck == cj {
+            return;
+        }
+
+        let (punto_i, punto_j, punto_k) = (self.punto(i), self.punto(j), self.punto(k));
+
+        match self.orden {
+            Orden::Cualquiera => {
+                let (ij, jk, ki) = (
+                    punto_i.distancia(punto_j),
+                    punto_j.distancia(punto_k),
+                    punto_k.distancia(punto_i),
+                );
+
+                // El punto central es el opuesto a la arista mas larga
+                let (distancia, camino) = if ki >= ij && ki >= jk {
+                    (ij + jk, [i, j, k])
+                } else if ij >= jk {
+                    (jk + ki, [j, k, i])
+                } else {
+                    (ki + ij, [k, i, j])
+                };
+
+                if distancia < self.best_option {
+                    self.best_option = distancia;
+                    stats::cuenta(Contador::Mejoras);
+                    self.best_points = camino;
+                }
+            }
+            Orden::Fijo => {
+                let mut camino = [i; 3];
+                for index in [i, j, k] {
+                    camino[self.clases[index] as usize] = index;
+                }
+
+                let [a, b, c] = camino;
+                let distancia =
+                    self.punto(a).distancia(self.punto(b)) + self.punto(b).distancia(self.punto(c));
+
+                if distancia < self.best_option {
+                    self.best_option = distancia;
+                    stats::cuenta(Contador::Mejoras);
+                    self.best_points = camino;
+                }
+            }
+        }
+    }
+
+    // Triples en [start, end) cuyo punto mas a la izquierda esta en [start, mid)
+    #[inline(always)]
+    fn calcula_fixed_range(&mut self, start: usize, end: usize, mid: usize) {
+        stats::suma(Contador::I, (mid - start) as u64);
+        for i in start..mid {
+            let ci = self.clases[i];
+            if ci == SIN_CLASE {
+                continue;
+            }
+            let punto_i = self.punto(i);
+
+            for j in i + 1..end {
+                let punto_j = self.punto(j);
+                if (punto_j.x - punto_i.x) >= self.best_option {
+                    stats::cuenta(Contador::CortesX);
+                    break;
+                }
+
+                let cj = self.clases[j];
+                if cj == SIN_CLASE || cj == ci {
+                    continue;
+                }
+                if (punto_j.y - punto_i.y).abs() >= self.best_option {
+                    stats::cuenta(Contador::RechazosY);
+                    continue;
+                }
+                stats::cuenta(Contador::J);
+
+                for k in j + 1..end {
+                    let punto_k = self.punto(k);
+                    if (punto_k.x - punto_i.x) >= self.best_option {
+                        break;
+                    }
+
+                    stats::cuenta(Contador::K);
+                    if (punto_k.y - punto_i.y).abs() >= self.best_option {
+                        stats::cuenta(Contador::RechazosY);
+                        continue;
+                    }
+
+                    self.evalua(i, j, k);
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn calcula_fixed(&mut self, start: usize, end: usize) {
+        self.calcula_fixed_range(start, end, end)
+    }
+
+    fn divide_venceras(&mut self, start: usize, end: usize) {
+        if end - start < self.fixed_points {
+            return self.calcula_fixed(start, end);
+        }
+
+        let mitad_index = start + (end - start) / 2;
+        self.divide_venceras(start, mitad_index);
+        self.divide_venceras(mitad_index, end);
+
+        self.recheck_actual_best(start, end, mitad_index);
+    }
+
+    fn recheck_actual_best(&mut self, start: usize, end: usize, mitad_index: usize) {
+        let mitad = self.punto(mitad_index).x;
+        let slice = &self.puntos[start..end];
+
+        let new_start = start + slice.partition_point(|p| p.punto.x <= mitad - self.best_option);
+        let new_end = start + slice.partition_point(|p| p.punto.x < mitad + self.best_option);
+
+        let _fase = stats::en_fase(Fase::Franja);
+        stats::franja(new_end - new_start);
+        self.calcula_fixed_range(new_start, new_end, mitad_index.max(new_start));
+    }
+}
@@ -1,23 +1,25 @@
 use std::sync::RwLock;
 
+use crate::escalar::Escalar;
 use crate::punto::*;
+use crate::stats::{self, Contador, Fase};
 
 const FIXED_POINTS: usize = 126;
 
 const MAX: f64 = f64::MAX;
 
 #[allow(unused)]
-pub struct DyVMT<'a> {
-    puntos: &'a [Punto],
+pub struct DyVMT<'a, T: Escalar = f64> {
+    puntos: &'a [Punto<T>],
     best_option: RwLock<BestPoint>,
     points: RwLock<[usize; 3]>,
     pub fixed_points: usize,
 }
 
 #[allow(unused)]
-impl<'a> DyVMT<'a> {
+impl<'a, T: Escalar> DyVMT<'a, T> {
     #[allow(unused)]
-    pub fn new_with_fixed(puntos: &'a [Punto], fixed_points: usize) -> DyVMT {
+    pub fn new_with_fixed(puntos: &'a [Punto<T>], fixed_points: usize) -> DyVMT<'a, T> {
         DyVMT {
             puntos,
             best_option: RwLock::new(MAX),
@@ -27,48 +29,47 @@ impl<'a> DyVMT<'a> {
     }
 
     #[allow(unused)]
-    pub fn new(puntos: &'a [Punto]) -> DyVMT {
+    pub fn new(puntos: &'a [Punto<T>]) -> DyVMT<'a, T> {
         DyVMT {
             puntos,
             best_option: RwLock::new(MAX),
             points: RwLock::new([0; 3]),
-            fixed_points: FIXED_POINTS,
+            fixed_points: crate::perfil::fixed_points::<T>("dyv_mt", FIXED_POINTS),
         }
     }
 
     pub fn start(&mut self) -> BestPoint {
         let size = self.puntos.len();
-        let mitad: f64 = (self.puntos[0].x + self.puntos[size - 1].x) / 2.0;
+        let mitad: f64 = (self.puntos[0].x.to_f64() + self.puntos[size - 1].x.to_f64()) / 2.0;
 
-        let mitad_index = match self
-            .puntos
-            .binary_search_by(|p| p.x.partial_cmp(&mitad).unwrap())
-        {
-            Ok(index) => index,
-            Err(index) => index - 1,
-        };
+        let mitad_index = Self::indice_mitad(self.puntos, mitad);
 
+        // Cada hilo devuelve lo que ha contado para que stats::lee_hilo() de
+        // quien llama a start() lo incluya
         std::thread::scope(|s| {
             let a = s.spawn(|| {
                 self.divide_venceras(
-                    self.puntos[0].x,
-                    self.puntos[mitad_index - 1].x,
+                    self.puntos[0].x.to_f64(),
+                    self.puntos[mitad_index - 1].x.to_f64(),
                     0,
                     &self.puntos[0..mitad_index],
-                )
+                );
+                stats::lee_hilo()
             });
 
             let b = s.spawn(|| {
                 self.divide_venceras(
-                    self.puntos[mitad_index].x,
-                    self.puntos[size - 1].x,
+                    self.puntos[mitad_index].x.to_f64(),
+                    self.puntos[size - 1].x.to_f64(),
                     mitad_index,
-                    &self.puntos[mitad_index..size - 1],
-                )
+                    &self.puntos[mitad_index..],
+                );
+                stats::lee_hilo()
             });
 
-            let _ = b.join();
-            let _ = a.join();
+            for contado in [b.join(), a.join()].into_iter().flatten() {
+                stats::anade_hilo(&contado);
+            }
         });
 
         /*
@@ -82,47 +83,38 @@ impl<'a> DyVMT<'a> {
 
         let best = *self.best_option.read().unwrap();
         self.recheck_actual_best(
-            self.puntos[mitad_index].x + best,
-            self.puntos[mitad_index].x - best,
+            self.puntos[mitad_index].x.to_f64() + best,
+            self.puntos[mitad_index].x.to_f64() - best,
             0,
             self.puntos,
         );
         *self.best_option.read().unwrap()
     }
 
-    fn calcula_fixed(&self, start: usize, end: usize) {
-        let mut best_option_cache = *self.best_option.read().unwrap();
-        let mut points = [0, 0, 0];
-
-        for i in start..end {
-            let punto_i = &self.puntos[i];
-
-            for j in (start..i).chain(i + 1..end) {
-                let punto_j = &self.puntos[j];
-                let distancia_ij = punto_i.distancia(punto_j);
-
-                if distancia_ij >= best_option_cache {
-                    continue;
+    // i en start..mid; en la franja mid es el primer punto a la derecha del
+    // corte, un trio que lo cruza tiene a la izquierda el de menor indice
+    fn calcula_fixed(&self, start: usize, mid: usize, end: usize) {
+        let mut mejor_hoja = (*self.best_option.read().unwrap(), [0, 0, 0]);
+        stats::suma(Contador::I, (mid - start) as u64);
+
+        for i in start..mid {
+            // j a los dos lados de i, alejandose: cada lado se corta cuando la
+            // x de j ya esta a best de la de i
+            for j in (start..i).rev() {
+                if !self.prueba_j(i, j, end, &mut mejor_hoja) {
+                    break;
                 }
-
-                let mut mejor = best_option_cache - distancia_ij;
-                for k in i + 1..end {
-                    if k == j {
-                        continue;
-                    }
-
-                    let punto_k = &self.puntos[k];
-                    let distancia_jk = punto_j.distancia(punto_k);
-
-                    if distancia_jk < mejor {
-                        mejor = distancia_jk;
-                        points = [i, j, k];
-                    }
+            }
+            for j in i + 1..end {
+                if !self.prueba_j(i, j, end, &mut mejor_hoja) {
+                    break;
                 }
-                best_option_cache = distancia_ij + mejor;
             }
         }
+        // Lo contado en la hoja pasa a los globales de una vez
+        stats::vuelca();
 
+        let (best_option_cache, points) = mejor_hoja;
         let mut best_option_lock = self.best_option.write().unwrap();
         if best_option_cache < *best_option_lock {
             *best_option_lock = best_option_cache;
@@ -130,55 +122,132 @@ impl<'a> DyVMT<'a> {
         }
     }
 
-    fn divide_venceras(&self, start: f64, end: f64, offset: usize, s_slice: &[Punto]) {
+    // Caminos i-j-k con k a la derecha de i. false si j esta a best o mas en
+    // x, y con el los que quedan en ese lado
+    #[inline(always)]
+    fn prueba_j(&self, i: usize, j: usize, end: usize, hoja: &mut (f64, [usize; 3])) -> bool {
+        let (best, points) = hoja;
+        let (punto_i, punto_j) = (&self.puntos[i], &self.puntos[j]);
+        if punto_j.x.diferencia(punto_i.x).abs() >= *best {
+            stats::cuenta(Contador::CortesX);
+            return false;
+        }
+        // Como get_next_point de DyV: con las x iguales la franja es toda la
+        // columna y esto es lo que deja fuera a los lejanos en y
+        if punto_j.y.diferencia(punto_i.y).abs() >= *best {
+            stats::cuenta(Contador::RechazosY);
+            return true;
+        }
+        stats::cuenta(Contador::J);
+        let distancia_ij = punto_i.distancia(punto_j);
+
+        if distancia_ij >= *best {
+            return true;
+        }
+
+        let mut mejor = *best - distancia_ij;
+        for k in i + 1..end {
+            let punto_k = &self.puntos[k];
+            // d(i, k) <= d(i, j) + d(j, k), y de aqui en adelante la x solo crece
+            if punto_k.x.diferencia(punto_i.x) >= *best {
+                break;
+            }
+            if k == j {
+                continue;
+            }
+
+            stats::cuenta(Contador::K);
+            // Lejos en y de j y de i: no mejora ninguno de los dos caminos
+            if punto_k.y.diferencia(punto_j.y).abs() >= mejor
+                && punto_k.y.diferencia(punto_i.y).abs() >= mejor
+            {
+                stats::cuenta(Contador::RechazosY);
+                continue;
+            }
+            let distancia_jk = punto_j.distancia(punto_k);
+            let distancia_ik = punto_i.distancia(punto_k);
+
+            if distancia_jk < mejor {
+                // Baja el best_option de este calcula_fixed
+                stats::cuenta(Contador::Mejoras);
+                mejor = distancia_jk;
+                *best = distancia_ij + distancia_jk;
+                *points = [i, j, k];
+            }
+            // El mismo trio con i en el centro, como en DyV
+            if distancia_ik < mejor {
+                stats::cuenta(Contador::Mejoras);
+                mejor = distancia_ik;
+                *best = distancia_ij + distancia_ik;
+                *points = [j, i, k];
+            }
+        }
+        true
+    }
+
+    fn divide_venceras(&self, start: f64, end: f64, offset: usize, s_slice: &[Punto<T>]) {
         // let (mut start_index, mut end_index) = self.get_points_between(start, end, s_slice);
 
         let start_index = 0;
         let end_index = s_slice.len() - 1;
 
         if s_slice.len() < self.fixed_points {
-            return self.calcula_fixed(offset, end_index + offset + 1);
+            return self.calcula_fixed(offset, end_index + offset + 1, end_index + offset + 1);
         }
 
         //assert!(end_index <= self.puntos.len());
 
         let mitad: f64 = (start + end) / 2.0;
-        let mitad_index = match s_slice.binary_search_by(|p| p.x.partial_cmp(&mitad).unwrap()) {
-            Ok(index) => index,
-            Err(index) => index - 1,
-        };
+        let mitad_index = Self::indice_mitad(s_slice, mitad);
 
         // let offset = start_index + offset;
-        self.divide_venceras(start, mitad, offset, &s_slice[start_index..mitad_index]);
+        self.divide_venceras(
+            start,
+            s_slice[mitad_index - 1].x.to_f64(),
+            offset,
+            &s_slice[start_index..mitad_index],
+        );
 
         self.divide_venceras(
-            mitad,
+            s_slice[mitad_index].x.to_f64(),
             end,
             mitad_index + offset,
-            &s_slice[mitad_index..end_index],
+            &s_slice[mitad_index..],
         );
 
         self.recheck_actual_best(end, start, offset, s_slice);
     }
 
-    fn recheck_actual_best(&self, end: f64, start: f64, offset: usize, s_slice: &[Punto]) {
+    // Primer punto con x >= mitad. Si caen todos del mismo lado es que todas
+    // las x son iguales y se parte por la mitad de los puntos; la franja
+    // alrededor de `mitad` los cubre a todos igualmente.
+    // `start` y `end` de divide_venceras son siempre la primera y la ultima x
+    // del slice, asi las dos mitades quedan a distinto lado de `mitad`.
+    fn indice_mitad(s_slice: &[Punto<T>], mitad: f64) -> usize {
+        match s_slice.partition_point(|p| p.x.to_f64() < mitad) {
+            0 => s_slice.len() / 2,
+            index if index == s_slice.len() => s_slice.len() / 2,
+            index => index,
+        }
+    }
+
+    fn recheck_actual_best(&self, end: f64, start: f64, offset: usize, s_slice: &[Punto<T>]) {
         let mitad: f64 = (start + end) / 2.0;
         let best_option = *self.best_option.read().unwrap();
         let (new_start, new_end) =
             self.get_points_between(mitad - best_option, mitad + best_option, s_slice);
 
-        self.calcula_fixed(new_start + offset, new_end + offset + 1);
+        // El mismo corte que en divide_venceras
+        let corte = Self::indice_mitad(s_slice, mitad).clamp(new_start, new_end + 1);
+
+        let _fase = stats::en_fase(Fase::Franja);
+        stats::franja(new_end + 1 - new_start);
+        self.calcula_fixed(new_start + offset, corte + offset, new_end + offset + 1);
     }
 
-    fn get_points_between(&self, start: f64, end: f64, puntos: &[Punto]) -> (usize, usize) {
-        let start_index = match puntos.binary_search_by(|p| p.x.partial_cmp(&start).unwrap()) {
-            Ok(index) => index,
-            Err(index) => index,
-        };
-        let end_index = match puntos.binary_search_by(|p| p.x.partial_cmp(&end).unwrap()) {
-            Ok(index) => index,
-            Err(index) => index - 1,
-        };
+    fn get_points_between(&self, start: f64, end: f64, puntos: &[Punto<T>]) -> (usize, usize) {
+        let start_index = puntos.partition_point(|p| p.x_comparef64(&start).is_lt());
+        let end_index = puntos.partition_point(|p| p.x_comparef64(&end).is_le()) - 1;
 
         (start_index, end_index)
     }
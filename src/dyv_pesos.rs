@@ -0,0 +1,185 @@
+use crate::punto::*;
+use crate::stats::{self, Contador, Fase};
+
+const FIXED_POINTS: usize = 108;
+const MAX: f64 = f64::MAX;
+
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Objetivo {
+    // d(a, b) + d(b, c) + w_a + w_b + w_c, con pesos >= 0
+    Aditivo,
+    // d(a, b) * (w_a + w_b) / 2 + d(b, c) * (w_b + w_c) / 2, con pesos > 0
+    Escalado,
+}
+
+// Mejor camino de 3 puntos con pesos por vertice. Con el peso minimo w_min
+// la longitud geometrica de un camino de coste C esta acotada por
+// C - 3 * w_min (aditivo) o C / w_min (escalado), y esa cota sustituye a
+// best_option en las ventanas de x/y.
+#[allow(unused)]
+pub struct DyVPesos<'a> {
+    puntos: &'a [PuntoPesado],
+    objetivo: Objetivo,
+    min_peso: f64,
+    best_option: BestPoint,
+    best_points: [usize; 3],
+    pub fixed_points: usize,
+}
+
+#[allow(unused)]
+impl<'a> DyVPesos<'a> {
+    pub fn new(puntos: &'a [PuntoPesado], objetivo: Objetivo) -> Self {
+        Self::new_with_fixed(puntos, objetivo, FIXED_POINTS)
+    }
+
+    pub fn new_with_fixed(
+        puntos: &'a [PuntoPesado],
+        objetivo: Objetivo,
+        fixed_points: usize,
+    ) -> Self {
+        let min_peso = puntos.iter().map(|p| p.etiqueta).fold(MAX, f64::min);
+
+        match objetivo {
+            Objetivo::Aditivo => assert!(min_peso >= 0.0, "pesos negativos: {min_peso}"),
+            Objetivo::Escalado => assert!(min_peso > 0.0, "pesos no positivos: {min_peso}"),
+        }
+
+        Self {
+            puntos,
+            objetivo,
+            min_peso,
+            best_option: MAX,
+            best_points: [0; 3],
+            fixed_points: fixed_points.max(3),
+        }
+    }
+
+    pub fn start(&mut self) -> BestPoint {
+        self.divide_venceras(0, self.puntos.len());
+        self.best_option
+    }
+
+    // Indices del camino en orden de recorrido
+    pub fn get_points(&self) -> [usize; 3] {
+        self.best_points
+    }
+
+    // Cota de la longitud geometrica de cualquier camino que mejore best_option
+    #[inline(always)]
+    fn radio(&self) -> f64 {
+        if self.best_option == MAX {
+            return MAX;
+        }
+
+        match self.objetivo {
+            Objetivo::Aditivo => self.best_option - 3.0 * self.min_peso,
+            Objetivo::Escalado => self.best_option / self.min_peso,
+        }
+    }
+
+    #[inline(always)]
+    fn arista(&self, a: &PuntoPesado, b: &PuntoPesado) -> f64 {
+        let distancia = a.punto.distancia(&b.punto);
+        match self.objetivo {
+            Objetivo::Aditivo => distancia,
+            Objetivo::Escalado => distancia * (a.etiqueta + b.etiqueta) / 2.0,
+        }
+    }
+
+    #[inline(always)]
+    fn evalua(&mut self, i: usize, j: usize, k: usize) {
+        let (punto_i, punto_j, punto_k) = (&self.puntos[i], &self.puntos[j], &self.puntos[k]);
+        let (ij, jk, ki) = (
+            self.arista(punto_i, punto_j),
+            self.arista(punto_j, punto_k),
+            self.arista(punto_k, punto_i),
+        );
+
+        let vertices = match self.objetivo {
+            Objetivo::Aditivo => punto_i.etiqueta + punto_j.etiqueta + punto_k.etiqueta,
+            Objetivo::Escalado => 0.0,
+        };
+
+        for (distancia, camino) in [
+            (ij + jk, [i, j, k]),
+            (jk + ki, [j, k, i]),
+            (ki + ij, [k, i, j]),
+        ] {
+            let distancia = distancia + vertices;
+            if distancia < self.best_option {
+                self.best_option = distancia;
+                stats::cuenta(Contador::Mejoras);
+                self.best_points = camino;
+            }
+        }
+    }
+
+    // Triples en [start, end) cuyo punto mas a la izquierda esta en [start, mid)
+    #[inline(always)]
+    fn calcula_fixed_range(&mut self, start: usize, end: usize, mid: usize) {
+        stats::suma(Contador::I, (mid - start) as u64);
+        for i in start..mid {
+            let punto_i = self.puntos[i].punto;
+
+            for j in i + 1..end {
+                let punto_j = self.puntos[j].punto;
+                if (punto_j.x - punto_i.x) >= self.radio() {
+                    stats::cuenta(Contador::CortesX);
+                    break;
+                }
+
+                if (punto_j.y - punto_i.y).abs() >= self.radio() {
+                    stats::cuenta(Contador::RechazosY);
+                    continue;
+                }
+                stats::cuenta(Contador::J);
+
+                for k in j + 1..end {
+                    let punto_k = self.puntos[k].punto;
+                    if (punto_k.x - punto_i.x) >= self.radio() {
+                        break;
+                    }
+
+                    stats::cuenta(Contador::K);
+                    if (punto_k.y - punto_i.y).abs() >= self.radio() {
+                        stats::cuenta(Contador::RechazosY);
+                        continue;
+                    }
+
+                    self.evalua(i, j, k);
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn calcula_fixed(&mut self, start: usize, end: usize) {
+        self.calcula_fixed_range(start, end, end)
+    }
+
+    fn divide_venceras(&mut self, start: usize, end: usize) {
+        if end - start < self.fixed_points {
+            return self.calcula_fixed(start, end);
+        }
+
+        let mitad_index = start + (end - start) / 2;
+        self.divide_venceras(start, mitad_index);
+        self.divide_venceras(mitad_index, end);
+
+        self.recheck_actual_best(start, end, mitad_index);
+    }
+
+    fn recheck_actual_best(&mut self, start: usize, end: usize, mitad_index: usize) {
+        let mitad = self.puntos[mitad_index].punto.x;
+        let radio = self.radio();
+        let slice = &self.puntos[start..end];
+
+        let new_start = start + slice.partition_point(|p| p.punto.x <= mitad - radio);
+        let new_end = start + slice.partition_point(|p| p.punto.x < mitad + radio);
+
+        let _fase = stats::en_fase(Fase::Franja);
+        stats::franja(new_end - new_start);
+        self.calcula_fixed_range(new_start, new_end, mitad_index.max(new_start));
+    }
+}
@@ -1,253 +1,262 @@
+use crate::escalar::{Escalar, PorCarriles};
+use crate::kernel::Kernel;
 use crate::punto::*;
+use crate::soa::PuntosSoa;
+use crate::stats::{self, Contador, Fase};
 
 const FIXED_POINTS: usize = 98;
 const MAX: f64 = f64::MAX;
 
 #[allow(unused)]
-pub struct DyVSIMD<'a> {
-    puntos: &'a [Punto],
+pub struct DyVSIMD<'a, T: Escalar = f64> {
+    puntos: &'a [Punto<T>],
+    soa: PuntosSoa<T>,
     best_option: BestPoint,
-    best_points: [Punto; 3],
+    // Indices en puntos, en el orden del camino
+    best_points: [usize; 3],
     pub fixed_points: usize,
+    // Por defecto el mejor de la CPU; se puede forzar otro antes de start()
+    pub kernel: Kernel,
     f_cf: bool,
 }
 
 #[allow(unused)]
-impl<'a> DyVSIMD<'a> {
+impl<'a, T: Escalar> DyVSIMD<'a, T> {
     #[allow(unused)]
-    pub fn new_with_fixed(puntos: &'a [Punto], fixed_points: usize) -> Self {
+    pub fn new_with_fixed(puntos: &'a [Punto<T>], fixed_points: usize) -> Self {
         Self {
             puntos,
+            soa: PuntosSoa::new(puntos),
             best_option: MAX,
-            best_points: [Punto::default(), Punto::default(), Punto::default()],
+            best_points: [0, 0, 0],
             fixed_points,
+            kernel: Kernel::detecta(),
             f_cf: false,
         }
     }
 
     #[allow(unused)]
-    pub fn new(puntos: &'a [Punto]) -> Self {
+    pub fn new(puntos: &'a [Punto<T>]) -> Self {
         Self {
             puntos,
+            soa: PuntosSoa::new(puntos),
+            // Cota inicial con el camino 1-0-2, que puede ser ya el mejor
             best_option: puntos[0].distancia3(&puntos[1], &puntos[2]),
-            best_points: [Punto::default(), Punto::default(), Punto::default()],
-            fixed_points: FIXED_POINTS,
+            best_points: [1, 0, 2],
+            fixed_points: crate::perfil::fixed_points::<T>("dyv_simd", FIXED_POINTS),
+            kernel: Kernel::detecta(),
             f_cf: false,
         }
     }
 
     pub fn start(&mut self) -> BestPoint {
-        self.divide_venceras_it();
+        // Ejecutar instrucciones que la CPU no tiene es UB, no un error
+        assert!(
+            self.kernel.disponible(),
+            "el kernel {} no esta disponible en esta CPU",
+            self.kernel
+        );
+        T::con_carriles(self.kernel, Corrida(self));
         self.best_option
     }
 
-    #[inline]
-    fn get_next_point(
-        &'a self,
-        puntos: &'a [Punto],
-        punto_i: &'a Punto,
-        mut start: usize,
-    ) -> Option<usize> {
-        use std::ops::Not;
-        use std::simd::cmp::SimdPartialOrd;
-        use std::simd::prelude::SimdFloat;
-        use std::simd::{f64x4, f64x8};
+    // El mismo divide_venceras_it compilado para cada juego de instrucciones:
+    // todo lo que hay debajo es inline(always) y hereda el target_feature
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    fn divide_venceras_avx2<const N: usize>(&mut self) {
+        self.divide_venceras_it::<N>()
+    }
 
-        let vec_punto_i = f64x4::splat(punto_i.y);
-        let vec_distancia = f64x4::splat(self.best_option);
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx512f")]
+    fn divide_venceras_avx512<const N: usize>(&mut self) {
+        self.divide_venceras_it::<N>()
+    }
 
-        let mut it = puntos[start..].array_chunks::<8>();
-        let max_x = punto_i.x + self.best_option;
+    // Los puntos con y a menos de best_option del central, en start..end
+    #[inline(always)]
+    fn get_next_point<const N: usize>(
+        &self,
+        punto_i: usize,
+        start: usize,
+        end: usize,
+    ) -> Option<usize> {
+        // Vectorizado en escalar.rs, N carriles segun el tipo y el kernel
+        let siguiente =
+            T::siguiente_en_ventana::<N>(&self.soa, punto_i, self.best_option, start, end);
+        // Los CortesX los cuenta siguiente_en_ventana, que es donde se corta
+        if let Some(j) = siguiente {
+            stats::cuenta(Contador::J);
+            stats::suma(Contador::RechazosY, (j - start) as u64);
+        }
+        siguiente
+    }
 
-        while let Some(chunk) = it.next() {
-            //for chunk in it.by_ref() {
-            if chunk[0].x >= max_x {
-                return None;
+    // i y k hasta end, con i < k y j cualquiera de los dos extremos. Los k se
+    // filtran de N en N, con las distancias en vectores si el tipo las tiene,
+    // y los que podrian mejorar pasan en orden por la actualizacion escalar,
+    // que da el mismo resultado que ir k a k.
+    #[inline(always)]
+    fn calcula_k<const N: usize>(&mut self, i: usize, j: usize, distancia_ij: f64, end: usize) {
+        let punto_i = self.soa.punto(i);
+        let punto_j = self.soa.punto(j);
+        let mut mejor = self.best_option - distancia_ij;
+        let mut visitados = 0;
+
+        T::candidatos_k::<N>(&self.soa, i, j, self.best_option, i + 1, end, |k| {
+            if k == j {
+                return self.best_option;
+            }
+            visitados += 1;
+            let punto_k = self.soa.punto(k);
+            // El filtro se hizo con un best_option que puede haber bajado
+            if punto_k.y.diferencia(punto_i.y).abs() >= self.best_option
+                && punto_k.y.diferencia(punto_j.y).abs() >= self.best_option
+            {
+                stats::cuenta(Contador::RechazosY);
+                return self.best_option;
             }
 
-            let vector_y = f64x4::from_array([chunk[0].y, chunk[1].y, chunk[2].y, chunk[3].y]);
-            let res = (vector_y - vec_punto_i).abs().simd_le(vec_distancia);
-            match res.first_set() {
-                None => start += 4,
-                Some(i) => return Some(start + i),
+            let mut distancia_jk = punto_j.distancia(&punto_k);
+
+            let distancia_jik = distancia_ij + punto_i.distancia(&punto_k);
+
+            if distancia_jk < mejor {
+                distancia_jk += distancia_ij;
+                self.best_option = distancia_jk;
+                stats::cuenta(Contador::Mejoras);
+                mejor = self.best_option - distancia_ij;
+                self.best_points = [i, j, k];
             }
-            // Unroll
 
-            let vector_y = f64x4::from_array([chunk[4].y, chunk[5].y, chunk[6].y, chunk[7].y]);
-            let res = (vector_y - vec_punto_i).abs().simd_le(vec_distancia);
-            match res.first_set() {
-                None => start += 4,
-                Some(i) => return Some(start + i),
+            if distancia_jik < self.best_option {
+                self.best_option = distancia_jik;
+                stats::cuenta(Contador::Mejoras);
+                mejor = self.best_option - distancia_ij;
+                self.best_points = [j, i, k];
             }
-        }
+            self.best_option
+        });
 
-        it.remainder()
-            .iter()
-            .position(|punto_y| {
-                (punto_y.y - punto_i.y).abs() < self.best_option
-                    && (punto_y.x - punto_i.x).abs() < self.best_option
-            })
-            .map(|val| val + start)
+        // Los que no ha visitado los ha quitado el filtro vectorial
+        if stats::ACTIVAS {
+            stats::suma(Contador::K, visitados);
+            stats::suma(Contador::RechazosY, (end - i - 2) as u64 - visitados);
+        }
     }
 
+    // i en la mitad izquierda start..mid; j y k en toda la franja
     #[inline(always)]
-    fn calcula_fixed_range(&mut self, slice: &'a [Punto], mid: usize) {
-        let (f_mid, s_half) = slice.split_at(mid);
-        for (i, punto_i) in f_mid.iter().enumerate() {
+    fn calcula_fixed_range<const N: usize>(&mut self, start: usize, end: usize, mid: usize) {
+        stats::suma(Contador::I, (mid - start) as u64);
+        for i in start..mid {
             let mut j = i + 1;
 
-            while let Some(punto_j_index) = self.get_next_point(slice, punto_i, j) {
-                let punto_j: &'a Punto = &slice[punto_j_index];
+            while let Some(punto_j) = self.get_next_point::<N>(i, j, end) {
+                j = punto_j + 1;
 
-                j = punto_j_index + 1;
-
-                let distancia_ij = punto_i.distancia(punto_j);
+                let distancia_ij = self.soa.punto(i).distancia(&self.soa.punto(punto_j));
 
                 if distancia_ij >= self.best_option {
                     continue;
                 }
 
-                let mut mejor = self.best_option - distancia_ij;
-
-                for punto_k in s_half.iter() {
-                    if (punto_k.y - punto_i.y).abs() >= self.best_option
-                        && (punto_k.y - punto_j.y).abs() >= self.best_option
-                    {
-                        continue;
-                    }
-
-                    let mut distancia_jk = punto_j.distancia(punto_k);
-
-                    let distancia_jik = distancia_ij + punto_i.distancia(punto_k);
-
-                    if distancia_jk < mejor {
-                        if punto_k.x_eq(punto_j) {
-                            continue;
-                        }
-                        distancia_jk += distancia_ij;
-                        self.best_option = distancia_jk;
-                        mejor = self.best_option - distancia_ij;
-                        self.best_points = [*punto_j, *punto_i, *punto_k];
-                    }
-
-                    if distancia_jik < self.best_option {
-                        self.best_option = distancia_jik;
-                        self.best_points = [*punto_i, *punto_j, *punto_k];
-                    }
-                }
+                // k tambien en la mitad izquierda: i y k a la izquierda con
+                // el central j a la derecha
+                self.calcula_k::<N>(i, punto_j, distancia_ij, end);
             }
         }
     }
 
     #[inline(always)]
-    fn calcula_fixed(&mut self, slice: &'a [Punto]) {
-        let mut i = 0;
-        for punto_i in slice.iter() {
+    fn calcula_fixed<const N: usize>(&mut self, start: usize, end: usize) {
+        stats::suma(Contador::I, (end - start) as u64);
+        for i in start..end {
             let mut j = i + 1;
 
-            while let Some(punto_j_index) = self.get_next_point(slice, punto_i, j) {
-                let punto_j: &'a Punto = unsafe { slice.get_unchecked(punto_j_index) }; //&slice[punto_j_index];
+            while let Some(punto_j) = self.get_next_point::<N>(i, j, end) {
+                j = 1 + punto_j;
 
-                j = 1 + punto_j_index;
-
-                if (punto_j.x - punto_i.x) >= self.best_option {
-                    break;
-                }
-
-                let distancia_ij = punto_i.distancia(punto_j);
+                let distancia_ij = self.soa.punto(i).distancia(&self.soa.punto(punto_j));
 
                 if distancia_ij >= self.best_option {
                     continue;
                 }
 
-                let mut mejor = self.best_option - distancia_ij;
-
-                for punto_k in slice.iter().skip(i + 1)
-                //.filter(|punto_k| !punto_k.x_eq(punto_j))
-                {
-                    if (punto_k.y - punto_i.y).abs() >= self.best_option
-                        && (punto_k.y - punto_j.y).abs() >= self.best_option
-                    {
-                        continue;
-                    }
-
-                    let mut distancia_jk = punto_j.distancia(punto_k);
-
-                    let distancia_jik = distancia_ij + punto_i.distancia(punto_k);
-
-                    if distancia_jk < mejor {
-                        if punto_k.x_eq(punto_j) {
-                            continue;
-                        }
-                        distancia_jk += distancia_ij;
-                        self.best_option = distancia_jk;
-                        mejor = self.best_option - distancia_ij;
-                        self.best_points = [*punto_j, *punto_i, *punto_k];
-                    }
-
-                    if distancia_jik < self.best_option {
-                        self.best_option = distancia_jik;
-                        self.best_points = [*punto_i, *punto_j, *punto_k];
-                    }
-                }
+                self.calcula_k::<N>(i, punto_j, distancia_ij, end);
             }
-            i += 1;
         }
     }
 
-    fn divide_venceras_it(&mut self) {
-        let v = self.puntos.len() / self.fixed_points;
+    #[inline(always)]
+    fn divide_venceras_it<const N: usize>(&mut self) {
+        let len = self.soa.len();
 
-        for chunk in self.puntos.chunks(self.fixed_points) {
-            //let end = (i + 1) * self.fixed_points;
-            //let slice = &self.puntos.get(self.fixed_points * i..end).unwrap();
-            self.calcula_fixed(chunk)
+        for start in (0..len).step_by(self.fixed_points) {
+            self.calcula_fixed::<N>(start, (start + self.fixed_points).min(len))
         }
 
-        // Merge respuestas
-        let mut start = 0;
-        while start < self.puntos.len() {
-            let mut end = start + self.fixed_points * 2;
-            if start + self.fixed_points * 2 > self.puntos.len() {
-                end = self.puntos.len()
+        // Merge respuestas por niveles, como el recursivo: bloques de
+        // fixed_points, 2 * fixed_points, ... cada uno ya resuelto por dentro,
+        // y la franja entre cada par de vecinos
+        let mut ancho = self.fixed_points;
+        while ancho < len {
+            let mut start = 0;
+            while start + ancho < len {
+                let end = (start + ancho * 2).min(len);
+                self.recheck_actual_best::<N>(start, end, start + ancho);
+                start += ancho * 2
             }
-            let slice = self.puntos.get(start..end).unwrap();
-            self.recheck_actual_best(slice);
-            start += self.fixed_points
+            ancho *= 2
         }
     }
 
-    fn recheck_actual_best(&mut self, s_slice: &'a [Punto]) {
-        let mitad_index = s_slice.len() / 2;
-        let mitad = s_slice[mitad_index].x;
-        let (new_start, new_end) =
-            Self::get_points_between(mitad - self.best_option, mitad + self.best_option, s_slice);
-
-        let mid = mitad_index - new_start;
-        self.calcula_fixed_range(&s_slice[new_start..new_end + 1], mid);
+    #[inline(always)]
+    fn recheck_actual_best<const N: usize>(
+        &mut self,
+        start: usize,
+        end: usize,
+        mitad_index: usize,
+    ) {
+        let mitad = self.soa.xs()[mitad_index].to_f64();
+        let (new_start, new_end) = Self::get_points_between(
+            mitad - self.best_option,
+            mitad + self.best_option,
+            &self.soa.xs()[start..end],
+        );
+
+        let _fase = stats::en_fase(Fase::Franja);
+        stats::franja(new_end + 1 - new_start);
+        self.calcula_fixed_range::<N>(start + new_start, start + new_end + 1, mitad_index);
     }
 
-    fn get_points_between(start: f64, end: f64, puntos: &[Punto]) -> (usize, usize) {
-        let start_index = match puntos.binary_search_by(|p| p.x_comparef64(&start)) {
-            Ok(index) | Err(index) => index,
-        };
-
-        let end_index = match puntos.binary_search_by(|p| p.x_comparef64(&end)) {
-            Ok(index) => index,
-            Err(index) => index - 1,
-        };
+    fn get_points_between(start: f64, end: f64, xs: &[T]) -> (usize, usize) {
+        // Con x repetidas binary_search devuelve cualquiera de las iguales
+        let start_index = xs.partition_point(|x| x.to_f64() < start);
+        let end_index = xs.partition_point(|x| x.to_f64() <= end) - 1;
 
         (start_index, end_index)
     }
 
     pub fn get_points(&self) -> [usize; 3] {
-        let mut indexes = [0; 3];
-        for (i, point) in self.best_points.iter().enumerate() {
-            // SAFETY:
-            // El punto que estoy buscando siempre va a existir
-            let index = self.puntos.binary_search_by(|p| p.cmp(point)).unwrap();
-            indexes[i] = index;
+        self.best_points
+    }
+}
+
+struct Corrida<'s, 'a, T: Escalar>(&'s mut DyVSIMD<'a, T>);
+
+impl<T: Escalar> PorCarriles for Corrida<'_, '_, T> {
+    fn corre<const N: usize>(self) {
+        match self.0.kernel {
+            Kernel::Sse2 => self.0.divide_venceras_it::<N>(),
+            // start() ya ha comprobado que la CPU los tiene
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => unsafe { self.0.divide_venceras_avx2::<N>() },
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx512 => unsafe { self.0.divide_venceras_avx512::<N>() },
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => unreachable!(),
         }
-        indexes
     }
 }
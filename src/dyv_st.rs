@@ -1,21 +1,23 @@
+use crate::escalar::Escalar;
 use crate::punto::*;
+use crate::stats::{self, Contador, Fase};
 
 const FIXED_POINTS: usize = 108;
 const MAX: f64 = f64::MAX;
 
 #[allow(unused)]
-pub struct DyV<'a> {
-    puntos: &'a [Punto],
+pub struct DyV<'a, T: Escalar = f64> {
+    puntos: &'a [Punto<T>],
     best_option: BestPoint,
-    best_points: [Punto; 3],
+    best_points: [Punto<T>; 3],
     pub fixed_points: usize,
     f_cf: bool,
 }
 
 #[allow(unused)]
-impl<'a> DyV<'a> {
+impl<'a, T: Escalar> DyV<'a, T> {
     #[allow(unused)]
-    pub fn new_with_fixed(puntos: &'a [Punto], fixed_points: usize) -> DyV {
+    pub fn new_with_fixed(puntos: &'a [Punto<T>], fixed_points: usize) -> DyV<'a, T> {
         DyV {
             puntos,
             best_option: MAX,
@@ -26,12 +28,12 @@ impl<'a> DyV<'a> {
     }
 
     #[allow(unused)]
-    pub fn new(puntos: &'a [Punto]) -> DyV {
+    pub fn new(puntos: &'a [Punto<T>]) -> DyV<'a, T> {
         DyV {
             puntos,
             best_option: MAX,
             best_points: [Punto::default(), Punto::default(), Punto::default()],
-            fixed_points: FIXED_POINTS,
+            fixed_points: crate::perfil::fixed_points::<T>("dyv_st", FIXED_POINTS),
             f_cf: false,
         }
     }
@@ -43,24 +45,33 @@ impl<'a> DyV<'a> {
 
     #[inline]
     fn get_next_point(
-        puntos: &mut impl Iterator<Item = &'a Punto>,
-        punto_i: &'a Punto,
+        puntos: &mut impl Iterator<Item = &'a Punto<T>>,
+        punto_i: &'a Punto<T>,
         target: f64,
-    ) -> Option<&'a Punto> {
-        puntos.find(|sig| (sig.y - punto_i.y).abs() < target)
+    ) -> Option<&'a Punto<T>> {
+        puntos.find(|sig| {
+            let dentro = sig.y.diferencia(punto_i.y).abs() < target;
+            if !dentro {
+                stats::cuenta(Contador::RechazosY);
+            }
+            dentro
+        })
     }
 
     #[inline(always)]
-    fn calcula_fixed_range(&mut self, slice: &'a [Punto], mid: usize) {
+    fn calcula_fixed_range(&mut self, slice: &'a [Punto<T>], mid: usize) {
         let (f_mid, s_half) = slice.split_at(mid);
+        stats::suma(Contador::I, mid as u64);
         for (i, punto_i) in f_mid.iter().enumerate() {
 
             let mut j_iter = slice[i + 1..].iter();
             while let Some(punto_j) = Self::get_next_point(&mut j_iter, punto_i, self.best_option)
             {
-                if (punto_j.x - punto_i.x) >= self.best_option {
+                if punto_j.x.diferencia(punto_i.x) >= self.best_option {
+                    stats::cuenta(Contador::CortesX);
                     break;
                 }
+                stats::cuenta(Contador::J);
 
                 let distancia_ij = punto_i.distancia(punto_j);
 
@@ -73,9 +84,13 @@ impl<'a> DyV<'a> {
                 for punto_k in slice
                     .iter()
                     .skip(i + 1)
-                    .filter(|punto_k| !punto_k.total_cmp(punto_j))
+                    .filter(|punto_k| !punto_k.mismo(punto_j))
                 {
-                    if (punto_k.y - punto_i.y).abs() >= self.best_option && (punto_k.y - punto_j.y).abs() >= self.best_option {
+                    stats::cuenta(Contador::K);
+                    if punto_k.y.diferencia(punto_i.y).abs() >= self.best_option
+                        && punto_k.y.diferencia(punto_j.y).abs() >= self.best_option
+                    {
+                        stats::cuenta(Contador::RechazosY);
                         continue;
                     }
 
@@ -86,13 +101,17 @@ impl<'a> DyV<'a> {
                     if distancia_jk < mejor {
                         distancia_jk += distancia_ij;
                         self.best_option = distancia_jk;
+                        stats::cuenta(Contador::Mejoras);
                         mejor = self.best_option - distancia_ij;
-                        self.best_points = [*punto_j, *punto_i, *punto_k];
+                        self.best_points = [*punto_i, *punto_j, *punto_k];
                     }
 
                     if distancia_jik < self.best_option {
                         self.best_option = distancia_jik;
-                        self.best_points = [*punto_i, *punto_j, *punto_k];
+                        stats::cuenta(Contador::Mejoras);
+                        // la rama de jk compara con `mejor`, que tiene que seguir a best_option
+                        mejor = self.best_option - distancia_ij;
+                        self.best_points = [*punto_j, *punto_i, *punto_k];
                     }
                 }
             }
@@ -100,20 +119,23 @@ impl<'a> DyV<'a> {
     }
 
     #[inline(always)]
-    fn calcula_fixed(&mut self, slice: &'a [Punto]) {
+    fn calcula_fixed(&mut self, slice: &'a [Punto<T>]) {
 
+        stats::suma(Contador::I, slice.len() as u64);
         let mut i = 0;
         for punto_i in slice.iter() {
-            let mut j_slice: &'a [Punto] = slice.get(i + 1..).unwrap();
+            let mut j_slice: &'a [Punto<T>] = slice.get(i + 1..).unwrap();
             let mut j_iter = j_slice.iter();
 
-            let max_x = punto_i.x + self.best_option;
+            let max_x = punto_i.x.to_f64() + self.best_option;
 
             while let Some(punto_j) = Self::get_next_point(&mut j_iter, punto_i, self.best_option) 
             {
-                if (punto_j.x - punto_i.x) >= self.best_option {
+                if punto_j.x.diferencia(punto_i.x) >= self.best_option {
+                    stats::cuenta(Contador::CortesX);
                     break;
                 }
+                stats::cuenta(Contador::J);
 
                 let distancia_ij = punto_i.distancia(punto_j);
 
@@ -127,9 +149,13 @@ impl<'a> DyV<'a> {
                 for punto_k in slice
                     .iter()
                     .skip(i + 1)
-                    .filter(|punto_k| !punto_k.total_cmp(punto_j))
+                    .filter(|punto_k| !punto_k.mismo(punto_j))
                 {
-                    if (punto_k.y - punto_i.y).abs() >= self.best_option && (punto_k.y - punto_j.y).abs() >= self.best_option {
+                    stats::cuenta(Contador::K);
+                    if punto_k.y.diferencia(punto_i.y).abs() >= self.best_option
+                        && punto_k.y.diferencia(punto_j.y).abs() >= self.best_option
+                    {
+                        stats::cuenta(Contador::RechazosY);
                         continue;
                     }
 
@@ -140,13 +166,16 @@ impl<'a> DyV<'a> {
                     if distancia_jk < mejor {
                         distancia_jk += distancia_ij;
                         self.best_option = distancia_jk;
+                        stats::cuenta(Contador::Mejoras);
                         mejor = self.best_option - distancia_ij;
-                        self.best_points = [*punto_j, *punto_i, *punto_k];
+                        self.best_points = [*punto_i, *punto_j, *punto_k];
                     }
 
                     if distancia_jik < self.best_option {
                         self.best_option = distancia_jik;
-                        self.best_points = [*punto_i, *punto_j, *punto_k];
+                        stats::cuenta(Contador::Mejoras);
+                        mejor = self.best_option - distancia_ij;
+                        self.best_points = [*punto_j, *punto_i, *punto_k];
                     }
                 }
 
@@ -155,7 +184,7 @@ impl<'a> DyV<'a> {
         }
     }
 
-    fn divide_venceras(&mut self, s_slice: &'a [Punto]) {
+    fn divide_venceras(&mut self, s_slice: &'a [Punto<T>]) {
         let len = s_slice.len();
 
         if len < self.fixed_points {
@@ -170,37 +199,27 @@ impl<'a> DyV<'a> {
         self.recheck_actual_best(s_slice);
     }
 
-    fn recheck_actual_best(&mut self, s_slice: &'a [Punto]) {
+    fn recheck_actual_best(&mut self, s_slice: &'a [Punto<T>]) {
         let mitad_index = s_slice.len() / 2;
-        let mitad = s_slice[mitad_index].x;
+        let mitad = s_slice[mitad_index].x.to_f64();
         let (new_start, new_end) =
             Self::get_points_between(mitad - self.best_option, mitad + self.best_option, s_slice);
 
         let mid = mitad_index - new_start;
+        let _fase = stats::en_fase(Fase::Franja);
+        stats::franja(new_end + 1 - new_start);
         self.calcula_fixed_range(&s_slice[new_start..new_end + 1], mid);
     }
 
-    fn get_points_between(start: f64, end: f64, puntos: &[Punto]) -> (usize, usize) {
-        let start_index = match puntos.binary_search_by(|p| p.x_comparef64(&start)) {
-            Ok(index) | Err(index) => index,
-        };
-
-        let end_index = match puntos.binary_search_by(|p| p.x_comparef64(&end)) {
-            Ok(index) => index,
-            Err(index) => index - 1,
-        };
+    fn get_points_between(start: f64, end: f64, puntos: &[Punto<T>]) -> (usize, usize) {
+        // Con x repetidas binary_search devuelve cualquiera de las iguales
+        let start_index = puntos.partition_point(|p| p.x_comparef64(&start).is_lt());
+        let end_index = puntos.partition_point(|p| p.x_comparef64(&end).is_le()) - 1;
 
         (start_index, end_index)
     }
 
     pub fn get_points(&self) -> [usize; 3] {
-        let mut indexes = [0; 3];
-        for (i, point) in self.best_points.iter().enumerate() {
-            // SAFETY:
-            // El punto que estoy buscando siempre va a existir
-            let index = self.puntos.binary_search_by(|p| p.cmp(point)).unwrap();
-            indexes[i] = index;
-        }
-        indexes
+        indices_de(self.puntos, &self.best_points)
     }
 }
@@ -0,0 +1,520 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display};
+#[cfg(feature = "simd-nightly")]
+use std::simd::{Simd, SimdElement};
+
+use crate::kernel::Kernel;
+use crate::punto::Punto;
+use crate::soa::PuntosSoa;
+use crate::stats::{self, Contador};
+
+// Tipo de las coordenadas. Las distancias y best_option van siempre en f64;
+// con enteros cada distancia sale de la distancia al cuadrado exacta, pero la
+// raiz y la suma de dos se redondean, asi que un empate entre caminos
+// distintos puede depender del orden de las sumas como en f64.
+pub trait Escalar:
+    Copy + Default + PartialEq + PartialOrd + Debug + Display + Send + Sync + 'static
+{
+    // Puntos en un vector de 256 bits; con AVX-512 DyVSIMD usa el doble
+    const CARRILES: usize;
+
+    fn to_f64(self) -> f64;
+
+    // Redondeando en los enteros; None si no es finito o no cabe en Self
+    // (1e39 en f32, 3e9 en i32)
+    fn desde_f64(valor: f64) -> Option<Self>;
+
+    fn total_cmp(&self, other: &Self) -> Ordering;
+
+    // Clave sin signo con el mismo orden que total_cmp, para el radix sort
+    fn clave_orden(self) -> u64;
+
+    // self - otro en f64, exacta en enteros de hasta 53 bits
+    fn diferencia(self, otro: Self) -> f64;
+
+    fn distancia(a: &Punto<Self>, b: &Punto<Self>) -> f64;
+
+    fn es_finito(self) -> bool {
+        true
+    }
+
+    fn parse(campo: &[u8]) -> Option<Self>;
+
+    // Llama a f.corre::<N>() con los carriles que tocan a este tipo con `kernel`
+    fn con_carriles(kernel: Kernel, f: impl PorCarriles);
+
+    // Primer indice en start..end con |y - y del centro| <= radio, o None si
+    // antes hay un punto con x >= x del centro + radio. La x solo se mira cada
+    // 2 * N puntos, asi que puede devolver alguno algo mas alla en x; esos no
+    // bajan best y el llamador sigue.
+    fn siguiente_en_ventana<const N: usize>(
+        puntos: &PuntosSoa<Self>,
+        centro: usize,
+        radio: f64,
+        start: usize,
+        end: usize,
+    ) -> Option<usize>;
+
+    // Llama a `visita`, en orden, con los k de start..end que podrian bajar
+    // best con el camino i-j-k o j-i-k. visita devuelve el best nuevo, que
+    // solo puede bajar. Los enteros solo filtran por y; f64 y f32 calculan
+    // ademas las dos distancias de N k a la vez.
+    fn candidatos_k<const N: usize>(
+        puntos: &PuntosSoa<Self>,
+        i: usize,
+        j: usize,
+        best: f64,
+        start: usize,
+        end: usize,
+        visita: impl FnMut(usize) -> f64,
+    );
+}
+
+// Codigo que necesita el numero de carriles como constante. Cada Escalar lo
+// fija segun el kernel, que solo se conoce al ejecutar.
+pub trait PorCarriles {
+    fn corre<const N: usize>(self);
+}
+
+// Vectores de N puntos sacados de PuntosSoa; N es $carriles o, con AVX-512, el
+// doble. $dif es |a - b| y $cmp la comparacion con el radio que deja dentro al
+// punto. Con $f64, que pasa un vector de coordenadas a f64, los k tambien se
+// filtran por distancia. Sin la feature simd-nightly todo esto se queda en los
+// bloques de N escalares de mas abajo.
+macro_rules! ventana_simd {
+    ($carriles:literal, $radio:expr, |$a:ident, $b:ident| $dif:expr, $cmp:ident $(, $f64:expr)?) => {
+        fn con_carriles(kernel: Kernel, f: impl PorCarriles) {
+            match kernel {
+                Kernel::Avx512 => f.corre::<{ 2 * $carriles }>(),
+                Kernel::Sse2 | Kernel::Avx2 => f.corre::<$carriles>(),
+            }
+        }
+
+        #[cfg(not(feature = "simd-nightly"))]
+        #[inline(always)]
+        fn siguiente_en_ventana<const N: usize>(
+            puntos: &PuntosSoa<Self>,
+            centro: usize,
+            radio: f64,
+            start: usize,
+            end: usize,
+        ) -> Option<usize> {
+            ventana_por_bloques::<Self, N>(puntos, centro, radio, start, end)
+        }
+
+        #[cfg(not(feature = "simd-nightly"))]
+        #[inline(always)]
+        fn candidatos_k<const N: usize>(
+            puntos: &PuntosSoa<Self>,
+            i: usize,
+            j: usize,
+            best: f64,
+            start: usize,
+            end: usize,
+            visita: impl FnMut(usize) -> f64,
+        ) {
+            candidatos_por_bloques::<Self, N>(puntos, i, j, best, start, end, visita)
+        }
+
+        // Bloques alineados como en candidatos_k, con mascara en el primero
+        #[cfg(feature = "simd-nightly")]
+        #[inline(always)]
+        fn siguiente_en_ventana<const N: usize>(
+            puntos: &PuntosSoa<Self>,
+            centro: usize,
+            radio: f64,
+            start: usize,
+            end: usize,
+        ) -> Option<usize> {
+            use std::simd::prelude::*;
+
+            let dif = |$a: Simd<Self, N>, $b: Simd<Self, N>| $dif;
+            let (_, vectores) = puntos.vectores::<N>();
+            let (xs, ys) = (&puntos.xs()[..end], &puntos.ys()[..end]);
+            let (centro_x, centro_y) = (xs[centro], ys[centro]);
+            let vec_y = Simd::splat(centro_y);
+            let vec_radio = Simd::splat(($radio)(radio));
+
+            let mut bloque = start / N;
+            let mut validos = u64::MAX << (start % N);
+            while (bloque + 2) * N <= end {
+                // x ordenadas: basta el primero de cada 2 * N
+                if xs[(bloque * N).max(start)].diferencia(centro_x) >= radio {
+                    stats::cuenta(Contador::CortesX);
+                    return None;
+                }
+
+                // Unroll
+                for _ in 0..2 {
+                    let cerca = dif(vectores[bloque], vec_y).$cmp(vec_radio).to_bitmask() & validos;
+                    if cerca != 0 {
+                        return Some(bloque * N + cerca.trailing_zeros() as usize);
+                    }
+                    validos = u64::MAX;
+                    bloque += 1;
+                }
+            }
+
+            resto_de_ventana(xs, ys, (centro_x, centro_y), radio, (bloque * N).max(start))
+        }
+
+        #[cfg(feature = "simd-nightly")]
+        #[inline(always)]
+        fn candidatos_k<const N: usize>(
+            puntos: &PuntosSoa<Self>,
+            i: usize,
+            j: usize,
+            mut best: f64,
+            start: usize,
+            end: usize,
+            mut visita: impl FnMut(usize) -> f64,
+        ) {
+            use std::simd::prelude::*;
+
+            // Aqui se recorre todo start..end: bloques alineados y mascara
+            // solo en el primero y el ultimo
+            let dif = |$a: Simd<Self, N>, $b: Simd<Self, N>| $dif;
+            let (_, ys) = puntos.vectores::<N>();
+            let (y_i, y_j) = (Simd::splat(puntos.ys()[i]), Simd::splat(puntos.ys()[j]));
+            let mut vec_radio = Simd::splat(($radio)(best));
+            let filtro = filtro_k!(puntos, i, j $(, $f64)?);
+
+            let mut validos = u64::MAX << (start % N);
+            for bloque in start / N..end.div_ceil(N) {
+                let base = bloque * N;
+                if base + N > end {
+                    validos &= !(u64::MAX << (end - base));
+                }
+
+                let cerca = dif(ys[bloque], y_i).$cmp(vec_radio) | dif(ys[bloque], y_j).$cmp(vec_radio);
+                let mut cerca = cerca.to_bitmask() & validos;
+                if cerca != 0 {
+                    // Casi todos los bloques se descartan solo por y, sin raices
+                    cerca &= filtro(best, bloque);
+                }
+                if cerca != 0 {
+                    while cerca != 0 {
+                        best = visita(base + cerca.trailing_zeros() as usize);
+                        cerca &= cerca - 1;
+                    }
+                    vec_radio = Simd::splat(($radio)(best));
+                }
+                validos = u64::MAX;
+            }
+        }
+    };
+}
+
+// Bits de los k del bloque que pasan el filtro en y y aun pueden descartarse.
+// Sin conversion a f64 no se descarta ninguno.
+#[cfg(feature = "simd-nightly")]
+macro_rules! filtro_k {
+    ($puntos:ident, $i:ident, $j:ident) => {
+        |_: f64, _: usize| u64::MAX
+    };
+    ($puntos:ident, $i:ident, $j:ident, $f64:expr) => {{
+        let distancia_ij = $puntos.punto($i).distancia(&$puntos.punto($j));
+        move |best: f64, bloque: usize| {
+            mejoran::<Self, N>($puntos, ($i, $j), distancia_ij, best, bloque, $f64)
+        }
+    }};
+}
+
+// Las dos distancias a los k del bloque en f64 y con las mismas operaciones
+// que Escalar::distancia, asi que salen los mismos bits que en escalar. Como
+// best solo baja, un k que no mejora con el best del bloque tampoco mejoraria
+// despues: visitar solo estos da el mismo resultado que ir k a k.
+#[cfg(feature = "simd-nightly")]
+#[inline(always)]
+fn mejoran<T: Escalar + SimdElement, const N: usize>(
+    puntos: &PuntosSoa<T>,
+    (i, j): (usize, usize),
+    distancia_ij: f64,
+    best: f64,
+    bloque: usize,
+    a_f64: impl Fn(Simd<T, N>) -> Simd<f64, N>,
+) -> u64 {
+    use std::simd::prelude::*;
+    use std::simd::StdFloat;
+
+    let (xs, ys) = puntos.vectores::<N>();
+    let (kx, ky) = (a_f64(xs[bloque]), a_f64(ys[bloque]));
+    let distancia = |centro: usize| {
+        let dx = kx - Simd::splat(puntos.xs()[centro].to_f64());
+        let dy = ky - Simd::splat(puntos.ys()[centro].to_f64());
+        (dx * dx + dy * dy).sqrt()
+    };
+
+    let mejora_jk = distancia(j).simd_lt(Simd::splat(best - distancia_ij));
+    let mejora_jik = (Simd::splat(distancia_ij) + distancia(i)).simd_lt(Simd::splat(best));
+    (mejora_jk | mejora_jik).to_bitmask()
+}
+
+// Version estable: bloques de N escalares con chunks_exact. Las mascaras se
+// sacan sin saltos para que el compilador las vectorice con lo que tenga el
+// kernel; los k solo se filtran por y, la distancia la mira `visita`.
+#[cfg(not(feature = "simd-nightly"))]
+#[inline(always)]
+fn mascara<T: Escalar>(bloque: &[T], dentro: impl Fn(T) -> bool) -> u64 {
+    bloque.iter().enumerate().fold(0, |mascara, (carril, &y)| {
+        mascara | (dentro(y) as u64) << carril
+    })
+}
+
+#[cfg(not(feature = "simd-nightly"))]
+#[inline(always)]
+fn ventana_por_bloques<T: Escalar, const N: usize>(
+    puntos: &PuntosSoa<T>,
+    centro: usize,
+    radio: f64,
+    mut start: usize,
+    end: usize,
+) -> Option<usize> {
+    let (xs, ys) = (&puntos.xs()[..end], &puntos.ys()[..end]);
+    let (centro_x, centro_y) = (xs[centro], ys[centro]);
+
+    for bloque in ys[start..].chunks_exact(2 * N) {
+        // x ordenadas: basta el primero de cada 2 * N
+        if xs[start].diferencia(centro_x) >= radio {
+            stats::cuenta(Contador::CortesX);
+            return None;
+        }
+
+        let cerca = mascara(bloque, |y| y.diferencia(centro_y).abs() < radio);
+        if cerca != 0 {
+            return Some(start + cerca.trailing_zeros() as usize);
+        }
+        start += 2 * N;
+    }
+
+    resto_de_ventana(xs, ys, (centro_x, centro_y), radio, start)
+}
+
+// Los ultimos de siguiente_en_ventana, de uno en uno, hasta el final de xs.
+// El primero a radio o mas a la derecha corta como en los bloques; por la
+// izquierda solo se salta
+#[inline(always)]
+fn resto_de_ventana<T: Escalar>(
+    xs: &[T],
+    ys: &[T],
+    (centro_x, centro_y): (T, T),
+    radio: f64,
+    start: usize,
+) -> Option<usize> {
+    for k in start..xs.len() {
+        let dx = xs[k].diferencia(centro_x);
+        if dx >= radio {
+            stats::cuenta(Contador::CortesX);
+            return None;
+        }
+        if ys[k].diferencia(centro_y).abs() < radio && dx.abs() < radio {
+            return Some(k);
+        }
+    }
+    None
+}
+
+#[cfg(not(feature = "simd-nightly"))]
+#[inline(always)]
+fn candidatos_por_bloques<T: Escalar, const N: usize>(
+    puntos: &PuntosSoa<T>,
+    i: usize,
+    j: usize,
+    mut best: f64,
+    start: usize,
+    end: usize,
+    mut visita: impl FnMut(usize) -> f64,
+) {
+    let ys = &puntos.ys()[..end];
+    let (y_i, y_j) = (ys[i], ys[j]);
+
+    let bloques = ys[start..].chunks_exact(N);
+    let resto = bloques.remainder();
+    let mut base = start;
+    for bloque in bloques.chain(std::iter::once(resto)) {
+        let mut cerca = mascara(bloque, |y| {
+            y.diferencia(y_i).abs() < best || y.diferencia(y_j).abs() < best
+        });
+        while cerca != 0 {
+            best = visita(base + cerca.trailing_zeros() as usize);
+            cerca &= cerca - 1;
+        }
+        base += N;
+    }
+}
+
+impl Escalar for f64 {
+    const CARRILES: usize = 4;
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn desde_f64(valor: f64) -> Option<Self> {
+        valor.is_finite().then_some(valor)
+    }
+
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f64::total_cmp(self, other)
+    }
+
+    // Negativos con todos los bits invertidos, positivos con el de signo a 1
+    fn clave_orden(self) -> u64 {
+        let bits = self.to_bits();
+        bits ^ ((bits as i64 >> 63) as u64 | 1 << 63)
+    }
+
+    #[inline(always)]
+    fn diferencia(self, otro: Self) -> f64 {
+        self - otro
+    }
+
+    #[inline(always)]
+    fn distancia(a: &Punto<Self>, b: &Punto<Self>) -> f64 {
+        let diff_x = (b.x - a.x) * (b.x - a.x);
+        let diff_y = (b.y - a.y) * (b.y - a.y);
+        (diff_x + diff_y).sqrt()
+    }
+
+    fn es_finito(self) -> bool {
+        self.is_finite()
+    }
+
+    fn parse(campo: &[u8]) -> Option<Self> {
+        fast_float2::parse(campo).ok()
+    }
+
+    ventana_simd!(4, |radio| radio, |a, b| (a - b).abs(), simd_le, |v| v);
+}
+
+// Las diferencias y cuadrados de f32 son exactos en f64, asi que la distancia
+// se calcula en f64 con un solo redondeo antes de la raiz
+impl Escalar for f32 {
+    const CARRILES: usize = 8;
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn desde_f64(valor: f64) -> Option<Self> {
+        let convertido = valor as f32;
+        convertido.is_finite().then_some(convertido)
+    }
+
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f32::total_cmp(self, other)
+    }
+
+    fn clave_orden(self) -> u64 {
+        let bits = self.to_bits();
+        (bits ^ ((bits as i32 >> 31) as u32 | 1 << 31)) as u64
+    }
+
+    #[inline(always)]
+    fn diferencia(self, otro: Self) -> f64 {
+        self as f64 - otro as f64
+    }
+
+    #[inline(always)]
+    fn distancia(a: &Punto<Self>, b: &Punto<Self>) -> f64 {
+        let diff_x = b.x.diferencia(a.x);
+        let diff_y = b.y.diferencia(a.y);
+        (diff_x * diff_x + diff_y * diff_y).sqrt()
+    }
+
+    fn es_finito(self) -> bool {
+        self.is_finite()
+    }
+
+    fn parse(campo: &[u8]) -> Option<Self> {
+        fast_float2::parse(campo).ok()
+    }
+
+    // El radio redondeado hacia arriba, si no se podria saltar un j valido
+    ventana_simd!(
+        8,
+        |radio: f64| {
+            let r = radio as f32;
+            if (r as f64) < radio {
+                r.next_up()
+            } else {
+                r
+            }
+        },
+        |a, b| (a - b).abs(),
+        simd_le,
+        |v| v.cast::<f64>()
+    );
+}
+
+// Un entero d cumple d < radio si y solo si d < ceil(radio). Las diferencias
+// saturan, que no cambia el resultado de la comparacion.
+macro_rules! escalar_entero {
+    ($t:ty, $ancho:ty, $carriles:literal) => {
+        impl Escalar for $t {
+            const CARRILES: usize = $carriles;
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            // MIN es -2^(bits-1), exacto en f64; MAX + 1 es su opuesto
+            fn desde_f64(valor: f64) -> Option<Self> {
+                let redondeado = valor.round();
+                let min = <$t>::MIN as f64;
+                (redondeado >= min && redondeado < -min).then_some(redondeado as $t)
+            }
+
+            fn total_cmp(&self, other: &Self) -> Ordering {
+                self.cmp(other)
+            }
+
+            fn clave_orden(self) -> u64 {
+                (self as i64 as u64) ^ 1 << 63
+            }
+
+            #[inline(always)]
+            fn diferencia(self, otro: Self) -> f64 {
+                (self as $ancho - otro as $ancho) as f64
+            }
+
+            // Cuadrados exactos en u128; solo la suma de dos cuadrados de i64
+            // en los extremos puede no caber
+            #[inline(always)]
+            fn distancia(a: &Punto<Self>, b: &Punto<Self>) -> f64 {
+                let dx = (b.x as $ancho - a.x as $ancho).unsigned_abs() as u128;
+                let dy = (b.y as $ancho - a.y as $ancho).unsigned_abs() as u128;
+                match (dx * dx).checked_add(dy * dy) {
+                    Some(cuadrado) => (cuadrado as f64).sqrt(),
+                    None => (dx as f64).hypot(dy as f64),
+                }
+            }
+
+            // Enteros o reales con parte decimal 0 ("12.0")
+            fn parse(campo: &[u8]) -> Option<Self> {
+                let texto = std::str::from_utf8(campo).ok()?;
+                texto.parse().ok().or_else(|| {
+                    let valor: f64 = texto.parse().ok()?;
+                    let en_rango = valor >= <$t>::MIN as f64 && valor <= <$t>::MAX as f64;
+                    (valor.fract() == 0.0 && en_rango).then_some(valor as $t)
+                })
+            }
+
+            ventana_simd!(
+                $carriles,
+                |radio: f64| {
+                    if radio >= <$t>::MAX as f64 {
+                        <$t>::MAX
+                    } else {
+                        radio.ceil().max(0.0) as $t
+                    }
+                },
+                |a, b| a.saturating_sub(b).saturating_abs(),
+                simd_lt
+            );
+        }
+    };
+}
+
+escalar_entero!(i32, i64, 8);
+escalar_entero!(i64, i128, 4);
@@ -0,0 +1,394 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+
+use crate::dyv_st::DyV;
+use crate::formatos::{aplica_no_finitos, avisa_quitados, NoFinitos, RangoFinito};
+use crate::lector;
+use crate::punto::*;
+
+const MAX: f64 = f64::MAX;
+const BYTES_PUNTO: usize = 16;
+// Runs abiertos a la vez en el merge
+const MAX_RUNS: usize = 64;
+
+// Pipeline para conjuntos que no caben en memoria:
+//  1. ordenacion externa por x en runs binarios (f64 LE x, y) + merge k-way
+//  2. fase de hojas: DyV sobre bloques consecutivos del fichero ordenado
+//  3. merge en las fronteras: la franja x > ultimo_x - best_option de lo ya
+//     leido es un rango del fichero ordenado; se cruza con la cabeza del
+//     bloque siguiente
+// En memoria nunca hay mas de `max_puntos` puntos: un tercio para el bloque y
+// dos para la franja mas la cabeza. Si la franja no cabe se vuelve a leer del
+// fichero por trozos.
+#[allow(unused)]
+pub struct DyVExterno {
+    fichero: PathBuf,
+    max_puntos: usize,
+    best_option: BestPoint,
+    best_points: [usize; 3],
+}
+
+#[allow(unused)]
+impl DyVExterno {
+    // `fichero` debe estar ordenado por x en formato binario crudo (ver ordena_externo)
+    pub fn new<I: AsRef<Path>>(fichero: I, max_puntos: usize) -> Self {
+        Self {
+            fichero: fichero.as_ref().to_path_buf(),
+            max_puntos: max_puntos.max(9),
+            best_option: MAX,
+            best_points: [0; 3],
+        }
+    }
+
+    pub fn start(&mut self) -> io::Result<BestPoint> {
+        let mut reader = BufReader::new(File::open(&self.fichero)?);
+        // Para volver a leer la franja cuando no esta en memoria
+        let mut rangos = File::open(&self.fichero)?;
+
+        let bloque = self.max_puntos / 3;
+        let mut offset = 0;
+        // La franja es [desde, offset) del fichero. `franja` la tiene en
+        // memoria si cabe en un bloque; si no, esta vacia.
+        let mut desde = 0;
+        let mut franja: Vec<Punto> = Vec::with_capacity(2 * bloque);
+        let mut ultimo_x = f64::MIN;
+
+        loop {
+            let puntos = lee_bloque(&mut reader, bloque)?;
+            if puntos.is_empty() {
+                break;
+            }
+
+            if desde < offset {
+                let limite = ultimo_x + self.best_option;
+                let cabeza = puntos.partition_point(|p| p.x < limite);
+                if offset - desde <= bloque {
+                    let len = franja.len();
+                    franja.extend_from_slice(&puntos[..cabeza]);
+                    self.resuelve(&franja, |i| i + desde);
+                    franja.truncate(len);
+                } else {
+                    self.resuelve_por_trozos(&mut rangos, desde..offset + cabeza, 2 * bloque / 3)?;
+                }
+            }
+
+            self.resuelve(&puntos, |i| i + offset);
+
+            ultimo_x = puntos[puntos.len() - 1].x;
+            let limite = ultimo_x - self.best_option;
+            let nuevo = if puntos[0].x <= limite {
+                offset + puntos.partition_point(|p| p.x <= limite)
+            } else if franja.is_empty() && desde < offset {
+                primero_mayor(&mut rangos, desde..offset, limite)?
+            } else {
+                desde + franja.partition_point(|p| p.x <= limite)
+            };
+
+            // Lo que queda de la franja anterior, si estaba en memoria, o se
+            // lee de nuevo si ahora cabe
+            let fin = offset + puntos.len();
+            if fin - nuevo > bloque {
+                franja.clear();
+            } else if nuevo >= offset {
+                franja.clear();
+                franja.extend_from_slice(&puntos[nuevo - offset..]);
+            } else {
+                if franja.is_empty() {
+                    franja = lee_rango(&mut rangos, nuevo..offset)?;
+                } else {
+                    franja.drain(..nuevo - desde);
+                }
+                franja.extend_from_slice(&puntos);
+            }
+            desde = nuevo;
+            offset = fin;
+        }
+
+        Ok(self.best_option)
+    }
+
+    // Indices en el orden del fichero ordenado
+    pub fn get_points(&self) -> [usize; 3] {
+        self.best_points
+    }
+
+    fn resuelve(&mut self, puntos: &[Punto], global: impl Fn(usize) -> usize) {
+        if puntos.len() < 3 {
+            return;
+        }
+
+        let mut dyv = DyV::new(puntos);
+        let res = dyv.start();
+        if res < self.best_option {
+            self.best_option = res;
+            self.best_points = dyv.get_points().map(global);
+        }
+    }
+
+    // Un rango del fichero que no cabe: todo camino esta en como mucho tres
+    // trozos de `trozo` puntos, asi que basta con resolver juntos cada grupo
+    // de trozos. Van ordenados por x y un trozo solo se junta con los
+    // siguientes que empiezan a menos de best_option de donde acaba.
+    fn resuelve_por_trozos(
+        &mut self,
+        fichero: &mut File,
+        rango: Range<usize>,
+        trozo: usize,
+    ) -> io::Result<()> {
+        let trozos: Vec<Range<usize>> = rango
+            .clone()
+            .step_by(trozo.max(1))
+            .map(|i| i..(i + trozo.max(1)).min(rango.end))
+            .collect();
+
+        // x del primer y del ultimo punto de cada trozo
+        let mut extremos = Vec::with_capacity(trozos.len());
+        for t in &trozos {
+            let primero = lee_rango(fichero, t.start..t.start + 1)?[0].x;
+            let ultimo = lee_rango(fichero, t.end - 1..t.end)?[0].x;
+            extremos.push((primero, ultimo));
+        }
+
+        for a in 0..trozos.len() {
+            let cerca = (a + 1..trozos.len())
+                .take_while(|&b| extremos[b].0 - extremos[a].1 < self.best_option)
+                .count();
+            if cerca == 0 {
+                self.resuelve_trozos(fichero, &[&trozos[a]])?;
+            }
+            for b in a + 1..=a + cerca {
+                if b == a + cerca {
+                    self.resuelve_trozos(fichero, &[&trozos[a], &trozos[b]])?;
+                }
+                for c in b + 1..=a + cerca {
+                    self.resuelve_trozos(fichero, &[&trozos[a], &trozos[b], &trozos[c]])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn resuelve_trozos(&mut self, fichero: &mut File, trozos: &[&Range<usize>]) -> io::Result<()> {
+        let mut puntos = Vec::with_capacity(trozos.iter().map(|t| t.len()).sum());
+        // Donde empieza cada trozo en `puntos` y en el fichero
+        let mut bases = Vec::with_capacity(trozos.len());
+        for trozo in trozos {
+            bases.push((puntos.len(), trozo.start));
+            puntos.extend(lee_rango(fichero, (*trozo).clone())?);
+        }
+        self.resuelve(&puntos, |i| {
+            let (base, inicio) = bases.iter().rev().find(|(base, _)| *base <= i).unwrap();
+            inicio + i - base
+        });
+        Ok(())
+    }
+}
+
+// Los puntos `rango` del fichero binario
+fn lee_rango(fichero: &mut File, rango: Range<usize>) -> io::Result<Vec<Punto>> {
+    fichero.seek(SeekFrom::Start((rango.start * BYTES_PUNTO) as u64))?;
+    let puntos = lee_bloque(&mut BufReader::new(&*fichero), rango.len())?;
+    if puntos.len() < rango.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "fichero ordenado truncado",
+        ));
+    }
+    Ok(puntos)
+}
+
+// Primer indice de `rango` con x > limite, leyendo en orden
+fn primero_mayor(fichero: &mut File, rango: Range<usize>, limite: f64) -> io::Result<usize> {
+    fichero.seek(SeekFrom::Start((rango.start * BYTES_PUNTO) as u64))?;
+    let mut reader = BufReader::new(&*fichero);
+    for i in rango.clone() {
+        match lee_punto(&mut reader)? {
+            Some(punto) if punto.x > limite => return Ok(i),
+            Some(_) => {}
+            None => break,
+        }
+    }
+    Ok(rango.end)
+}
+
+fn escribe_punto(writer: &mut impl Write, punto: &Punto) -> io::Result<()> {
+    writer.write_all(&punto.x.to_le_bytes())?;
+    writer.write_all(&punto.y.to_le_bytes())
+}
+
+fn lee_punto(reader: &mut impl Read) -> io::Result<Option<Punto>> {
+    let mut buffer = [0u8; BYTES_PUNTO];
+    match reader.read_exact(&mut buffer) {
+        Ok(()) => Ok(Some(Punto {
+            x: f64::from_le_bytes(buffer[..8].try_into().unwrap()),
+            y: f64::from_le_bytes(buffer[8..].try_into().unwrap()),
+        })),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[allow(unused)]
+pub fn lee_bloque(reader: &mut impl Read, max_puntos: usize) -> io::Result<Vec<Punto>> {
+    let mut puntos = Vec::with_capacity(max_puntos.min(1 << 20));
+    while puntos.len() < max_puntos {
+        match lee_punto(reader)? {
+            Some(punto) => puntos.push(punto),
+            None => break,
+        }
+    }
+    Ok(puntos)
+}
+
+fn escribe_run(path: &Path, puntos: &[Punto]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for punto in puntos {
+        escribe_punto(&mut writer, punto)?;
+    }
+    writer.flush()
+}
+
+// Ficheros temporales que se borran al salir, tambien si algo falla a medias
+pub(crate) struct Temporales(pub(crate) Vec<PathBuf>);
+
+impl Drop for Temporales {
+    fn drop(&mut self) {
+        for fichero in &self.0 {
+            let _ = std::fs::remove_file(fichero);
+        }
+    }
+}
+
+// Lee un TSPLIB en bloques de `max_puntos`, ordena cada bloque en un run y
+// mezcla todos los runs en `salida` (binario crudo ordenado por x). El
+// parseo es el de lector; las coordenadas no finitas siguen `politica`.
+#[allow(unused)]
+pub fn ordena_externo<I: AsRef<Path>, O: AsRef<Path>>(
+    entrada: I,
+    salida: O,
+    max_puntos: usize,
+    politica: NoFinitos,
+) -> io::Result<usize> {
+    let tmp_dir = salida
+        .as_ref()
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    let mut runs = Temporales(Vec::new());
+    let mut total = 0;
+
+    let file = File::open(entrada)?;
+    if file.metadata()?.len() == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "sin NODE_COORD_SECTION",
+        ));
+    }
+    // SAFETY:
+    // Solo lectura, el fichero no se modifica mientras se parsea
+    let datos = unsafe { Mmap::map(&file)? };
+    if !datos
+        .split(|b| *b == b'\n')
+        .any(|linea| linea.trim_ascii() == b"NODE_COORD_SECTION")
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "sin NODE_COORD_SECTION",
+        ));
+    }
+
+    // Recortar lleva los infinitos a los extremos finitos de todo el fichero,
+    // no de cada trozo: hace falta una primera pasada
+    let rango = (politica == NoFinitos::Recortar).then(|| {
+        let mut rango = RangoFinito::vacio();
+        for puntos in lector::trozos_tsp(&datos, max_puntos.max(1)) {
+            puntos.iter().for_each(|p| rango.anade(p));
+        }
+        rango
+    });
+
+    let mut leidos = 0;
+    let mut quitados = 0;
+    for mut puntos in lector::trozos_tsp(&datos, max_puntos.max(1)) {
+        let en_trozo = puntos.len();
+        quitados +=
+            aplica_no_finitos(&mut puntos, politica, rango, |p| p).map_err(|(indice, punto)| {
+                let (linea, nodo) = lector::ubica_tsp(&datos, leidos + indice);
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "linea {linea}, nodo {nodo}: coordenada no finita ({}, {})",
+                        punto.x, punto.y
+                    ),
+                )
+            })?;
+        leidos += en_trozo;
+
+        puntos.sort();
+        let run = nombre_run(&tmp_dir, runs.0.len());
+        runs.0.push(run.clone());
+        escribe_run(&run, &puntos)?;
+        total += puntos.len();
+    }
+
+    mezcla_runs(&tmp_dir, &runs.0, salida.as_ref())?;
+    avisa_quitados(quitados);
+    Ok(total)
+}
+
+fn nombre_run(tmp_dir: &Path, n: usize) -> PathBuf {
+    tmp_dir.join(format!(".puntitos_run_{}_{}.bin", std::process::id(), n))
+}
+
+// Se mezclan como mucho MAX_RUNS runs a la vez, para no abrir un fichero (y
+// su buffer) por cada uno; si hay mas, por pasadas con runs intermedios.
+fn mezcla_runs(tmp_dir: &Path, runs: &[PathBuf], salida: &Path) -> io::Result<()> {
+    let mut intermedios = Temporales(Vec::new());
+    let mut pendientes = runs.to_vec();
+    let mut siguiente = runs.len();
+
+    while pendientes.len() > MAX_RUNS {
+        let mut mezclados = Vec::with_capacity(pendientes.len().div_ceil(MAX_RUNS));
+        for grupo in pendientes.chunks(MAX_RUNS) {
+            let run = nombre_run(tmp_dir, siguiente);
+            siguiente += 1;
+            intermedios.0.push(run.clone());
+            mezcla(grupo, &run)?;
+            mezclados.push(run);
+        }
+        pendientes = mezclados;
+    }
+
+    mezcla(&pendientes, salida)
+}
+
+// Merge k-way con un heap de (punto, run)
+fn mezcla(runs: &[PathBuf], salida: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(salida)?);
+    let mut readers = runs
+        .iter()
+        .map(|run| File::open(run).map(BufReader::new))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut heap = BinaryHeap::with_capacity(readers.len());
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(punto) = lee_punto(reader)? {
+            heap.push(Reverse((punto, i)));
+        }
+    }
+
+    while let Some(Reverse((punto, i))) = heap.pop() {
+        escribe_punto(&mut writer, &punto)?;
+        if let Some(siguiente) = lee_punto(&mut readers[i])? {
+            heap.push(Reverse((siguiente, i)));
+        }
+    }
+
+    writer.flush()
+}
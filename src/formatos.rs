@@ -0,0 +1,838 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::{json, Map, Value};
+
+use crate::compresion::{crea_fichero, Compresion};
+use crate::escalar::Escalar;
+use crate::punto::*;
+
+pub const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+pub type Propiedades = Map<String, Value>;
+
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Formato {
+    Tsplib,
+    Pts,
+    Csv,
+    GeoJson,
+    Wkt,
+    Npy,
+}
+
+#[allow(unused)]
+impl Formato {
+    // Por la extension, ignorando la de compresion (.tsp.gz -> Tsplib)
+    pub fn por_extension<I: AsRef<Path>>(path: I) -> Option<Self> {
+        let path = path.as_ref();
+        let mut ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        if matches!(ext.as_str(), "gz" | "zst" | "xz") {
+            ext = Path::new(path.file_stem()?)
+                .extension()?
+                .to_str()?
+                .to_ascii_lowercase();
+        }
+
+        match ext.as_str() {
+            "tsp" => Some(Formato::Tsplib),
+            "pts" => Some(Formato::Pts),
+            "csv" => Some(Formato::Csv),
+            "geojson" | "json" => Some(Formato::GeoJson),
+            "wkt" => Some(Formato::Wkt),
+            "npy" => Some(Formato::Npy),
+            _ => None,
+        }
+    }
+
+    // Por los primeros bytes: magic de .pts/.npy, '{' de JSON, palabra clave
+    // WKT, cabecera TSPLIB ("CLAVE : valor" o NODE_COORD_SECTION) y si no,
+    // CSV cuando la primera linea tiene separadores
+    pub fn detecta(datos: &[u8]) -> Self {
+        if datos.starts_with(&crate::pts::MAGIC) {
+            return Formato::Pts;
+        }
+        if datos.starts_with(NPY_MAGIC) {
+            return Formato::Npy;
+        }
+
+        let inicio = String::from_utf8_lossy(&datos[..datos.len().min(4096)]);
+        let primera = inicio
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .unwrap_or("");
+        let mayusculas = primera.to_ascii_uppercase();
+
+        let clave_tsplib = primera.split_once(':').is_some_and(|(clave, _)| {
+            let clave = clave.trim();
+            !clave.is_empty() && clave.bytes().all(|b| b.is_ascii_uppercase() || b == b'_')
+        });
+
+        if primera.starts_with('{') {
+            Formato::GeoJson
+        } else if ["POINT", "MULTIPOINT", "LINESTRING", "GEOMETRYCOLLECTION"]
+            .iter()
+            .any(|g| mayusculas.starts_with(g))
+        {
+            Formato::Wkt
+        } else if clave_tsplib || primera == "NODE_COORD_SECTION" {
+            Formato::Tsplib
+        } else if primera.contains([',', ';', '\t']) {
+            Formato::Csv
+        } else {
+            Formato::Tsplib
+        }
+    }
+}
+
+fn invalido(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+// Que hacer con coordenadas NaN o infinitas al leer
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NoFinitos {
+    // Error con la linea (y el nodo en TSPLIB) del primero
+    #[default]
+    Rechazar,
+    // Se quitan los puntos con alguna coordenada no finita
+    Descartar,
+    // +-inf al maximo/minimo finito de esa coordenada; un NaN no tiene a
+    // donde ir y el punto se quita
+    Recortar,
+}
+
+impl FromStr for NoFinitos {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" | "rechazar" => Ok(NoFinitos::Rechazar),
+            "drop" | "descartar" => Ok(NoFinitos::Descartar),
+            "clamp" | "recortar" => Ok(NoFinitos::Recortar),
+            _ => Err(format!("politica de no finitos desconocida: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OpcionesLectura {
+    pub csv: OpcionesCsv,
+    pub no_finitos: NoFinitos,
+}
+
+// Lee `datos` (ya descomprimidos) en el formato indicado. Lo que sale de
+// aqui tiene todas las coordenadas finitas.
+#[allow(unused)]
+pub fn lee(datos: &[u8], formato: Formato, opciones: &OpcionesLectura) -> io::Result<Vec<Punto>> {
+    lee_con_propiedades(datos, formato, opciones).map(|(puntos, _)| puntos)
+}
+
+// Como lee, y con las propiedades de cada punto si es GeoJSON (vacio si no)
+#[allow(unused)]
+pub fn lee_con_propiedades(
+    datos: &[u8],
+    formato: Formato,
+    opciones: &OpcionesLectura,
+) -> io::Result<(Vec<Punto>, Vec<Propiedades>)> {
+    let (mut puntos, propiedades) = parsea(datos, formato, opciones)?;
+
+    // JSON no tiene NaN ni infinitos, asi que el filtro no quita ningun
+    // punto con propiedades y siguen emparejadas
+    let leidos = puntos.len();
+    let quitados = aplica_no_finitos(&mut puntos, opciones.no_finitos, None, |p| p)
+        .map_err(|(indice, punto)| no_finito(datos, formato, indice, leidos, punto, "no finita"))?;
+    avisa_quitados(quitados);
+    Ok((puntos, propiedades))
+}
+
+// Como lee, con las coordenadas en T multiplicadas por `escala`. Un valor que
+// no cabe en T (1e39 en f32, 3e9 en i32) cuenta como no finito: con Descartar
+// se quita el punto y si no es un error con su linea. Recortar no lo lleva al
+// maximo de T, eso seria cambiar el punto sin avisar.
+#[allow(unused)]
+pub fn lee_como<T: Escalar>(
+    datos: &[u8],
+    formato: Formato,
+    opciones: &OpcionesLectura,
+    escala: f64,
+) -> io::Result<Vec<Punto<T>>> {
+    let (puntos, _) = parsea(datos, formato, opciones)?;
+    let leidos = puntos.len();
+
+    // Con el indice en el fichero, que Descartar cambia
+    let mut puntos: Vec<(usize, Punto)> = puntos.into_iter().enumerate().collect();
+    let politica = opciones.no_finitos;
+    let mut quitados = aplica_no_finitos(&mut puntos, politica, None, |(_, p)| p)
+        .map_err(|(indice, punto)| no_finito(datos, formato, indice, leidos, punto, "no finita"))?;
+
+    let mut convertidos = Vec::with_capacity(puntos.len());
+    for (indice, punto) in puntos {
+        match Punto::desde(&punto, escala) {
+            Some(convertido) => convertidos.push(convertido),
+            None if politica == NoFinitos::Descartar => quitados += 1,
+            None => {
+                let tipo = format!("fuera de rango para {}", std::any::type_name::<T>());
+                return Err(no_finito(datos, formato, indice, leidos, punto, &tipo));
+            }
+        }
+    }
+    avisa_quitados(quitados);
+    Ok(convertidos)
+}
+
+// Como lee, con una etiqueta L por punto: el campo de detras de las
+// coordenadas en TSPLIB, la columna opciones.csv.etiqueta en CSV y la
+// propiedad con ese nombre en GeoJSON. Sin etiqueta se queda `defecto`. Los
+// no finitos siguen la politica y se llevan su etiqueta con ellos.
+#[allow(unused)]
+pub fn lee_etiquetados<L: FromStr + Clone>(
+    datos: &[u8],
+    formato: Formato,
+    opciones: &OpcionesLectura,
+    defecto: L,
+) -> io::Result<Vec<PuntoEtiquetado<L>>> {
+    let (puntos, textos) = match formato {
+        Formato::Tsplib => (
+            parsea(datos, formato, opciones)?.0,
+            crate::lector::etiquetas_tsp(datos),
+        ),
+        Formato::Csv => lee_csv_con(datos, &opciones.csv, true)?,
+        Formato::GeoJson => {
+            let nombre = match &opciones.csv.etiqueta {
+                Columna::Nombre(nombre) => Some(nombre.as_str()),
+                Columna::Indice(_) => None,
+            };
+            lee_geojson(datos)?
+                .into_iter()
+                .map(|p| {
+                    let texto = nombre.and_then(|n| p.etiqueta.get(n)).map(|v| match v {
+                        Value::String(texto) => texto.clone(),
+                        otro => otro.to_string(),
+                    });
+                    (p.punto, texto)
+                })
+                .unzip()
+        }
+        _ => {
+            let (puntos, _) = parsea(datos, formato, opciones)?;
+            let textos = vec![None; puntos.len()];
+            (puntos, textos)
+        }
+    };
+
+    let leidos = puntos.len();
+    let mut etiquetados = Vec::with_capacity(leidos);
+    for (indice, (punto, texto)) in puntos.into_iter().zip(textos).enumerate() {
+        // Un campo vacio es como si no estuviera
+        let etiqueta = match texto.filter(|t| !t.trim().is_empty()) {
+            Some(texto) => texto.trim().parse().map_err(|_| {
+                let donde = donde(datos, formato, indice, leidos);
+                invalido(format!("{donde}: etiqueta no valida: {texto}"))
+            })?,
+            None => defecto.clone(),
+        };
+        etiquetados.push(PuntoEtiquetado::new(punto, etiqueta));
+    }
+
+    let quitados = aplica_no_finitos(&mut etiquetados, opciones.no_finitos, None, |p| {
+        &mut p.punto
+    })
+    .map_err(|(indice, punto)| no_finito(datos, formato, indice, leidos, punto, "no finita"))?;
+    avisa_quitados(quitados);
+    Ok(etiquetados)
+}
+
+// Los puntos tal cual, sin mirar si son finitos
+fn parsea(
+    datos: &[u8],
+    formato: Formato,
+    opciones: &OpcionesLectura,
+) -> io::Result<(Vec<Punto>, Vec<Propiedades>)> {
+    let mut propiedades = Vec::new();
+    let puntos = match formato {
+        Formato::Tsplib => crate::lector::intenta_tsp_como(datos)
+            .map_err(|campo| invalido(format!("coordenada no valida: {campo}")))?,
+        Formato::Pts => crate::pts::parse_pts(datos)?,
+        Formato::Csv => lee_csv(datos, &opciones.csv)?,
+        Formato::GeoJson => lee_geojson(datos)?
+            .into_iter()
+            .map(|p| {
+                propiedades.push(p.etiqueta);
+                p.punto
+            })
+            .collect(),
+        Formato::Wkt => lee_wkt(datos)?,
+        Formato::Npy => lee_npy(datos)?,
+    };
+    Ok((puntos, propiedades))
+}
+
+// Error con la linea (y el nodo en TSPLIB) del punto `indice` de los
+// `leidos` que ha sacado parsea
+fn no_finito(
+    datos: &[u8],
+    formato: Formato,
+    indice: usize,
+    leidos: usize,
+    punto: Punto,
+    que: &str,
+) -> io::Error {
+    invalido(format!(
+        "{}: coordenada {que} ({}, {})",
+        donde(datos, formato, indice, leidos),
+        punto.x,
+        punto.y
+    ))
+}
+
+// "linea L, nodo N" en TSPLIB, "linea L" en CSV y "punto i" en el resto
+fn donde(datos: &[u8], formato: Formato, indice: usize, leidos: usize) -> String {
+    match formato {
+        Formato::Tsplib => {
+            let (linea, nodo) = crate::lector::ubica_tsp(datos, indice);
+            format!("linea {linea}, nodo {nodo}")
+        }
+        Formato::Csv => format!("linea {}", ubica_csv(datos, indice, leidos)),
+        _ => format!("punto {indice}"),
+    }
+}
+
+// Con Rechazar devuelve el indice y el punto del primero no finito
+#[allow(unused)]
+pub fn filtra_no_finitos(
+    mut puntos: Vec<Punto>,
+    politica: NoFinitos,
+) -> Result<Vec<Punto>, (usize, Punto)> {
+    aplica_no_finitos(&mut puntos, politica, None, |p| p)?;
+    Ok(puntos)
+}
+
+// Extremos finitos de x e y a los que Recortar lleva los infinitos
+#[derive(Clone, Copy, Debug)]
+pub struct RangoFinito {
+    min_x: f64,
+    max_x: f64,
+    min_y: f64,
+    max_y: f64,
+}
+
+#[allow(unused)]
+impl RangoFinito {
+    pub fn vacio() -> Self {
+        RangoFinito {
+            min_x: f64::INFINITY,
+            max_x: f64::NEG_INFINITY,
+            min_y: f64::INFINITY,
+            max_y: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn anade(&mut self, punto: &Punto) {
+        if punto.x.is_finite() {
+            self.min_x = self.min_x.min(punto.x);
+            self.max_x = self.max_x.max(punto.x);
+        }
+        if punto.y.is_finite() {
+            self.min_y = self.min_y.min(punto.y);
+            self.max_y = self.max_y.max(punto.y);
+        }
+    }
+
+    // Ninguna finita: a los extremos de f64
+    fn recorta(v: f64, min: f64, max: f64) -> f64 {
+        if min > max {
+            v.clamp(f64::MIN, f64::MAX)
+        } else {
+            v.clamp(min, max)
+        }
+    }
+
+    fn aplica(&self, punto: &mut Punto) {
+        punto.x = Self::recorta(punto.x, self.min_x, self.max_x);
+        punto.y = Self::recorta(punto.y, self.min_y, self.max_y);
+    }
+}
+
+// Paso comun de todos los lectores. `punto` saca el punto de cada elemento,
+// asi las etiquetas o los pesos se quitan junto con el. Con Recortar el rango
+// puede venir de fuera (el ordenado externo lo calcula en una primera pasada).
+// Devuelve cuantos elementos se han quitado, incluidos los NaN de Recortar
+#[allow(unused)]
+pub fn aplica_no_finitos<E>(
+    elementos: &mut Vec<E>,
+    politica: NoFinitos,
+    rango: Option<RangoFinito>,
+    punto: fn(&mut E) -> &mut Punto,
+) -> Result<usize, (usize, Punto)> {
+    let finito = |p: &Punto| p.x.is_finite() && p.y.is_finite();
+    let antes = elementos.len();
+
+    match politica {
+        NoFinitos::Rechazar => {
+            if let Some(indice) = elementos.iter_mut().position(|e| !finito(punto(e))) {
+                return Err((indice, *punto(&mut elementos[indice])));
+            }
+        }
+        NoFinitos::Descartar => elementos.retain_mut(|e| finito(punto(e))),
+        NoFinitos::Recortar => {
+            let rango = rango.unwrap_or_else(|| {
+                let mut rango = RangoFinito::vacio();
+                for elemento in elementos.iter_mut() {
+                    rango.anade(punto(elemento));
+                }
+                rango
+            });
+            elementos.retain_mut(|e| {
+                let p = punto(e);
+                !p.x.is_nan() && !p.y.is_nan()
+            });
+            for elemento in elementos.iter_mut() {
+                rango.aplica(punto(elemento));
+            }
+        }
+    }
+    Ok(antes - elementos.len())
+}
+
+// Los lectores avisan por stderr de lo que se ha quitado
+#[allow(unused)]
+pub fn avisa_quitados(quitados: usize) {
+    if quitados > 0 {
+        eprintln!("aviso: {quitados} puntos con coordenadas no finitas quitados");
+    }
+}
+
+#[allow(unused)]
+pub fn escribe<I: AsRef<Path>>(
+    name: I,
+    puntos: &[Punto],
+    formato: Formato,
+    compresion: Compresion,
+) -> io::Result<()> {
+    escribe_con_propiedades(name, puntos, &[], formato, compresion)
+}
+
+// Las propiedades solo se escriben en GeoJSON; un punto sin ellas (porque
+// `propiedades` es mas corto) sale con las propiedades vacias
+#[allow(unused)]
+pub fn escribe_con_propiedades<I: AsRef<Path>>(
+    name: I,
+    puntos: &[Punto],
+    propiedades: &[Propiedades],
+    formato: Formato,
+    compresion: Compresion,
+) -> io::Result<()> {
+    let ordenado = puntos.windows(2).all(|w| w[0].x <= w[1].x);
+    if formato == Formato::Pts {
+        return crate::pts::write_pts_compressed(name, puntos, ordenado, compresion);
+    }
+
+    let mut file = crea_fichero(name, compresion)?;
+    match formato {
+        Formato::Tsplib => {
+            file.write_all(b"NODE_COORD_SECTION\n")?;
+            for (i, punto) in puntos.iter().enumerate() {
+                writeln!(file, "{} {} {}", i + 1, punto.x, punto.y)?;
+            }
+        }
+        Formato::Pts => unreachable!(),
+        Formato::Csv => escribe_csv(&mut file, puntos)?,
+        Formato::GeoJson => escribe_geojson(
+            &mut file,
+            puntos.iter().enumerate().map(|(i, p)| {
+                PuntoEtiquetado::new(*p, propiedades.get(i).cloned().unwrap_or_default())
+            }),
+        )?,
+        Formato::Wkt => escribe_wkt(&mut file, puntos)?,
+        Formato::Npy => escribe_npy(&mut file, puntos)?,
+    }
+    file.finish().map(drop)
+}
+
+// ---------------------------------------------------------------- CSV
+
+// Columna por posicion (desde 0) o por nombre de la cabecera
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Columna {
+    Indice(usize),
+    Nombre(String),
+}
+
+impl FromStr for Columna {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(i) => Columna::Indice(i),
+            Err(_) => Columna::Nombre(s.to_string()),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OpcionesCsv {
+    pub separador: u8,
+    pub x: Columna,
+    pub y: Columna,
+    // Solo para lee_etiquetados; si no esta, todos con la etiqueta por defecto
+    pub etiqueta: Columna,
+}
+
+impl Default for OpcionesCsv {
+    fn default() -> Self {
+        Self {
+            separador: b',',
+            x: Columna::Nombre("x".to_string()),
+            y: Columna::Nombre("y".to_string()),
+            etiqueta: Columna::Nombre("label".to_string()),
+        }
+    }
+}
+
+// Campos de una linea CSV, con comillas dobles y "" como escape
+fn campos_csv(linea: &str, separador: char) -> Vec<String> {
+    let mut campos = Vec::new();
+    let mut campo = String::new();
+    let mut comillas = false;
+    let mut chars = linea.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if comillas && chars.peek() == Some(&'"') => {
+                campo.push('"');
+                chars.next();
+            }
+            '"' => comillas = !comillas,
+            c if c == separador && !comillas => campos.push(std::mem::take(&mut campo)),
+            c => campo.push(c),
+        }
+    }
+    campos.push(campo);
+    campos
+}
+
+// La primera linea es cabecera si alguna de las columnas pedidas no es un numero.
+// Con columnas por nombre la cabecera es obligatoria.
+#[allow(unused)]
+pub fn lee_csv(datos: &[u8], opciones: &OpcionesCsv) -> io::Result<Vec<Punto>> {
+    lee_csv_con(datos, opciones, false).map(|(puntos, _)| puntos)
+}
+
+// Con `etiquetas`, tambien el campo de la columna de etiqueta de cada punto
+fn lee_csv_con(
+    datos: &[u8],
+    opciones: &OpcionesCsv,
+    etiquetas: bool,
+) -> io::Result<(Vec<Punto>, Vec<Option<String>>)> {
+    let texto = std::str::from_utf8(datos).map_err(|e| invalido(e.to_string()))?;
+    let separador = opciones.separador as char;
+    let mut lineas = texto
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+
+    let Some((_, primera)) = lineas.next() else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    let primera = campos_csv(primera, separador);
+
+    let indice = |columna: &Columna| -> io::Result<usize> {
+        match columna {
+            Columna::Indice(i) => Ok(*i),
+            Columna::Nombre(nombre) => primera
+                .iter()
+                .position(|c| c.trim().eq_ignore_ascii_case(nombre))
+                .ok_or_else(|| invalido(format!("columna {nombre} no esta en la cabecera"))),
+        }
+    };
+    let (ix, iy) = (indice(&opciones.x)?, indice(&opciones.y)?);
+    // Una columna de etiqueta que no esta en la cabecera no es un error
+    let ietiqueta = indice(&opciones.etiqueta).ok().filter(|_| etiquetas);
+    let etiqueta = |campos: &[String]| ietiqueta.and_then(|i| campos.get(i).cloned());
+
+    let parse = |campos: &[String], linea: usize| -> io::Result<Punto> {
+        let valor = |i: usize| {
+            campos
+                .get(i)
+                .and_then(|c| c.trim().parse::<f64>().ok())
+                .ok_or_else(|| invalido(format!("linea {}: coordenada no valida", linea + 1)))
+        };
+        Ok(Punto {
+            x: valor(ix)?,
+            y: valor(iy)?,
+        })
+    };
+
+    let (mut puntos, mut textos) = (Vec::new(), Vec::new());
+    if let Ok(punto) = parse(&primera, 0) {
+        puntos.push(punto);
+        textos.push(etiqueta(&primera));
+    }
+    for (n, linea) in lineas {
+        let campos = campos_csv(linea, separador);
+        puntos.push(parse(&campos, n)?);
+        textos.push(etiqueta(&campos));
+    }
+    if !etiquetas {
+        textos.clear();
+    }
+    Ok((puntos, textos))
+}
+
+// Linea (desde 1) del punto `indice` de los `leidos` por lee_csv: las
+// lineas vacias no cuentan y si sobra una es la cabecera
+fn ubica_csv(datos: &[u8], indice: usize, leidos: usize) -> usize {
+    let lineas: Vec<usize> = String::from_utf8_lossy(datos)
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(n, _)| n + 1)
+        .collect();
+    lineas[indice + lineas.len() - leidos]
+}
+
+#[allow(unused)]
+pub fn escribe_csv(writer: &mut impl Write, puntos: &[Punto]) -> io::Result<()> {
+    writer.write_all(b"x,y\n")?;
+    for punto in puntos {
+        writeln!(writer, "{},{}", punto.x, punto.y)?;
+    }
+    Ok(())
+}
+
+// ------------------------------------------------------------ GeoJSON
+
+fn coordenadas(valor: &Value) -> io::Result<Punto> {
+    match valor.as_array().map(Vec::as_slice) {
+        Some([x, y, ..]) => match (x.as_f64(), y.as_f64()) {
+            (Some(x), Some(y)) => Ok(Punto { x, y }),
+            _ => Err(invalido("coordenadas no numericas")),
+        },
+        _ => Err(invalido("coordenadas no validas")),
+    }
+}
+
+// Puntos de un FeatureCollection (o un Feature suelto) con geometrias Point
+// o MultiPoint; las propiedades de cada feature se copian en cada punto
+#[allow(unused)]
+pub fn lee_geojson(datos: &[u8]) -> io::Result<Vec<PuntoEtiquetado<Propiedades>>> {
+    let raiz: Value = serde_json::from_slice(datos).map_err(io::Error::from)?;
+    let features = match raiz["type"].as_str() {
+        Some("FeatureCollection") => raiz["features"]
+            .as_array()
+            .ok_or_else(|| invalido("FeatureCollection sin features"))?
+            .iter()
+            .collect(),
+        Some("Feature") => vec![&raiz],
+        _ => return Err(invalido("se esperaba un FeatureCollection o Feature")),
+    };
+
+    let mut puntos = Vec::with_capacity(features.len());
+    for feature in features {
+        let propiedades = feature["properties"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        let geometria = &feature["geometry"];
+
+        match geometria["type"].as_str() {
+            Some("Point") => puntos.push(PuntoEtiquetado::new(
+                coordenadas(&geometria["coordinates"])?,
+                propiedades,
+            )),
+            Some("MultiPoint") => {
+                for valor in geometria["coordinates"].as_array().into_iter().flatten() {
+                    puntos.push(PuntoEtiquetado::new(
+                        coordenadas(valor)?,
+                        propiedades.clone(),
+                    ));
+                }
+            }
+            Some(otro) => return Err(invalido(format!("geometria {otro} no soportada"))),
+            None => return Err(invalido("feature sin geometria")),
+        }
+    }
+    Ok(puntos)
+}
+
+#[allow(unused)]
+pub fn escribe_geojson(
+    writer: &mut impl Write,
+    puntos: impl IntoIterator<Item = PuntoEtiquetado<Propiedades>>,
+) -> io::Result<()> {
+    let features: Vec<Value> = puntos
+        .into_iter()
+        .map(|p| {
+            json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [p.punto.x, p.punto.y] },
+                "properties": p.etiqueta,
+            })
+        })
+        .collect();
+
+    serde_json::to_writer(
+        &mut *writer,
+        &json!({ "type": "FeatureCollection", "features": features }),
+    )?;
+    writer.write_all(b"\n")
+}
+
+// El trio ganador como LineString en el orden de get_points
+#[allow(unused)]
+pub fn triple_geojson(puntos: &[Punto], indices: [usize; 3], distancia: f64) -> Value {
+    let coordenadas: Vec<[f64; 2]> = indices
+        .iter()
+        .map(|&i| [puntos[i].x, puntos[i].y])
+        .collect();
+    json!({
+        "type": "Feature",
+        "geometry": { "type": "LineString", "coordinates": coordenadas },
+        "properties": { "distancia": distancia, "indices": indices },
+    })
+}
+
+// ---------------------------------------------------------------- WKT
+
+// Acepta POINT, MULTIPOINT (con o sin parentesis por punto) y LINESTRING,
+// una o varias geometrias seguidas; de cada coordenada se cogen x e y
+#[allow(unused)]
+pub fn lee_wkt(datos: &[u8]) -> io::Result<Vec<Punto>> {
+    let texto = std::str::from_utf8(datos).map_err(|e| invalido(e.to_string()))?;
+    let mut puntos = Vec::new();
+
+    for trozo in texto.split(['(', ')', ',', '\n', ';']) {
+        let trozo = trozo.trim();
+        if trozo.is_empty() || trozo.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            continue;
+        }
+
+        let mut valores = trozo.split_whitespace().map(str::parse::<f64>);
+        match (valores.next(), valores.next()) {
+            (Some(Ok(x)), Some(Ok(y))) => puntos.push(Punto { x, y }),
+            _ => return Err(invalido(format!("coordenada WKT no valida: {trozo}"))),
+        }
+    }
+    Ok(puntos)
+}
+
+#[allow(unused)]
+pub fn escribe_wkt(writer: &mut impl Write, puntos: &[Punto]) -> io::Result<()> {
+    writer.write_all(b"MULTIPOINT (")?;
+    for (i, punto) in puntos.iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b", ")?;
+        }
+        write!(writer, "({} {})", punto.x, punto.y)?;
+    }
+    writer.write_all(b")\n")
+}
+
+// ---------------------------------------------------------------- NPY
+
+// Array (n, k>=2) de '<f8' o '<f4', en orden C o Fortran; x e y son las dos
+// primeras columnas
+#[allow(unused)]
+pub fn lee_npy(datos: &[u8]) -> io::Result<Vec<Punto>> {
+    if !datos.starts_with(NPY_MAGIC) || datos.len() < 10 {
+        return Err(invalido("no es un fichero .npy"));
+    }
+
+    let (tam_cabecera, inicio) = match datos[6] {
+        1 => (u16::from_le_bytes([datos[8], datos[9]]) as usize, 10),
+        2 | 3 if datos.len() >= 12 => (
+            u32::from_le_bytes(datos[8..12].try_into().unwrap()) as usize,
+            12,
+        ),
+        _ => return Err(invalido("version de .npy no soportada")),
+    };
+    let cabecera = datos
+        .get(inicio..inicio + tam_cabecera)
+        .and_then(|c| std::str::from_utf8(c).ok())
+        .ok_or_else(|| invalido("cabecera .npy no valida"))?;
+    let cuerpo = &datos[inicio + tam_cabecera..];
+
+    let valor = |clave: &str| {
+        let resto = &cabecera[cabecera.find(&format!("'{clave}'"))? + clave.len() + 2..];
+        Some(resto.trim_start().strip_prefix(':')?.trim_start())
+    };
+
+    let bytes = match valor("descr") {
+        Some(d) if d.starts_with("'<f8'") => 8,
+        Some(d) if d.starts_with("'<f4'") => 4,
+        _ => return Err(invalido("dtype de .npy no soportado, se espera <f8 o <f4")),
+    };
+    let fortran = valor("fortran_order").is_some_and(|v| v.starts_with("True"));
+    let forma: Vec<usize> = valor("shape")
+        .and_then(|v| v.strip_prefix('(')?.split(')').next())
+        .map(|v| {
+            v.split(',')
+                .filter(|d| !d.trim().is_empty())
+                .map(|d| d.trim().parse())
+                .collect::<Result<_, _>>()
+        })
+        .and_then(Result::ok)
+        .ok_or_else(|| invalido("shape de .npy no valido"))?;
+
+    let [n, k] = forma[..] else {
+        return Err(invalido("se espera un array de dos dimensiones"));
+    };
+    if k < 2 {
+        return Err(invalido("el array necesita al menos dos columnas"));
+    }
+    let tam = n
+        .checked_mul(k)
+        .and_then(|nk| nk.checked_mul(bytes))
+        .ok_or_else(|| invalido("shape de .npy demasiado grande"))?;
+    if cuerpo.len() < tam {
+        return Err(invalido("datos de .npy incompletos"));
+    }
+
+    let lee = |i: usize| -> f64 {
+        let b = &cuerpo[i * bytes..(i + 1) * bytes];
+        match bytes {
+            8 => f64::from_le_bytes(b.try_into().unwrap()),
+            _ => f32::from_le_bytes(b.try_into().unwrap()) as f64,
+        }
+    };
+    Ok((0..n)
+        .map(|i| match fortran {
+            false => Punto {
+                x: lee(i * k),
+                y: lee(i * k + 1),
+            },
+            true => Punto {
+                x: lee(i),
+                y: lee(n + i),
+            },
+        })
+        .collect())
+}
+
+#[allow(unused)]
+pub fn escribe_npy(writer: &mut impl Write, puntos: &[Punto]) -> io::Result<()> {
+    let mut cabecera = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, 2), }}",
+        puntos.len()
+    );
+    // magic + version + longitud + cabecera + '\n' multiplo de 64
+    while (10 + cabecera.len() + 1) % 64 != 0 {
+        cabecera.push(' ');
+    }
+    cabecera.push('\n');
+
+    writer.write_all(NPY_MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(cabecera.len() as u16).to_le_bytes())?;
+    writer.write_all(cabecera.as_bytes())?;
+    for punto in puntos {
+        writer.write_all(&punto.x.to_le_bytes())?;
+        writer.write_all(&punto.y.to_le_bytes())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::punto::*;
+
+// Intentos por punto en Poisson-disk antes de dar la caja por llena
+const INTENTOS_POISSON: usize = 64;
+
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub enum Distribucion {
+    // Uniforme en [min_x, max_x) x [min_y, max_y)
+    Caja {
+        min_x: f64,
+        max_x: f64,
+        min_y: f64,
+        max_y: f64,
+    },
+    // Uniforme en el disco
+    Disco {
+        centro: Punto,
+        radio: f64,
+    },
+    // `clusters` centros uniformes en [0, lado)^2 y puntos normales alrededor
+    Clusters {
+        clusters: usize,
+        lado: f64,
+        sigma: f64,
+    },
+    // Dart throwing en [0, lado)^2 sin dos puntos a menos de `radio`; se
+    // acaba cuando no cabe ninguno mas
+    PoissonDisk {
+        lado: f64,
+        radio: f64,
+    },
+    // Reticula de `columnas` de ancho, por filas, con ruido uniforme en
+    // [-ruido, ruido] en cada coordenada
+    Reticula {
+        columnas: usize,
+        paso: f64,
+        ruido: f64,
+    },
+    // Uniforme en el segmento
+    Linea {
+        inicio: Punto,
+        fin: Punto,
+    },
+    // Uniforme en la circunferencia
+    Circulo {
+        centro: Punto,
+        radio: f64,
+    },
+    // Solo `distintos` puntos diferentes en [0, lado)^2, repetidos
+    Duplicados {
+        distintos: usize,
+        lado: f64,
+    },
+
+    // Casos malos para las franjas del DyV:
+    // x pegadas (a +-ancho) a los cortes diadicos m * lado / 2^niveles, que es
+    // donde caen las medianas de cada nivel, e y uniforme en [0, lado)
+    CercaDeCortes {
+        niveles: u32,
+        lado: f64,
+        ancho: f64,
+    },
+    // Solo `columnas` x distintas en [0, lado) y cualquier y
+    Columnas {
+        columnas: usize,
+        lado: f64,
+    },
+    // +-(1 + u) * 2^e con e uniforme en [-exponente, exponente] en cada coordenada
+    Exponencial {
+        exponente: i32,
+    },
+}
+
+#[allow(unused)]
+impl Distribucion {
+    pub fn caja(min: f64, max: f64) -> Self {
+        Distribucion::Caja {
+            min_x: min,
+            max_x: max,
+            min_y: min,
+            max_y: max,
+        }
+    }
+
+    // Parametros por defecto para `n` puntos en [0, lado)^2, para la CLI
+    pub fn por_nombre(nombre: &str, n: usize, lado: f64) -> Option<Self> {
+        let centro = Punto {
+            x: lado / 2.0,
+            y: lado / 2.0,
+        };
+        let columnas = (n as f64).sqrt().ceil().max(1.0) as usize;
+
+        Some(match nombre {
+            "caja" => Distribucion::caja(0.0, lado),
+            "disco" => Distribucion::Disco {
+                centro,
+                radio: lado / 2.0,
+            },
+            "clusters" => Distribucion::Clusters {
+                clusters: 10,
+                lado,
+                sigma: lado / 100.0,
+            },
+            // ~ la mitad de lo que admite el dart throwing antes de llenarse
+            "poisson" => Distribucion::PoissonDisk {
+                lado,
+                radio: 0.6 * lado / (n.max(1) as f64).sqrt(),
+            },
+            "reticula" => Distribucion::Reticula {
+                columnas,
+                paso: lado / columnas as f64,
+                ruido: lado / columnas as f64 / 10.0,
+            },
+            "linea" => Distribucion::Linea {
+                inicio: Punto { x: 0.0, y: 0.0 },
+                fin: Punto { x: lado, y: lado },
+            },
+            "circulo" => Distribucion::Circulo {
+                centro,
+                radio: lado / 2.0,
+            },
+            "duplicados" => Distribucion::Duplicados {
+                distintos: (n / 100).max(1),
+                lado,
+            },
+            "vertical" => Distribucion::Linea {
+                inicio: Punto {
+                    x: centro.x,
+                    y: 0.0,
+                },
+                fin: Punto {
+                    x: centro.x,
+                    y: lado,
+                },
+            },
+            "cortes" => Distribucion::CercaDeCortes {
+                niveles: (n.max(2) as f64).log2() as u32,
+                lado,
+                ancho: lado * 1e-9,
+            },
+            "columnas" => Distribucion::Columnas {
+                columnas: columnas.min(64),
+                lado,
+            },
+            "exponencial" => Distribucion::Exponencial { exponente: 60 },
+            _ => return None,
+        })
+    }
+}
+
+// Iterador infinito de puntos (salvo PoissonDisk), reproducible con la semilla
+#[allow(unused)]
+pub struct Generador {
+    rng: StdRng,
+    distribucion: Distribucion,
+    generados: usize,
+    // Centros de Clusters o puntos base de Duplicados
+    base: Vec<Punto>,
+    // Rejilla de PoissonDisk, celdas de radio/sqrt(2) con un punto como mucho
+    rejilla: HashMap<(i64, i64), Punto>,
+}
+
+#[allow(unused)]
+impl Generador {
+    pub fn new(distribucion: Distribucion, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let base = match &distribucion {
+            Distribucion::Clusters { clusters, lado, .. } => {
+                uniformes(&mut rng, (*clusters).max(1), *lado)
+            }
+            Distribucion::Duplicados { distintos, lado } => {
+                uniformes(&mut rng, (*distintos).max(1), *lado)
+            }
+            _ => Vec::new(),
+        };
+
+        Self {
+            rng,
+            distribucion,
+            generados: 0,
+            base,
+            rejilla: HashMap::new(),
+        }
+    }
+
+    fn normal(&mut self) -> f64 {
+        // Box-Muller, u1 en (0, 1] para no hacer ln(0)
+        let u1: f64 = 1.0 - self.rng.gen::<f64>();
+        let u2: f64 = self.rng.gen();
+        (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos()
+    }
+
+    fn poisson(&mut self, lado: f64, radio: f64) -> Option<Punto> {
+        let celda = radio / std::f64::consts::SQRT_2;
+        let clave = |p: &Punto| ((p.x / celda) as i64, (p.y / celda) as i64);
+
+        for _ in 0..INTENTOS_POISSON {
+            let candidato = Punto {
+                x: self.rng.gen_range(0.0..lado),
+                y: self.rng.gen_range(0.0..lado),
+            };
+            let (cx, cy) = clave(&candidato);
+
+            let libre = (cx - 2..=cx + 2)
+                .flat_map(|x| (cy - 2..=cy + 2).map(move |y| (x, y)))
+                .filter_map(|c| self.rejilla.get(&c))
+                .all(|p| p.distancia(&candidato) >= radio);
+
+            if libre {
+                self.rejilla.insert((cx, cy), candidato);
+                return Some(candidato);
+            }
+        }
+        None
+    }
+}
+
+fn uniformes(rng: &mut StdRng, n: usize, lado: f64) -> Vec<Punto> {
+    (0..n)
+        .map(|_| Punto {
+            x: rng.gen_range(0.0..lado),
+            y: rng.gen_range(0.0..lado),
+        })
+        .collect()
+}
+
+impl Iterator for Generador {
+    type Item = Punto;
+
+    fn next(&mut self) -> Option<Punto> {
+        let punto = match self.distribucion.clone() {
+            Distribucion::Caja {
+                min_x,
+                max_x,
+                min_y,
+                max_y,
+            } => Punto {
+                x: self.rng.gen_range(min_x..max_x),
+                y: self.rng.gen_range(min_y..max_y),
+            },
+            Distribucion::Disco { centro, radio } => {
+                let r = radio * self.rng.gen::<f64>().sqrt();
+                let angulo = self.rng.gen_range(0.0..TAU);
+                Punto {
+                    x: centro.x + r * angulo.cos(),
+                    y: centro.y + r * angulo.sin(),
+                }
+            }
+            Distribucion::Clusters { sigma, .. } => {
+                let centro = self.base[self.rng.gen_range(0..self.base.len())];
+                Punto {
+                    x: centro.x + sigma * self.normal(),
+                    y: centro.y + sigma * self.normal(),
+                }
+            }
+            Distribucion::PoissonDisk { lado, radio } => self.poisson(lado, radio)?,
+            Distribucion::Reticula {
+                columnas,
+                paso,
+                ruido,
+            } => {
+                let columnas = columnas.max(1);
+                let (fila, columna) = (self.generados / columnas, self.generados % columnas);
+                let mut desplaza = || {
+                    if ruido > 0.0 {
+                        self.rng.gen_range(-ruido..=ruido)
+                    } else {
+                        0.0
+                    }
+                };
+                Punto {
+                    x: columna as f64 * paso + desplaza(),
+                    y: fila as f64 * paso + desplaza(),
+                }
+            }
+            Distribucion::Linea { inicio, fin } => {
+                let t: f64 = self.rng.gen();
+                Punto {
+                    x: inicio.x + t * (fin.x - inicio.x),
+                    y: inicio.y + t * (fin.y - inicio.y),
+                }
+            }
+            Distribucion::Circulo { centro, radio } => {
+                let angulo = self.rng.gen_range(0.0..TAU);
+                Punto {
+                    x: centro.x + radio * angulo.cos(),
+                    y: centro.y + radio * angulo.sin(),
+                }
+            }
+            Distribucion::Duplicados { .. } => self.base[self.rng.gen_range(0..self.base.len())],
+            Distribucion::CercaDeCortes {
+                niveles,
+                lado,
+                ancho,
+            } => {
+                let cortes = 1u64 << niveles.min(62);
+                let corte = self.rng.gen_range(1..cortes.max(2)) as f64;
+                let ruido = if ancho > 0.0 {
+                    self.rng.gen_range(-ancho..=ancho)
+                } else {
+                    0.0
+                };
+                Punto {
+                    x: corte * lado / cortes as f64 + ruido,
+                    y: self.rng.gen_range(0.0..lado),
+                }
+            }
+            Distribucion::Columnas { columnas, lado } => {
+                let columnas = columnas.max(1);
+                Punto {
+                    x: self.rng.gen_range(0..columnas) as f64 * lado / columnas as f64,
+                    y: self.rng.gen_range(0.0..lado),
+                }
+            }
+            Distribucion::Exponencial { exponente } => {
+                let mut coordenada = || {
+                    let e = self.rng.gen_range(-exponente..=exponente);
+                    let signo = if self.rng.gen() { 1.0 } else { -1.0 };
+                    signo * (1.0 + self.rng.gen::<f64>()) * 2f64.powi(e)
+                };
+                Punto {
+                    x: coordenada(),
+                    y: coordenada(),
+                }
+            }
+        };
+
+        self.generados += 1;
+        Some(punto)
+    }
+}
+
+// `n` puntos (o menos si es un PoissonDisk que se llena)
+#[allow(unused)]
+pub fn genera(distribucion: Distribucion, n: usize, seed: u64) -> Vec<Punto> {
+    Generador::new(distribucion, seed).take(n).collect()
+}
+
+// Entradas que castigan las franjas y la ventana en y del DyV, ya ordenadas
+#[allow(unused)]
+pub const ADVERSARIOS: [&str; 5] = [
+    "vertical",
+    "cortes",
+    "columnas",
+    "exponencial",
+    "duplicados",
+];
+
+#[allow(unused)]
+pub fn adversarios(n: usize, seed: u64) -> Vec<(&'static str, Vec<Punto>)> {
+    ADVERSARIOS
+        .iter()
+        .map(|nombre| {
+            let distribucion = Distribucion::por_nombre(nombre, n, 1000.0).unwrap();
+            let mut puntos = genera(distribucion, n, seed);
+            puntos.sort();
+            (*nombre, puntos)
+        })
+        .collect()
+}
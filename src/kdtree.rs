@@ -0,0 +1,244 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::punto::*;
+
+const MAX: f64 = f64::MAX;
+
+// Arbol implicito: cada rango [lo, hi) tiene su nodo en la mitad y
+// el eje de corte alterna con la profundidad (x en pares, y en impares)
+pub struct KdTree<'a> {
+    puntos: &'a [Punto],
+    indices: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Candidato {
+    distancia: f64,
+    index: usize,
+}
+
+impl PartialEq for Candidato {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidato {}
+
+impl PartialOrd for Candidato {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidato {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distancia
+            .total_cmp(&other.distancia)
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+#[allow(unused)]
+impl<'a> KdTree<'a> {
+    pub fn new(puntos: &'a [Punto]) -> Self {
+        let mut indices: Vec<usize> = (0..puntos.len()).collect();
+        Self::construye(puntos, &mut indices, 0);
+        Self { puntos, indices }
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    fn construye(puntos: &[Punto], indices: &mut [usize], depth: usize) {
+        if indices.len() <= 1 {
+            return;
+        }
+
+        let mitad = indices.len() / 2;
+        indices.select_nth_unstable_by(mitad, |a, b| {
+            Self::coordenada(&puntos[*a], depth).total_cmp(&Self::coordenada(&puntos[*b], depth))
+        });
+
+        let (first_half, second_half) = indices.split_at_mut(mitad);
+        Self::construye(puntos, first_half, depth + 1);
+        Self::construye(puntos, &mut second_half[1..], depth + 1);
+    }
+
+    #[inline(always)]
+    fn coordenada(punto: &Punto, depth: usize) -> f64 {
+        if depth & 1 == 0 {
+            punto.x
+        } else {
+            punto.y
+        }
+    }
+
+    pub fn nearest(&self, punto: &Punto, k: usize) -> Vec<(usize, f64)> {
+        self.nearest_filtered(punto, k, |_| true)
+    }
+
+    // Los k vecinos mas cercanos entre los indices que acepta el filtro,
+    // ordenados por distancia
+    pub fn nearest_filtered(
+        &self,
+        punto: &Punto,
+        k: usize,
+        filtro: impl Fn(usize) -> bool,
+    ) -> Vec<(usize, f64)> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        self.busca_knn(0, self.indices.len(), 0, punto, k, &filtro, &mut heap);
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|c| (c.index, c.distancia))
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn busca_knn(
+        &self,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        punto: &Punto,
+        k: usize,
+        filtro: &impl Fn(usize) -> bool,
+        heap: &mut BinaryHeap<Candidato>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+
+        let mitad = lo + (hi - lo) / 2;
+        let index = self.indices[mitad];
+        let nodo = &self.puntos[index];
+
+        if filtro(index) {
+            let distancia = punto.distancia(nodo);
+            if heap.len() < k {
+                heap.push(Candidato { distancia, index });
+            } else if distancia < heap.peek().unwrap().distancia {
+                heap.pop();
+                heap.push(Candidato { distancia, index });
+            }
+        }
+
+        let diff = Self::coordenada(punto, depth) - Self::coordenada(nodo, depth);
+        let (cerca, lejos) = if diff < 0.0 {
+            ((lo, mitad), (mitad + 1, hi))
+        } else {
+            ((mitad + 1, hi), (lo, mitad))
+        };
+
+        self.busca_knn(cerca.0, cerca.1, depth + 1, punto, k, filtro, heap);
+
+        let peor = if heap.len() < k {
+            MAX
+        } else {
+            heap.peek().unwrap().distancia
+        };
+
+        if diff.abs() < peor {
+            self.busca_knn(lejos.0, lejos.1, depth + 1, punto, k, filtro, heap);
+        }
+    }
+
+    // Todos los puntos a distancia <= radio, ordenados por distancia
+    pub fn within_radius(&self, punto: &Punto, radio: f64) -> Vec<(usize, f64)> {
+        let mut res = Vec::new();
+        self.busca_radio(0, self.indices.len(), 0, punto, radio, &mut res);
+        res.sort_unstable_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        res
+    }
+
+    fn busca_radio(
+        &self,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        punto: &Punto,
+        radio: f64,
+        res: &mut Vec<(usize, f64)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+
+        let mitad = lo + (hi - lo) / 2;
+        let index = self.indices[mitad];
+        let nodo = &self.puntos[index];
+
+        let distancia = punto.distancia(nodo);
+        if distancia <= radio {
+            res.push((index, distancia));
+        }
+
+        let diff = Self::coordenada(punto, depth) - Self::coordenada(nodo, depth);
+        if diff - radio <= 0.0 {
+            self.busca_radio(lo, mitad, depth + 1, punto, radio, res);
+        }
+        if diff + radio >= 0.0 {
+            self.busca_radio(mitad + 1, hi, depth + 1, punto, radio, res);
+        }
+    }
+}
+
+// Solver de comparacion: con j fijo como punto central el mejor camino i-j-k
+// usa los dos vecinos mas cercanos de j, asi que basta una consulta 2-NN por punto
+#[allow(unused)]
+pub struct TripleKd<'a> {
+    puntos: &'a [Punto],
+    tree: KdTree<'a>,
+    best_option: BestPoint,
+    best_points: [usize; 3],
+}
+
+#[allow(unused)]
+impl<'a> TripleKd<'a> {
+    pub fn new(puntos: &'a [Punto]) -> Self {
+        Self {
+            puntos,
+            tree: KdTree::new(puntos),
+            best_option: MAX,
+            best_points: [0; 3],
+        }
+    }
+
+    pub fn from_tree(tree: KdTree<'a>) -> Self {
+        Self {
+            puntos: tree.puntos,
+            tree,
+            best_option: MAX,
+            best_points: [0; 3],
+        }
+    }
+
+    pub fn start(&mut self) -> BestPoint {
+        for (j, punto_j) in self.puntos.iter().enumerate() {
+            let vecinos = self.tree.nearest_filtered(punto_j, 2, |index| index != j);
+            if let [(i, distancia_ij), (k, distancia_jk)] = vecinos[..] {
+                let distancia = distancia_ij + distancia_jk;
+                if distancia < self.best_option {
+                    self.best_option = distancia;
+                    self.best_points = [i, j, k];
+                }
+            }
+        }
+        self.best_option
+    }
+
+    pub fn get_points(&self) -> [usize; 3] {
+        self.best_points
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Juego de instrucciones con el que corre DyVSIMD. Sse2 es lo que da el target
+// por defecto (en x86_64 siempre esta); Avx2 y Avx512 son el mismo codigo
+// compilado con esas extensiones, y con Avx512 los vectores son el doble de
+// anchos (f64x8).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    Sse2,
+    Avx2,
+    Avx512,
+}
+
+#[allow(unused)]
+impl Kernel {
+    pub const TODOS: [Kernel; 3] = [Kernel::Sse2, Kernel::Avx2, Kernel::Avx512];
+
+    // El mejor que tenga la CPU en la que se ejecuta
+    pub fn detecta() -> Self {
+        Self::TODOS
+            .into_iter()
+            .rev()
+            .find(|kernel| kernel.disponible())
+            .unwrap_or(Kernel::Sse2)
+    }
+
+    pub fn disponible(self) -> bool {
+        match self {
+            Kernel::Sse2 => true,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx512 => is_x86_feature_detected!("avx512f"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kernel::Sse2 => "sse2",
+            Kernel::Avx2 => "avx2",
+            Kernel::Avx512 => "avx512",
+        })
+    }
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sse2" => Ok(Kernel::Sse2),
+            "avx2" => Ok(Kernel::Avx2),
+            "avx512" | "avx-512" => Ok(Kernel::Avx512),
+            otro => Err(format!("kernel desconocido: {otro} (sse2, avx2, avx512)")),
+        }
+    }
+}
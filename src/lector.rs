@@ -0,0 +1,373 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use memmap2::Mmap;
+
+use crate::compresion::{descomprime, Compresion};
+use crate::escalar::Escalar;
+use crate::formatos::{self, Formato, OpcionesLectura, Propiedades};
+use crate::punto::*;
+
+const SECCION: &[u8] = b"NODE_COORD_SECTION";
+// Por debajo de esto no compensa lanzar hilos
+const MIN_BYTES_POR_HILO: usize = 1 << 20;
+
+// Lector TSPLIB sobre el buffer de bytes: sin un String por linea ni un Vec
+// por campo, y repartiendo el cuerpo en bloques alineados a linea que se
+// parsean en paralelo. Los floats van por fast_float2, que redondea
+// correctamente igual que `str::parse::<f64>`, asi que los valores son
+// exactamente los mismos que con el lector anterior.
+#[allow(unused)]
+pub fn read_points_fast<I: AsRef<Path>>(file_name: I) -> Vec<Punto> {
+    read_points_with(file_name, &OpcionesLectura::default())
+}
+
+// El formato sale de la extension (.csv, .geojson, .wkt, .npy...) o, si no
+// hay una conocida, del contenido. "-" es la entrada estandar.
+#[allow(unused)]
+pub fn read_points_with<I: AsRef<Path>>(file_name: I, opciones: &OpcionesLectura) -> Vec<Punto> {
+    read_points_con_propiedades(file_name, opciones).0
+}
+
+// Como read_points_with, con las propiedades de cada punto de un GeoJSON
+#[allow(unused)]
+pub fn read_points_con_propiedades<I: AsRef<Path>>(
+    file_name: I,
+    opciones: &OpcionesLectura,
+) -> (Vec<Punto>, Vec<Propiedades>) {
+    con_datos(file_name.as_ref(), |datos, formato| {
+        formatos::lee_con_propiedades(datos, formato, opciones)
+    })
+    .unwrap_or_else(|e| panic!("{e}"))
+}
+
+// Como read_points_with, con el error en vez de un panic
+#[allow(unused)]
+pub fn intenta_read_points<I: AsRef<Path>>(
+    file_name: I,
+    opciones: &OpcionesLectura,
+) -> io::Result<Vec<Punto>> {
+    con_datos(file_name.as_ref(), |datos, formato| {
+        formatos::lee(datos, formato, opciones)
+    })
+}
+
+// Llama a `lee` con los datos ya descomprimidos de `file_name` ("-" es la
+// entrada estandar) y su formato. Un fichero vacio no tiene puntos.
+fn con_datos<R: Default>(
+    file_name: &Path,
+    lee: impl FnOnce(&[u8], Formato) -> io::Result<R>,
+) -> io::Result<R> {
+    if file_name == Path::new("-") {
+        let datos = lee_stream(io::stdin().lock())?;
+        if datos.is_empty() {
+            return Ok(R::default());
+        }
+        return lee(&datos, Formato::detecta(&datos));
+    }
+
+    let formato = Formato::por_extension(file_name);
+    let Some(mmap) = mapea(file_name)? else {
+        return Ok(R::default());
+    };
+    let datos = descomprime_mapeado(&mmap)?;
+
+    // Con .tsp y .pts manda el contenido, por si la extension no corresponde
+    let formato = match formato {
+        Some(Formato::Tsplib | Formato::Pts) | None => Formato::detecta(&datos),
+        Some(formato) => formato,
+    };
+    lee(&datos, formato)
+}
+
+// Como read_points_with con coordenadas T. TSPLIB se parsea directamente
+// como T; el resto de formatos, TSPLIB con valores que no son un T finito y
+// punto fijo (escala distinta de 1) pasan por f64, se multiplican por
+// `escala` y se convierten con formatos::lee_como, que no satura.
+#[allow(unused)]
+pub fn read_points_como<T: Escalar, I: AsRef<Path>>(
+    file_name: I,
+    opciones: &OpcionesLectura,
+    escala: f64,
+) -> Vec<Punto<T>> {
+    con_datos(file_name.as_ref(), |datos, formato| {
+        if escala == 1.0 && formato == Formato::Tsplib {
+            if let Ok(puntos) = intenta_tsp_como::<T>(datos) {
+                if puntos.iter().all(Punto::es_finito) {
+                    return Ok(puntos);
+                }
+            }
+        }
+        formatos::lee_como(datos, formato, opciones, escala)
+    })
+    .unwrap_or_else(|e| panic!("{e}"))
+}
+
+// Como read_points_with, con una etiqueta por punto (formatos::lee_etiquetados)
+#[allow(unused)]
+pub fn read_labelled_points<L: FromStr + Clone, I: AsRef<Path>>(
+    file_name: I,
+    opciones: &OpcionesLectura,
+    defecto: L,
+) -> Vec<PuntoEtiquetado<L>> {
+    con_datos(file_name.as_ref(), |datos, formato| {
+        formatos::lee_etiquetados(datos, formato, opciones, defecto)
+    })
+    .unwrap_or_else(|e| panic!("{e}"))
+}
+
+// None si el fichero esta vacio, que no se puede mapear
+fn mapea<I: AsRef<Path>>(file_name: I) -> io::Result<Option<Mmap>> {
+    let file = File::open(file_name)?;
+    if file.metadata()?.len() == 0 {
+        return Ok(None);
+    }
+    // SAFETY:
+    // Solo lectura, el fichero no se modifica mientras se parsea
+    unsafe { Mmap::map(&file).map(Some) }
+}
+
+// Sin copia si no esta comprimido
+fn descomprime_mapeado(mmap: &Mmap) -> io::Result<Cow<'_, [u8]>> {
+    Ok(match Compresion::detecta(mmap) {
+        Compresion::Ninguna => Cow::Borrowed(&mmap[..]),
+        _ => Cow::Owned(descomprime(mmap)?),
+    })
+}
+
+// Para tuberias y cualquier otro stream: la compresion y el formato se
+// deducen de los primeros bytes. El stream se lee entero antes de parsear.
+#[allow(unused)]
+pub fn read_points_from_reader<R: BufRead>(
+    reader: R,
+    opciones: &OpcionesLectura,
+) -> io::Result<Vec<Punto>> {
+    lee_reader(reader, opciones).map(|(puntos, _)| puntos)
+}
+
+fn lee_reader<R: BufRead>(
+    reader: R,
+    opciones: &OpcionesLectura,
+) -> io::Result<(Vec<Punto>, Vec<Propiedades>)> {
+    let datos = lee_stream(reader)?;
+    if datos.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    formatos::lee_con_propiedades(&datos, Formato::detecta(&datos), opciones)
+}
+
+// Entero y descomprimido
+fn lee_stream<R: BufRead>(mut reader: R) -> io::Result<Vec<u8>> {
+    let compresion = Compresion::detecta(reader.fill_buf()?);
+    let mut datos = Vec::new();
+    compresion.decoder(reader)?.read_to_end(&mut datos)?;
+    Ok(datos)
+}
+
+pub fn parse_tsp(datos: &[u8]) -> Vec<Punto> {
+    parse_tsp_como(datos)
+}
+
+// Con las coordenadas leidas directamente como T (f32, i32...), sin pasar por f64
+pub fn parse_tsp_como<T: Escalar>(datos: &[u8]) -> Vec<Punto<T>> {
+    intenta_tsp_como(datos).unwrap_or_else(|campo| panic!("coordenada no valida: {campo}"))
+}
+
+// Como parse_tsp_como, con el primer campo que no es un T como error
+pub fn intenta_tsp_como<T: Escalar>(datos: &[u8]) -> Result<Vec<Punto<T>>, String> {
+    let cuerpo = cuerpo_tsp(datos);
+    let hilos = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(cuerpo.len() / MIN_BYTES_POR_HILO)
+        .max(1);
+
+    if hilos == 1 {
+        return intenta_hasta(cuerpo, usize::MAX).map(|(puntos, _)| puntos);
+    }
+
+    let bloques = divide_en_lineas(cuerpo, hilos);
+    let partes: Vec<Result<Vec<Punto<T>>, String>> = std::thread::scope(|s| {
+        let handles: Vec<_> = bloques
+            .iter()
+            .map(|bloque| s.spawn(|| intenta_hasta(bloque, usize::MAX).map(|(puntos, _)| puntos)))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut puntos = Vec::new();
+    for parte in partes {
+        puntos.extend_from_slice(&parte?);
+    }
+    Ok(puntos)
+}
+
+// Lo que va detras de la linea NODE_COORD_SECTION
+fn cuerpo_tsp(datos: &[u8]) -> &[u8] {
+    let mut resto = datos;
+    while !resto.is_empty() {
+        let fin = resto
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(resto.len(), |i| i + 1);
+        let (linea, siguiente) = resto.split_at(fin);
+        resto = siguiente;
+
+        if linea.trim_ascii() == SECCION {
+            return resto;
+        }
+    }
+    panic!("fichero sin NODE_COORD_SECTION");
+}
+
+// El campo de detras de x e y de cada punto que devuelve parse_tsp, si lo
+// hay. Recorre el cuerpo igual que ubica_tsp.
+pub fn etiquetas_tsp(datos: &[u8]) -> Vec<Option<String>> {
+    let cuerpo = cuerpo_tsp(datos);
+    let mut etiquetas = Vec::new();
+    let mut pos = 0;
+
+    while pos < cuerpo.len() {
+        let (id, fin) = siguiente_campo(cuerpo, pos);
+        let (x, fin) = siguiente_campo(cuerpo, fin);
+        let (y, fin) = siguiente_campo(cuerpo, fin);
+        let (etiqueta, fin) = siguiente_campo(cuerpo, fin);
+
+        if !id.is_empty() && !x.is_empty() && !y.is_empty() {
+            etiquetas.push(
+                (!etiqueta.is_empty()).then(|| String::from_utf8_lossy(etiqueta).into_owned()),
+            );
+        }
+
+        pos = cuerpo[fin..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(cuerpo.len(), |i| fin + i + 1);
+    }
+    etiquetas
+}
+
+// Linea (desde 1) e id de nodo del punto `indice` que devuelve parse_tsp,
+// para los errores. Recorre el cuerpo igual que parse_hasta.
+pub fn ubica_tsp(datos: &[u8], indice: usize) -> (usize, String) {
+    let cuerpo = cuerpo_tsp(datos);
+    let inicio = datos.len() - cuerpo.len();
+    let mut restantes = indice;
+    let mut pos = 0;
+
+    while pos < cuerpo.len() {
+        let (id, fin) = siguiente_campo(cuerpo, pos);
+        let (x, fin) = siguiente_campo(cuerpo, fin);
+        let (y, fin) = siguiente_campo(cuerpo, fin);
+
+        if !id.is_empty() && !x.is_empty() && !y.is_empty() {
+            if restantes == 0 {
+                let linea = datos[..inicio + pos]
+                    .iter()
+                    .filter(|b| **b == b'\n')
+                    .count()
+                    + 1;
+                return (linea, String::from_utf8_lossy(id).into_owned());
+            }
+            restantes -= 1;
+        }
+
+        pos = cuerpo[fin..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(cuerpo.len(), |i| fin + i + 1);
+    }
+    panic!("el punto {indice} no esta en el fichero");
+}
+
+fn divide_en_lineas(cuerpo: &[u8], partes: usize) -> Vec<&[u8]> {
+    let mut bloques = Vec::with_capacity(partes);
+    let mut start = 0;
+
+    for i in 1..=partes {
+        let mut end = cuerpo.len() * i / partes;
+        if end < start {
+            end = start;
+        }
+        end = cuerpo[end..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(cuerpo.len(), |p| end + p + 1);
+
+        bloques.push(&cuerpo[start..end]);
+        start = end;
+    }
+
+    bloques
+}
+
+// El cuerpo de un TSPLIB en trozos de como mucho `max_puntos` puntos, en el
+// orden del fichero. Para lo que no cabe en memoria de una vez.
+#[allow(unused)]
+pub fn trozos_tsp(datos: &[u8], max_puntos: usize) -> impl Iterator<Item = Vec<Punto>> + '_ {
+    let mut resto = cuerpo_tsp(datos);
+    std::iter::from_fn(move || {
+        let (puntos, fin) = parse_hasta(resto, max_puntos.max(1));
+        resto = &resto[fin..];
+        (!puntos.is_empty()).then_some(puntos)
+    })
+}
+
+// Como mucho `max_puntos`, y hasta donde ha llegado
+fn parse_hasta<T: Escalar>(bloque: &[u8], max_puntos: usize) -> (Vec<Punto<T>>, usize) {
+    intenta_hasta(bloque, max_puntos)
+        .unwrap_or_else(|campo| panic!("coordenada no valida: {campo}"))
+}
+
+fn intenta_hasta<T: Escalar>(
+    bloque: &[u8],
+    max_puntos: usize,
+) -> Result<(Vec<Punto<T>>, usize), String> {
+    // ~ "1 x y\n" con coordenadas de 15-20 cifras, por lo alto
+    let mut puntos = Vec::with_capacity((bloque.len() / 32).min(max_puntos));
+    let mut pos = 0;
+
+    while pos < bloque.len() && puntos.len() < max_puntos {
+        let (id, fin) = siguiente_campo(bloque, pos);
+        let (x, fin) = siguiente_campo(bloque, fin);
+        let (y, fin) = siguiente_campo(bloque, fin);
+
+        // id x y; EOF y lineas vacias no tienen tres campos
+        if !id.is_empty() && !x.is_empty() && !y.is_empty() {
+            puntos.push(Punto {
+                x: parse_coordenada(x)?,
+                y: parse_coordenada(y)?,
+            });
+        }
+
+        // resto de la linea
+        pos = bloque[fin..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(bloque.len(), |i| fin + i + 1);
+    }
+
+    Ok((puntos, pos))
+}
+
+// Campo que empieza en `pos` saltando espacios, sin pasar del fin de linea
+#[inline(always)]
+fn siguiente_campo(linea: &[u8], mut pos: usize) -> (&[u8], usize) {
+    while pos < linea.len() && matches!(linea[pos], b' ' | b'\t' | b'\r') {
+        pos += 1;
+    }
+
+    let start = pos;
+    while pos < linea.len() && !matches!(linea[pos], b' ' | b'\t' | b'\r' | b'\n') {
+        pos += 1;
+    }
+
+    (&linea[start..pos], pos)
+}
+
+#[inline(always)]
+fn parse_coordenada<T: Escalar>(campo: &[u8]) -> Result<T, String> {
+    T::parse(campo).ok_or_else(|| String::from_utf8_lossy(campo).into_owned())
+}
@@ -1,13 +1,34 @@
 #![warn(clippy::all)]
-#![feature(portable_simd)]
-use rand::Rng;
+#![cfg_attr(feature = "simd-nightly", feature(portable_simd))]
+use rand::{Rng, SeedableRng};
+mod compresion;
 mod coord;
+mod dyv_aprox;
+mod dyv_clases;
+#[cfg(feature = "simd-nightly")]
+mod dyv_alt;
 mod dyv_mt;
+mod dyv_pesos;
 mod dyv_st;
 mod dyv_simd;
+mod kdtree;
+mod lector;
+mod pts;
 mod punto;
+mod query;
+mod region;
 mod test;
 mod dyv_it;
+mod externo;
+mod formatos;
+mod generador;
+mod certificado;
+mod escalar;
+mod soa;
+mod kernel;
+mod orden;
+mod perfil;
+mod stats;
 
 #[allow(unused_imports)]
 use dyv_mt::DyVMT;
@@ -15,114 +36,242 @@ use dyv_st::DyV;
 use dyv_simd::DyVSIMD;
 use punto::*;
 
-static N_POINTS: usize = 2_000_000;
 static MEDIA: u128 = 30;
 const POINT_FILES: &str = "point_files/";
 
 use std::{
-    fs::File,
-    io::{BufRead, BufWriter, Write},
+    borrow::Borrow,
+    io::{BufRead, Write},
     path::{Path, PathBuf},
+    str::FromStr,
     time::Instant,
 };
 
+use crate::compresion::Compresion;
+use crate::dyv_aprox::DyVAprox;
 use crate::dyv_it::DyVIT;
+use crate::escalar::Escalar;
+use crate::formatos::{Formato, NoFinitos, OpcionesLectura};
+use crate::kernel::Kernel;
+use crate::orden::Ordenados;
 #[allow(unused)]
-fn genera_random(num_puntos: usize, upper_bound: f64, lower_bound: f64) -> Vec<Punto> {
-    let mut puntos = Vec::with_capacity(num_puntos);
-    for _ in 0..num_puntos {
-        let mut rng = rand::thread_rng();
-        let x: f64 = rng.gen_range(lower_bound..upper_bound);
-        let y: f64 = rng.gen_range(lower_bound..upper_bound);
-        puntos.push(Punto { x, y })
-    }
-    puntos
+fn genera_random(num_puntos: usize, upper_bound: f64, lower_bound: f64, seed: u64) -> Vec<Punto> {
+    generador::genera(
+        generador::Distribucion::caja(lower_bound, upper_bound),
+        num_puntos,
+        seed,
+    )
 }
 
 #[allow(unused)]
 fn genera_random_with_dost<I: rand::distributions::Distribution<f64>>(
     dist: I,
     num_puntos: usize,
-    upper_bound: f64,
-    lower_bound: f64,
+    seed: u64,
 ) -> Vec<Punto> {
-    let mut puntos = Vec::with_capacity(num_puntos);
-    for _ in 0..num_puntos {
-        let mut rng = rand::thread_rng();
-        let x: f64 = dist.sample(&mut rng);
-        let y: f64 = dist.sample(&mut rng);
-        puntos.push(Punto { x, y })
-    }
-    puntos
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    (0..num_puntos)
+        .map(|_| Punto {
+            x: dist.sample(&mut rng),
+            y: dist.sample(&mut rng),
+        })
+        .collect()
 }
 
 #[allow(unused)]
 fn write_points(puntos: &[Punto]) {
-    let mut file = BufWriter::new(File::create("puntos.tsp").unwrap());
-    file.write_all("NODE_COORD_SECTION\n".as_bytes()).unwrap();
-
-    for punto in puntos {
-        file.write_all(format!("1 {} {}\n", punto.x, punto.y).as_bytes())
-            .unwrap();
-    }
+    write_points_with_name("puntos.tsp", puntos)
 }
 
 fn read_points_from_file<I: AsRef<Path>>(file_name: I) -> Vec<Punto> {
-    let mut points = Vec::with_capacity(N_POINTS);
-    let mut buffer = String::new();
-    let mut reader = std::io::BufReader::new(File::open(file_name).unwrap());
-
-    while buffer.trim() != "NODE_COORD_SECTION" {
-        buffer.clear();
-        reader.read_line(&mut buffer).unwrap();
-    }
+    lector::read_points_fast(file_name)
+}
 
-    while reader.read_line(&mut buffer).unwrap() != 0 {
-        let values: Vec<&str> = buffer.trim().split(' ').collect();
-        points.push(Punto {
-            x: values[1].parse().unwrap(),
-            y: values[2].parse().unwrap(),
-        });
-        buffer.clear()
-    }
+// Como read_points_from_file pero con una columna opcional de etiqueta
+// detras de las coordenadas, en cualquier formato que lea lector (tambien
+// "-" y comprimidos). Los puntos sin etiqueta se quedan con `default`.
+// Los no finitos siguen `no_finitos` y se llevan su etiqueta con ellos.
+#[allow(unused)]
+fn read_labelled_points_from_file<I: AsRef<Path>, L: FromStr + Clone>(
+    file_name: I,
+    default: L,
+    no_finitos: NoFinitos,
+) -> Vec<PuntoEtiquetado<L>> {
+    let opciones = OpcionesLectura {
+        no_finitos,
+        ..Default::default()
+    };
+    lector::read_labelled_points(file_name, &opciones, default)
+}
 
-    points
+// Columna de peso opcional; las lineas sin peso usan `default_weight`
+#[allow(unused)]
+fn read_weighted_points_from_file<I: AsRef<Path>>(
+    file_name: I,
+    default_weight: f64,
+    no_finitos: NoFinitos,
+) -> Vec<PuntoPesado> {
+    read_labelled_points_from_file(file_name, default_weight, no_finitos)
 }
 
+#[allow(unused)]
+fn write_points_with_name<I: AsRef<Path>, P: Borrow<Punto>>(
+    name: I,
+    puntos: impl IntoIterator<Item = P>,
+) {
+    write_points_compressed(name, puntos, Compresion::Ninguna)
+}
 
 #[allow(unused)]
-fn write_points_with_name<I: AsRef<Path>>(name: I, puntos: &[Punto]) {
-    let mut file = BufWriter::new(File::create(name).unwrap());
-    file.write_all("NODE_COORD_SECTION\n".as_bytes()).unwrap();
+fn write_points_compressed<I: AsRef<Path>, P: Borrow<Punto>>(
+    name: I,
+    puntos: impl IntoIterator<Item = P>,
+    compresion: Compresion,
+) {
+    let mut file = compresion::crea_fichero(name, compresion).unwrap();
+    write_points_to(&mut file, puntos).unwrap();
+    file.finish().unwrap();
+}
+
+// Acepta tanto &[Punto] como un iterador (p.ej. un Generador) sin pasar por un Vec
+fn write_points_to<W: Write, P: Borrow<Punto>>(
+    mut writer: W,
+    puntos: impl IntoIterator<Item = P>,
+) -> std::io::Result<()> {
+    writer.write_all("NODE_COORD_SECTION\n".as_bytes())?;
 
     for punto in puntos {
-        file.write_all(format!("1 {} {}\n", punto.x, punto.y).as_bytes())
-            .unwrap();
+        let punto = punto.borrow();
+        writer.write_all(format!("1 {} {}\n", punto.x, punto.y).as_bytes())?;
     }
+    writer.flush()
 }
 
-fn bench() {
+// puntitos bench [--kernel sse2|avx2|avx512] [--stats] [--stats-json <fichero>]
+// [--non-finite <politica>]
+// Sin --kernel DyVSIMD usa el mejor que tenga la CPU. --stats saca los
+// contadores de poda de la ultima ejecucion (necesita la feature `stats`).
+fn bench(args: &[String]) {
+    let kernel = flag(args, "--kernel").map_or_else(Kernel::detecta, |k| {
+        k.parse().unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(2);
+        })
+    });
+    if !kernel.disponible() {
+        eprintln!("el kernel {kernel} no esta disponible en esta CPU");
+        std::process::exit(2);
+    }
+
+    let muestra_stats = args.iter().any(|a| a == "--stats");
+    let stats_json = flag(args, "--stats-json");
+    if (muestra_stats || stats_json.is_some()) && !stats::ACTIVAS {
+        eprintln!("--stats necesita compilar con --features stats");
+        std::process::exit(2);
+    }
+
     let file_path = PathBuf::from(POINT_FILES).join("puntos_big_2m.tsp");
-    let mut puntos = read_points_from_file(&file_path);
-    puntos.sort();
-    println!("Testing {} GO!", file_path.display());
+
+    // Si hay un .pts ordenado al lado se mapea en vez de parsear el TSPLIB;
+    // con el checksum mal se parsea el TSPLIB
+    let pts_path = file_path.with_extension("pts");
+    let pts_file = pts::PtsFile::open(&pts_path)
+        .ok()
+        .filter(|f| f.cabecera().ordenado())
+        .filter(|f| {
+            let valido = f.verify();
+            if !valido {
+                eprintln!("{}: checksum no valido, se lee el TSPLIB", pts_path.display());
+            }
+            valido
+        });
+    let no_finitos = opciones_lectura(args).no_finitos;
+    let (leidos, mapeados);
+    let ordenados = match &pts_file {
+        Some(file) => {
+            mapeados = file.puntos_finitos(no_finitos).unwrap();
+            Ordenados::new(&mapeados)
+        }
+        None => {
+            leidos = formatos::filtra_no_finitos(read_points_from_file(&file_path), no_finitos)
+                .unwrap_or_else(|(indice, p)| {
+                    panic!("punto {indice}: coordenada no finita ({}, {})", p.x, p.y)
+                });
+            Ordenados::new(&leidos)
+        }
+    };
+    let puntos = ordenados.puntos();
+    println!("Testing {} GO! (kernel {kernel})", file_path.display());
     let mut media;
     for points in 1..=2 {
         media = 0;
         for _ in 0..MEDIA {
-            let mut dyv = DyVSIMD::new(&puntos);
+            let mut dyv = DyVSIMD::new(puntos);
+            dyv.kernel = kernel;
+            stats::reinicia();
             let start = Instant::now();
             let res = dyv.start();
             let end = Instant::now();
 
             let actual = end.duration_since(start).as_millis();
-            println!("\t{} ms {:?} {}", actual, dyv.get_points(), res);
+            println!("\t{} ms {:?} {}", actual, ordenados.originales(dyv.get_points()), res);
 
             media += actual;
             //println!("{res}, {:?}", points);
         }
         println!("Media: {} ms with {}", media / MEDIA, points);
     }
+
+    let estadisticas = stats::lee();
+    if muestra_stats {
+        println!("{estadisticas}");
+    }
+    if let Some(salida) = stats_json {
+        std::fs::write(salida, estadisticas.a_json().to_string()).unwrap();
+    }
+}
+
+// puntitos adversarial [n]
+// Tiempos de DyV con las entradas de generador::adversarios a n y 4n puntos. Con
+// n log n el tiempo crece ~4.6x; si crece mas de 8x la franja se esta comiendo
+// el coste (cuadratico seria ~16x).
+fn bench_adversarial(args: &[String]) {
+    let n: usize = args.first().map_or(50_000, |n| n.parse().expect("n no valido"));
+
+    type Resuelve = fn(&[Punto]) -> f64;
+    #[allow(unused_mut)]
+    let mut solvers: Vec<(&str, Resuelve)> = vec![
+        ("DyV", |p| DyV::new(p).start()),
+        ("DyVMT", |p| DyVMT::new(p).start()),
+        ("DyVIT", |p| DyVIT::new(p).start()),
+        ("DyVSIMD", |p| DyVSIMD::new(p).start()),
+    ];
+    #[cfg(feature = "simd-nightly")]
+    solvers.push(("DyVAlt", |p| dyv_alt::DyVAlt::new(p).start()));
+
+    let casos = generador::adversarios(n, 39)
+        .into_iter()
+        .zip(generador::adversarios(4 * n, 39))
+        .collect::<Vec<_>>();
+
+    for (solver, resuelve) in solvers {
+        let mide = |puntos: &[Punto]| {
+            let start = Instant::now();
+            resuelve(puntos);
+            Instant::now().duration_since(start).as_secs_f64() * 1000.0
+        };
+
+        println!("{solver}:");
+        for ((caso, pequeño), (_, grande)) in &casos {
+            let (t1, t4) = (mide(pequeño), mide(grande));
+            let crecimiento = t4 / t1.max(1e-3);
+            println!(
+                "{caso:>12}: {t1:>9.1} ms con {n}, {t4:>9.1} ms con {} ({crecimiento:.1}x){}",
+                4 * n,
+                if crecimiento > 8.0 { "  <- crece mas que n log n" } else { "" }
+            );
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -141,29 +290,364 @@ fn prueba() {
 
 #[allow(dead_code)]
 fn genera_puntos_file() {
-    let mut rng = rand::thread_rng();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
     for i in 0..25 {
         let n_points = rng.gen::<usize>() % 50_000 + 100_000;
-        let dist = rand::distributions::Uniform::new(10_000_000.1, 10_000_000.1);
-        let mut puntos = genera_random_with_dost(dist, n_points, 10_000_000.1, -10_000_000.0);
+        let mut puntos = genera_random(n_points, 10_000_000.1, -10_000_000.0, i);
         puntos.sort();
         write_points_with_name(format!("point_files/puntos_rand_small_{}.tsp", i), &puntos);
     }
 
-    let dist = rand::distributions::Uniform::new(10_000_000.1, 10_000_000.1);
-    let mut puntos = genera_random_with_dost(dist, 50_000, 10_000_000.1, -10_000_000.0);
-    puntos.sort();
-
     for i in 25..50 {
         let n_points = rng.gen::<usize>() % 1_000 + 500;
-        let dist = rand::distributions::Uniform::new(10_000_000.1, 10_000_000.1);
-        let mut puntos = genera_random_with_dost(dist, n_points, 10_000_000.1, -10_000_000.0);
+        let mut puntos = genera_random(n_points, 10_000_000.1, -10_000_000.0, i);
         puntos.sort();
         write_points_with_name(format!("point_files/puntos_rand_small_{}.tsp", i), &puntos);
     }
 }
 
+// puntitos aprox <fichero> <eps>
+fn aprox(args: &[String]) {
+    let [file, eps] = args else {
+        eprintln!("uso: puntitos aprox <fichero> <eps>");
+        std::process::exit(2);
+    };
+    let eps: f64 = eps.parse().expect("eps no valido");
+
+    let mut puntos = read_points_from_file(file);
+    puntos.sort();
+
+    let mut dyv = DyVAprox::new(&puntos, eps);
+    let start = Instant::now();
+    let res = dyv.start();
+    let end = Instant::now();
+
+    println!(
+        "{} ms {:?} {} (optimo >= {})",
+        end.duration_since(start).as_millis(),
+        dyv.get_points(),
+        res,
+        dyv.lower_bound()
+    );
+}
+
+// puntitos bench-aprox [n] [eps]
+// DyV exacto contra DyVAprox con n puntos al azar
+fn bench_aprox(args: &[String]) {
+    let n: usize = args.first().map_or(1_000_000, |n| n.parse().expect("n no valido"));
+    let eps: f64 = args.get(1).map_or(0.01, |eps| eps.parse().expect("eps no valido"));
+
+    let mut puntos = genera_random(n, 1000.0, -1000.0, 31);
+    puntos.sort();
+
+    let start = Instant::now();
+    let exacto = DyV::new(&puntos).start();
+    let t_exacto = start.elapsed().as_secs_f64() * 1000.0;
+
+    let mut dyv = DyVAprox::new(&puntos, eps);
+    let start = Instant::now();
+    let res = dyv.start();
+    let t_aprox = start.elapsed().as_secs_f64() * 1000.0;
+
+    println!("exacto: {t_exacto:>9.1} ms {exacto}");
+    println!(
+        " aprox: {t_aprox:>9.1} ms {res} (optimo >= {}, {} centrales, {:.1}x)",
+        dyv.lower_bound(),
+        dyv.centrales(),
+        t_exacto / t_aprox.max(1e-3)
+    );
+}
+
+// puntitos bench-parse [n]: el lector TSPLIB contra el de antes (un
+// Vec<&str> por linea y str::parse en un hilo) sobre el mismo texto en memoria
+fn bench_parse(args: &[String]) {
+    let n: usize = args.first().map_or(2_000_000, |n| n.parse().expect("n no valido"));
+    let mut datos = Vec::new();
+    write_points_to(&mut datos, genera_random(n, 1000.0, -1000.0, 32)).unwrap();
+
+    let start = Instant::now();
+    let mut anteriores: Vec<Punto> = Vec::with_capacity(n);
+    let mut lineas = datos.as_slice().lines().map(Result::unwrap);
+    lineas.find(|linea| linea.trim() == "NODE_COORD_SECTION");
+    for linea in lineas {
+        let values: Vec<&str> = linea.trim().split(' ').collect();
+        if values.len() >= 3 {
+            anteriores.push(Punto {
+                x: values[1].parse().unwrap(),
+                y: values[2].parse().unwrap(),
+            });
+        }
+    }
+    let t_anterior = start.elapsed().as_secs_f64() * 1000.0;
+
+    let start = Instant::now();
+    let puntos = lector::parse_tsp(&datos);
+    let t_lector = start.elapsed().as_secs_f64() * 1000.0;
+
+    let iguales = anteriores.len() == puntos.len()
+        && anteriores
+            .iter()
+            .zip(&puntos)
+            .all(|(a, b)| a.x.to_bits() == b.x.to_bits() && a.y.to_bits() == b.y.to_bits());
+    println!("{} MiB, {n} puntos", datos.len() >> 20);
+    println!("anterior: {t_anterior:>9.1} ms");
+    println!(
+        "  lector: {t_lector:>9.1} ms ({:.1}x, iguales: {iguales})",
+        t_anterior / t_lector.max(1e-3)
+    );
+}
+
+// Valor de `--nombre valor` en la linea de comandos
+fn flag<'a>(args: &'a [String], nombre: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == nombre)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+// puntitos externo <fichero> --max-mem <MiB> [--tmp <dir>] [--non-finite <politica>]
+fn externo(args: &[String]) {
+    let (Some(file), Some(max_mem)) = (args.first(), flag(args, "--max-mem")) else {
+        eprintln!("uso: puntitos externo <fichero> --max-mem <MiB> [--tmp <dir>]");
+        std::process::exit(2);
+    };
+    let max_mem: usize = max_mem.parse().expect("--max-mem no valido");
+    let max_puntos = (max_mem << 20) / std::mem::size_of::<Punto>();
+    let tmp_dir = flag(args, "--tmp").map_or_else(std::env::temp_dir, PathBuf::from);
+    let ordenado = tmp_dir.join(format!(".puntitos_ordenado_{}.bin", std::process::id()));
+    // Se borra al salir, tambien si ordena_externo o el solver fallan
+    let _temporales = externo::Temporales(vec![ordenado.clone()]);
+
+    let start = Instant::now();
+    let no_finitos = opciones_lectura(args).no_finitos;
+    let total = externo::ordena_externo(file, &ordenado, max_puntos, no_finitos).unwrap();
+    let ordenacion = Instant::now();
+
+    let mut dyv = externo::DyVExterno::new(&ordenado, max_puntos);
+    let res = dyv.start().unwrap();
+    let end = Instant::now();
+
+    println!(
+        "{total} puntos, ordenacion {} ms, solver {} ms {:?} {}",
+        ordenacion.duration_since(start).as_millis(),
+        end.duration_since(ordenacion).as_millis(),
+        dyv.get_points(),
+        res
+    );
+}
+
+// Seleccion de columnas para entradas CSV: --x <col> --y <col> [--sep <c>]
+// y que hacer con NaN/inf: --non-finite reject|drop|clamp
+fn opciones_lectura(args: &[String]) -> OpcionesLectura {
+    let mut opciones = OpcionesLectura::default();
+    if let Some(x) = flag(args, "--x") {
+        opciones.csv.x = x.parse().unwrap();
+    }
+    if let Some(y) = flag(args, "--y") {
+        opciones.csv.y = y.parse().unwrap();
+    }
+    if let Some(etiqueta) = flag(args, "--label") {
+        opciones.csv.etiqueta = etiqueta.parse().unwrap();
+    }
+    if let Some(sep) = flag(args, "--sep") {
+        opciones.csv.separador = sep.bytes().next().expect("--sep vacio");
+    }
+    if let Some(politica) = flag(args, "--non-finite") {
+        opciones.no_finitos = politica.parse().unwrap();
+    }
+    opciones
+}
+
+// puntitos convert <entrada> <salida> [--sort] [--compress gzip|zstd|xz]
+// El formato de salida sale de la extension (.tsp, .pts, .csv, .geojson, .wkt, .npy)
+fn convert(args: &[String]) {
+    let [entrada, salida, ..] = args else {
+        eprintln!("uso: puntitos convert <entrada> <salida> [--sort] [--compress gzip|zstd|xz]");
+        std::process::exit(2);
+    };
+
+    let ordena = args.iter().any(|a| a == "--sort");
+    let compresion: Compresion = flag(args, "--compress")
+        .map(|c| c.parse().unwrap())
+        .unwrap_or_default();
+    let formato = Formato::por_extension(salida).unwrap_or(Formato::Tsplib);
+
+    // Las propiedades de un GeoJSON van con sus puntos al ordenar
+    let (mut puntos, mut propiedades) =
+        lector::read_points_con_propiedades(entrada, &opciones_lectura(args));
+    if ordena && propiedades.is_empty() {
+        puntos.sort();
+    } else if ordena {
+        let mut juntos: Vec<_> = puntos.into_iter().zip(propiedades).collect();
+        juntos.sort_by_key(|a| a.0);
+        (puntos, propiedades) = juntos.into_iter().unzip();
+    }
+    formatos::escribe_con_propiedades(salida, &puntos, &propiedades, formato, compresion).unwrap();
+    println!("{} puntos escritos en {salida}", puntos.len());
+}
+
+// puntitos solve <fichero|-> [--geojson <salida>] [--certify [--tol t]]
+//                [--coord f64|f32|i32|i64 [--escala e]]
+// Con "-" se leen los puntos de la entrada estandar: gen | puntitos solve -
+// Los indices son los de la entrada, este ordenada o no
+// --certify recalcula en doble-doble los caminos a menos de t (relativo) del
+// resultado y dice si el optimo es unico
+// --coord resuelve con ese tipo de coordenada; con --escala los puntos se
+// multiplican por e antes de convertir (punto fijo)
+fn solve(args: &[String]) {
+    let Some(file) = args.first() else {
+        eprintln!("uso: puntitos solve <fichero|-> [--geojson <salida>] [--certify [--tol t]] [--coord f64|f32|i32|i64 [--escala e]]");
+        std::process::exit(2);
+    };
+
+    match flag(args, "--coord").unwrap_or("f64") {
+        "f64" => {}
+        "f32" => return resuelve_como::<f32>(file, args),
+        "i32" => return resuelve_como::<i32>(file, args),
+        "i64" => return resuelve_como::<i64>(file, args),
+        otro => {
+            eprintln!("tipo de coordenada desconocido: {otro}");
+            std::process::exit(2);
+        }
+    }
+
+    let puntos = lector::read_points_with(file, &opciones_lectura(args));
+    let ordenados = Ordenados::new(&puntos);
+
+    let mut dyv = DyV::new(ordenados.puntos());
+    let start = Instant::now();
+    let res = dyv.start();
+    let end = Instant::now();
+    let indices = ordenados.originales(dyv.get_points());
+    println!(
+        "{} ms {:?} {}",
+        end.duration_since(start).as_millis(),
+        indices,
+        res
+    );
+
+    if let Some(salida) = flag(args, "--geojson") {
+        let triple = formatos::triple_geojson(&puntos, indices, res);
+        std::fs::write(salida, triple.to_string()).unwrap();
+    }
+
+    if args.iter().any(|a| a == "--certify") {
+        let tolerancia = flag(args, "--tol").map_or(certificado::TOLERANCIA, |t| t.parse().unwrap());
+        match certificado::certifica(&puntos, res, tolerancia) {
+            Some(cert) => println!(
+                "certificado: {:?} {} ({} candidatos, margen {:?}) {}",
+                cert.indices,
+                cert.distancia,
+                cert.candidatos,
+                cert.margen,
+                if cert.unico { "unico" } else { "empate" }
+            ),
+            None => println!("certificado: ningun camino mide {res}"),
+        }
+    }
+}
+
+// El resultado se da en las unidades de la entrada, dividido por la escala
+fn resuelve_como<T: Escalar>(file: &str, args: &[String]) {
+    let escala = flag(args, "--escala").map_or(1.0, |e| e.parse().unwrap());
+    let puntos = lector::read_points_como::<T, _>(file, &opciones_lectura(args), escala);
+    let ordenados = Ordenados::new(&puntos);
+
+    let mut dyv = DyV::new(ordenados.puntos());
+    let start = Instant::now();
+    let res = dyv.start();
+    let end = Instant::now();
+    println!(
+        "{} ms {:?} {} ({} carriles)",
+        end.duration_since(start).as_millis(),
+        ordenados.originales(dyv.get_points()),
+        res / escala,
+        T::CARRILES
+    );
+}
+
+// puntitos autotune <fichero> [--muestra n] [--valores a,b,...] [--repeticiones r] [-o perfil]
+// Prueba cada fixed_points con n puntos al azar del fichero y guarda el mas
+// rapido de cada solver en el perfil de esta maquina, que es lo que usan sus
+// new() a partir de entonces (las entradas son para f64)
+fn autotune(args: &[String]) {
+    let Some(file) = args.first() else {
+        eprintln!("uso: puntitos autotune <fichero> [--muestra n] [--valores a,b,...] [--repeticiones r] [-o perfil]");
+        std::process::exit(2);
+    };
+    let n: usize = flag(args, "--muestra").map_or(200_000, |n| n.parse().expect("--muestra no valido"));
+    let valores: Vec<usize> = flag(args, "--valores").map_or_else(
+        || (16..=256).step_by(16).collect(),
+        |v| v.split(',').map(|n| n.parse().expect("--valores no valido")).collect(),
+    );
+    if valores.is_empty() || valores.iter().any(|&v| v < 3) {
+        eprintln!("--valores tiene que tener algun valor y todos >= 3");
+        std::process::exit(2);
+    }
+    let repeticiones: usize = flag(args, "--repeticiones").map_or(3, |r| r.parse().expect("--repeticiones no valido"));
+    let salida = flag(args, "-o").map_or_else(perfil::ruta, PathBuf::from);
+
+    let leidos = lector::read_points_with(file, &opciones_lectura(args));
+    let puntos = perfil::muestra(Ordenados::new(&leidos).puntos(), n, 0);
+    println!("{} de {} puntos, {} valores", puntos.len(), leidos.len(), valores.len());
+
+    let perfil = perfil::afina(&puntos, &valores, repeticiones, |variante, fixed, ms| {
+        println!("{variante:>9} {fixed:>5}: {ms:>9.2} ms")
+    });
+    for (variante, fixed) in &perfil.fixed_points {
+        println!("{variante}: {fixed}");
+    }
+    perfil.guarda(&salida).unwrap();
+    println!("perfil de {} en {}", perfil.host, salida.display());
+}
+
+// puntitos gen <distribucion> <n> [--seed S] [--lado L] [-o salida] [--compress c]
+// Sin -o se escribe TSPLIB por la salida estandar: puntitos gen caja 1000 | puntitos solve -
+fn genera(args: &[String]) {
+    let [nombre, n, ..] = args else {
+        eprintln!("uso: puntitos gen <caja|disco|clusters|poisson|reticula|linea|circulo|duplicados|vertical|cortes|columnas|exponencial> <n> [--seed S] [--lado L] [-o salida] [--compress c]");
+        std::process::exit(2);
+    };
+    let n: usize = n.parse().expect("n no valido");
+    let seed: u64 = flag(args, "--seed").map_or(0, |s| s.parse().expect("--seed no valido"));
+    let lado: f64 = flag(args, "--lado").map_or(1000.0, |l| l.parse().expect("--lado no valido"));
+    let Some(distribucion) = generador::Distribucion::por_nombre(nombre, n, lado) else {
+        eprintln!("distribucion desconocida: {nombre}");
+        std::process::exit(2);
+    };
+
+    let puntos = generador::Generador::new(distribucion, seed).take(n);
+    match flag(args, "-o") {
+        Some(salida) => {
+            let compresion: Compresion = flag(args, "--compress")
+                .map(|c| c.parse().unwrap())
+                .unwrap_or_default();
+            write_points_compressed(salida, puntos, compresion)
+        }
+        None => write_points_to(std::io::stdout().lock(), puntos).unwrap(),
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("aprox") => return aprox(&args[1..]),
+        Some("externo") => return externo(&args[1..]),
+        Some("convert") => return convert(&args[1..]),
+        Some("solve") => return solve(&args[1..]),
+        Some("gen") => return genera(&args[1..]),
+        Some("bench") => return bench(&args[1..]),
+        Some("adversarial") => return bench_adversarial(&args[1..]),
+        Some("bench-aprox") => return bench_aprox(&args[1..]),
+        Some("bench-parse") => return bench_parse(&args[1..]),
+        Some("autotune") => return autotune(&args[1..]),
+        Some(cmd) => {
+            eprintln!("comando desconocido: {cmd}");
+            std::process::exit(2);
+        }
+        None => {}
+    }
+
     /*
     for i in 0..10 {
         let puntos = read_points_from_file(&format!("point_files/puntos_rand_small_{}.tsp", i));
@@ -173,6 +657,6 @@ fn main() {
     }
     */
 
-    bench()
+    bench(&[])
     //prueba()
 }
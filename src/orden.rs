@@ -0,0 +1,189 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use crate::escalar::Escalar;
+use crate::punto::Punto;
+
+// Radix LSD de 11 bits sobre Escalar::clave_orden de la x: 6 pasadas
+const BITS: u32 = 11;
+const CUBOS: usize = 1 << BITS;
+const PASADAS: usize = u64::BITS.div_ceil(BITS) as usize;
+const MIN_POR_HILO: usize = 1 << 16;
+
+type Histograma = [usize; CUBOS];
+
+// Los puntos de la entrada ordenados por x, como los esperan los solvers. Si
+// ya venian ordenados no se copian; si no, se guarda de que indice de la
+// entrada viene cada uno para devolver los resultados en el orden original.
+pub struct Ordenados<'a, T: Escalar = f64> {
+    puntos: Cow<'a, [Punto<T>]>,
+    originales: Option<Vec<usize>>,
+}
+
+#[allow(unused)]
+impl<'a, T: Escalar> Ordenados<'a, T> {
+    pub fn new(puntos: &'a [Punto<T>]) -> Self {
+        if esta_ordenado(puntos) {
+            return Self {
+                puntos: Cow::Borrowed(puntos),
+                originales: None,
+            };
+        }
+
+        let hilos = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(puntos.len() / MIN_POR_HILO)
+            .max(1);
+        let originales = permutacion(puntos, hilos);
+        Self {
+            puntos: Cow::Owned(originales.iter().map(|&i| puntos[i]).collect()),
+            originales: Some(originales),
+        }
+    }
+
+    pub fn puntos(&self) -> &[Punto<T>] {
+        &self.puntos
+    }
+
+    pub fn ya_ordenado(&self) -> bool {
+        self.originales.is_none()
+    }
+
+    // Indice en la entrada del punto i de puntos()
+    pub fn original(&self, i: usize) -> usize {
+        self.originales
+            .as_ref()
+            .map_or(i, |originales| originales[i])
+    }
+
+    pub fn originales<const K: usize>(&self, indices: [usize; K]) -> [usize; K] {
+        indices.map(|i| self.original(i))
+    }
+}
+
+// O(n): solo se mira la x, los empates en x dan igual a los solvers
+pub fn esta_ordenado<T: Escalar>(puntos: &[Punto<T>]) -> bool {
+    puntos
+        .windows(2)
+        .all(|par| par[0].x_compare(&par[1]) != Ordering::Greater)
+}
+
+// Indices de la entrada en el orden de las x. Estable: con x iguales se
+// queda el orden de la entrada. Cada hilo cuenta y reparte su trozo, en
+// huecos del destino que solo son suyos.
+pub fn permutacion<T: Escalar>(puntos: &[Punto<T>], hilos: usize) -> Vec<usize> {
+    let mut actual: Vec<(u64, usize)> = puntos
+        .iter()
+        .enumerate()
+        .map(|(i, punto)| (punto.x.clave_orden(), i))
+        .collect();
+    if actual.is_empty() {
+        return Vec::new();
+    }
+    let mut siguiente = vec![(0, 0); actual.len()];
+    let trozo = actual.len().div_ceil(hilos.max(1));
+
+    // Cuantos puntos hay en cada cubo en cada pasada, con una sola lectura.
+    // No cambia al reordenar; lo que hay en cada trozo si.
+    let por_trozo: Vec<Vec<Histograma>> = std::thread::scope(|s| {
+        let handles: Vec<_> = actual
+            .chunks(trozo)
+            .map(|parte| s.spawn(move || histogramas(parte)))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    let mut totales = vec![[0; CUBOS]; PASADAS];
+    for histogramas in &por_trozo {
+        for (total, histograma) in totales.iter_mut().zip(histogramas) {
+            for (t, h) in total.iter_mut().zip(histograma) {
+                *t += h;
+            }
+        }
+    }
+
+    for (pasada, total) in totales.iter().enumerate() {
+        let desplazamiento = pasada as u32 * BITS;
+
+        // Todos en el mismo cubo (f32 e i32 usan la mitad de la clave)
+        if total[cubo(actual[0].0, desplazamiento)] == actual.len() {
+            continue;
+        }
+
+        let histogramas: Vec<Histograma> = if trozo >= actual.len() {
+            vec![*total]
+        } else {
+            std::thread::scope(|s| {
+                let handles: Vec<_> = actual
+                    .chunks(trozo)
+                    .map(|parte| s.spawn(move || histograma(parte, desplazamiento)))
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            })
+        };
+
+        // Cubos en orden y, dentro de cada cubo, trozos en orden
+        let mut inicios = vec![[0; CUBOS]; histogramas.len()];
+        let mut posicion = 0;
+        for c in 0..CUBOS {
+            for (inicio, histograma) in inicios.iter_mut().zip(&histogramas) {
+                inicio[c] = posicion;
+                posicion += histograma[c];
+            }
+        }
+
+        let destino = Destino(siguiente.as_mut_ptr());
+        std::thread::scope(|s| {
+            for (parte, mut inicio) in actual.chunks(trozo).zip(inicios) {
+                let destino = &destino;
+                s.spawn(move || {
+                    for &(clave, indice) in parte {
+                        let c = cubo(clave, desplazamiento);
+                        destino.escribe(inicio[c], (clave, indice));
+                        inicio[c] += 1;
+                    }
+                });
+            }
+        });
+        std::mem::swap(&mut actual, &mut siguiente);
+    }
+
+    actual.into_iter().map(|(_, indice)| indice).collect()
+}
+
+#[inline(always)]
+fn cubo(clave: u64, desplazamiento: u32) -> usize {
+    (clave >> desplazamiento) as usize & (CUBOS - 1)
+}
+
+fn histograma(parte: &[(u64, usize)], desplazamiento: u32) -> Histograma {
+    let mut cuenta = [0; CUBOS];
+    for &(clave, _) in parte {
+        cuenta[cubo(clave, desplazamiento)] += 1;
+    }
+    cuenta
+}
+
+fn histogramas(parte: &[(u64, usize)]) -> Vec<Histograma> {
+    let mut cuentas = vec![[0; CUBOS]; PASADAS];
+    for &(clave, _) in parte {
+        for (pasada, cuenta) in cuentas.iter_mut().enumerate() {
+            cuenta[cubo(clave, pasada as u32 * BITS)] += 1;
+        }
+    }
+    cuentas
+}
+
+// El vector de la siguiente pasada, compartido entre los hilos
+struct Destino(*mut (u64, usize));
+
+unsafe impl Sync for Destino {}
+
+impl Destino {
+    #[inline(always)]
+    fn escribe(&self, posicion: usize, valor: (u64, usize)) {
+        // SAFETY:
+        // Los huecos de cada trozo no se solapan y suman la longitud del
+        // vector, asi que cada posicion se escribe una vez y desde un hilo
+        unsafe { self.0.add(posicion).write(valor) }
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use rand::SeedableRng;
+use serde_json::{json, Value};
+
+use crate::dyv_it::DyVIT;
+use crate::dyv_mt::DyVMT;
+use crate::dyv_simd::DyVSIMD;
+use crate::dyv_st::DyV;
+use crate::escalar::Escalar;
+use crate::punto::*;
+
+// fixed_points de cada variante en esta maquina, el que saca `puntitos
+// autotune`. Las variantes van por el nombre de su modulo (dyv_st, dyv_mt...)
+// y el tipo de coordenada, ver clave().
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Perfil {
+    pub host: String,
+    pub fixed_points: BTreeMap<String, usize>,
+}
+
+// El solver ya construido, a falta de start(): DyVSIMD copia los puntos a
+// SoA al construirse y eso no tiene que ver con fixed_points
+type Preparado<'a> = Box<dyn FnOnce() -> BestPoint + 'a>;
+type Solver = for<'a> fn(&'a [Punto], usize) -> Preparado<'a>;
+
+const FIXED_REFERENCIA: usize = 3;
+
+// Las que se afinan; aprox, clases y pesos se quedan con su constante
+pub fn variantes() -> Vec<(&'static str, Solver)> {
+    #[allow(unused_mut)]
+    let mut variantes: Vec<(&'static str, Solver)> = vec![
+        ("dyv_st", |p, f| {
+            let mut dyv = DyV::new_with_fixed(p, f);
+            Box::new(move || dyv.start())
+        }),
+        ("dyv_it", |p, f| {
+            let mut dyv = DyVIT::new_with_fixed(p, f);
+            Box::new(move || dyv.start())
+        }),
+        ("dyv_simd", |p, f| {
+            let mut dyv = DyVSIMD::new_with_fixed(p, f);
+            Box::new(move || dyv.start())
+        }),
+        ("dyv_mt", |p, f| {
+            let mut dyv = DyVMT::new_with_fixed(p, f);
+            Box::new(move || dyv.start())
+        }),
+    ];
+    #[cfg(feature = "simd-nightly")]
+    variantes.push(("dyv_alt", |p, f| {
+        let mut dyv = crate::dyv_alt::DyVAlt::new_with_fixed(p, f);
+        Box::new(move || dyv.start())
+    }));
+    variantes
+}
+
+static CARGADO: OnceLock<Perfil> = OnceLock::new();
+
+// Lo que usan los new() de los solvers: el perfil se lee una vez por proceso
+// y sin entrada para la variante y T se queda `defecto`. new_with_fixed no
+// lo mira.
+pub fn fixed_points<T: Escalar>(variante: &str, defecto: usize) -> usize {
+    CARGADO
+        .get_or_init(carga)
+        .fixed_points::<T>(variante, defecto)
+}
+
+// El perfil de esta maquina, vacio si no hay
+pub fn carga() -> Perfil {
+    let ruta = ruta();
+    match Perfil::lee(&ruta) {
+        Ok(perfil) => perfil,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Perfil::default(),
+        Err(e) => {
+            eprintln!("perfil {} ignorado: {e}", ruta.display());
+            Perfil::default()
+        }
+    }
+}
+
+// Las entradas van por variante y tipo de coordenada: "dyv_st:f32"
+pub fn clave<T: Escalar>(variante: &str) -> String {
+    format!("{variante}:{}", std::any::type_name::<T>())
+}
+
+// $PUNTITOS_PERFIL o ~/.config/puntitos/<host>.json
+pub fn ruta() -> PathBuf {
+    if let Some(ruta) = std::env::var_os("PUNTITOS_PERFIL") {
+        return ruta.into();
+    }
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .unwrap_or_else(std::env::temp_dir);
+    config.join("puntitos").join(format!("{}.json", host()))
+}
+
+pub fn host() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .map(|host| host.trim().to_string())
+        .ok()
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "local".to_string())
+}
+
+#[allow(unused)]
+impl Perfil {
+    // Sin esa variante para T se queda `defecto`. Una entrada sin tipo, de
+    // antes de separar por tipo, se midio con f64 y solo vale para f64
+    pub fn fixed_points<T: Escalar>(&self, variante: &str, defecto: usize) -> usize {
+        let sin_tipo = || {
+            (std::any::type_name::<T>() == "f64")
+                .then(|| self.fixed_points.get(variante))
+                .flatten()
+        };
+        self.fixed_points
+            .get(&clave::<T>(variante))
+            .or_else(sin_tipo)
+            .copied()
+            .unwrap_or(defecto)
+    }
+
+    pub fn lee<I: AsRef<Path>>(ruta: I) -> io::Result<Self> {
+        let raiz: Value = serde_json::from_slice(&std::fs::read(ruta)?).map_err(io::Error::from)?;
+        let invalido = |mensaje: String| io::Error::new(io::ErrorKind::InvalidData, mensaje);
+
+        let host = raiz["host"]
+            .as_str()
+            .ok_or_else(|| invalido("perfil sin host".to_string()))?;
+        let fixed_points = raiz["fixed_points"]
+            .as_object()
+            .ok_or_else(|| invalido("perfil sin fixed_points".to_string()))?
+            .iter()
+            .map(|(variante, valor)| match valor.as_u64() {
+                Some(n) if n >= 3 => Ok((variante.clone(), n as usize)),
+                _ => Err(invalido(format!(
+                    "fixed_points no valido en {variante}: {valor}"
+                ))),
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            host: host.to_string(),
+            fixed_points,
+        })
+    }
+
+    pub fn guarda<I: AsRef<Path>>(&self, ruta: I) -> io::Result<()> {
+        if let Some(dir) = ruta.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = json!({ "host": self.host, "fixed_points": self.fixed_points });
+        std::fs::write(ruta, serde_json::to_string_pretty(&json)?)
+    }
+}
+
+// Mide cada variante con cada valor de `valores` y se queda con el mas
+// rapido. Cada medida es el minimo de `repeticiones` ejecuciones y se pasa a
+// `informa` en ms segun sale. Una variante que no da el mismo resultado que
+// dyv_st se queda sin entrada: lo rapido que falle no cuenta.
+pub fn afina(
+    puntos: &[Punto],
+    valores: &[usize],
+    repeticiones: usize,
+    mut informa: impl FnMut(&str, usize, f64),
+) -> Perfil {
+    let mut perfil = Perfil {
+        host: host(),
+        ..Perfil::default()
+    };
+
+    let referencia = DyV::new_with_fixed(puntos, FIXED_REFERENCIA).start();
+    'variantes: for (variante, solver) in variantes() {
+        let mut mejor = (f64::MAX, valores[0]);
+        for &fixed in valores {
+            let mut res = referencia;
+            let ms = (0..repeticiones.max(1))
+                .map(|_| {
+                    let preparado = solver(puntos, fixed);
+                    let start = Instant::now();
+                    res = preparado();
+                    start.elapsed().as_secs_f64() * 1000.0
+                })
+                .fold(f64::MAX, f64::min);
+            if !iguales(res, referencia) {
+                eprintln!("{variante} con {fixed} da {res} y dyv_st {referencia}: sin entrada");
+                continue 'variantes;
+            }
+            informa(variante, fixed, ms);
+            if ms < mejor.0 {
+                mejor = (ms, fixed);
+            }
+        }
+        // Las medidas son con f64
+        perfil.fixed_points.insert(clave::<f64>(variante), mejor.1);
+    }
+    perfil
+}
+
+// Salvo redondeo: con dos caminos casi empatados cada solver puede quedarse
+// con uno distinto segun el orden de las sumas
+pub fn iguales(a: BestPoint, b: BestPoint) -> bool {
+    (a - b).abs() <= 1e-12 * a.abs().max(b.abs())
+}
+
+// n puntos al azar de `puntos`, en el mismo orden
+pub fn muestra(puntos: &[Punto], n: usize, seed: u64) -> Vec<Punto> {
+    if n >= puntos.len() {
+        return puntos.to_vec();
+    }
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut indices = rand::seq::index::sample(&mut rng, puntos.len(), n).into_vec();
+    indices.sort_unstable();
+    indices.into_iter().map(|i| puntos[i]).collect()
+}
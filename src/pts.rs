@@ -0,0 +1,263 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::compresion::{crea_fichero, Compresion};
+use crate::formatos::{aplica_no_finitos, avisa_quitados, NoFinitos, OpcionesLectura};
+use crate::punto::*;
+
+// Formato binario .pts:
+//   magic     [u8; 8]  "PUNTOS\0\0"
+//   version   u32 LE
+//   flags     u32 LE   bit 0: ordenado por x
+//   count     u64 LE
+//   checksum  u64 LE   FNV-1a 64 de los datos
+//   datos     count * (x: f64 LE, y: f64 LE)
+// La cabecera ocupa 32 bytes, asi que en un host little endian los datos
+// quedan alineados a 8 y se pueden ver directamente como &[Punto] desde el mmap.
+pub const MAGIC: [u8; 8] = *b"PUNTOS\0\0";
+pub const VERSION: u32 = 1;
+pub const FLAG_ORDENADO: u32 = 1;
+const TAM_CABECERA: usize = 32;
+const BYTES_PUNTO: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cabecera {
+    pub version: u32,
+    pub flags: u32,
+    pub count: u64,
+    pub checksum: u64,
+}
+
+#[allow(unused)]
+impl Cabecera {
+    pub fn ordenado(&self) -> bool {
+        self.flags & FLAG_ORDENADO != 0
+    }
+
+    fn to_bytes(self) -> [u8; TAM_CABECERA] {
+        let mut bytes = [0u8; TAM_CABECERA];
+        bytes[..8].copy_from_slice(&MAGIC);
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.flags.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.count.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < TAM_CABECERA || bytes[..8] != MAGIC {
+            return Err(invalido("no es un fichero .pts"));
+        }
+
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+
+        let cabecera = Self {
+            version: u32_at(8),
+            flags: u32_at(12),
+            count: u64_at(16),
+            checksum: u64_at(24),
+        };
+
+        if cabecera.version != VERSION {
+            return Err(invalido("version de .pts no soportada"));
+        }
+        Ok(cabecera)
+    }
+}
+
+fn invalido(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// FNV-1a de 64 bits
+#[derive(Clone, Copy)]
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+pub fn checksum(datos: &[u8]) -> u64 {
+    let mut fnv = Fnv::new();
+    fnv.update(datos);
+    fnv.0
+}
+
+#[allow(unused)]
+pub fn write_pts<I: AsRef<Path>>(name: I, puntos: &[Punto], ordenado: bool) -> io::Result<()> {
+    write_pts_compressed(name, puntos, ordenado, Compresion::Ninguna)
+}
+
+// Un .pts comprimido no se puede mapear, se lee entero con parse_pts
+#[allow(unused)]
+pub fn write_pts_compressed<I: AsRef<Path>>(
+    name: I,
+    puntos: &[Punto],
+    ordenado: bool,
+    compresion: Compresion,
+) -> io::Result<()> {
+    let mut fnv = Fnv::new();
+    for punto in puntos {
+        fnv.update(&punto.x.to_le_bytes());
+        fnv.update(&punto.y.to_le_bytes());
+    }
+
+    let cabecera = Cabecera {
+        version: VERSION,
+        flags: if ordenado { FLAG_ORDENADO } else { 0 },
+        count: puntos.len() as u64,
+        checksum: fnv.0,
+    };
+
+    let mut file = crea_fichero(name, compresion)?;
+    file.write_all(&cabecera.to_bytes())?;
+    for punto in puntos {
+        file.write_all(&punto.x.to_le_bytes())?;
+        file.write_all(&punto.y.to_le_bytes())?;
+    }
+    file.finish().map(drop)
+}
+
+// Fichero .pts mapeado en memoria
+#[allow(unused)]
+pub struct PtsFile {
+    mmap: Mmap,
+    cabecera: Cabecera,
+}
+
+#[allow(unused)]
+impl PtsFile {
+    pub fn open<I: AsRef<Path>>(name: I) -> io::Result<Self> {
+        let file = File::open(name)?;
+        // SAFETY:
+        // El fichero solo se lee; si otro proceso lo modifica mientras esta
+        // mapeado el contenido de los puntos es indefinido, igual que con cualquier mmap
+        let mmap = unsafe { Mmap::map(&file)? };
+        let cabecera = Cabecera::from_bytes(&mmap)?;
+
+        let esperado = (cabecera.count as usize)
+            .checked_mul(BYTES_PUNTO)
+            .and_then(|n| n.checked_add(TAM_CABECERA));
+        if esperado != Some(mmap.len()) {
+            return Err(invalido("tamaño de .pts no coincide con la cabecera"));
+        }
+
+        Ok(Self { mmap, cabecera })
+    }
+
+    pub fn cabecera(&self) -> Cabecera {
+        self.cabecera
+    }
+
+    pub fn datos(&self) -> &[u8] {
+        &self.mmap[TAM_CABECERA..]
+    }
+
+    pub fn verify(&self) -> bool {
+        checksum(self.datos()) == self.cabecera.checksum
+    }
+
+    // Vista sin copia de los puntos del fichero. Los datos son little endian:
+    // en un host big endian, o si no estan alineados para Punto, es un error
+    // y hay que copiar (puntos_o_copia, to_vec).
+    pub fn puntos(&self) -> io::Result<&[Punto]> {
+        if cfg!(target_endian = "big") {
+            return Err(invalido("los .pts son little endian y este host no"));
+        }
+        // SAFETY:
+        // Punto es repr(C) con dos f64 y cualquier patron de bits es valido;
+        // align_to solo devuelve en el medio lo que esta alineado
+        let (prefix, puntos, suffix) = unsafe { self.datos().align_to::<Punto>() };
+        if !prefix.is_empty() || !suffix.is_empty() {
+            return Err(invalido("datos de .pts no alineados"));
+        }
+        Ok(puntos)
+    }
+
+    // Sin copia cuando se puede
+    pub fn puntos_o_copia(&self) -> Cow<'_, [Punto]> {
+        self.puntos()
+            .map_or_else(|_| Cow::Owned(self.to_vec()), Cow::Borrowed)
+    }
+
+    // Como puntos_o_copia pero pasando por la politica de no finitos; solo
+    // copia si hay alguno. Recortar y Descartar mantienen el orden por x
+    pub fn puntos_finitos(&self, politica: NoFinitos) -> io::Result<Cow<'_, [Punto]>> {
+        let puntos = self.puntos_o_copia();
+        if puntos.iter().all(|p| p.x.is_finite() && p.y.is_finite()) {
+            return Ok(puntos);
+        }
+        let mut puntos = puntos.into_owned();
+        let quitados =
+            aplica_no_finitos(&mut puntos, politica, None, |p| p).map_err(|(indice, p)| {
+                invalido(&format!(
+                    "punto {indice}: coordenada no finita ({}, {})",
+                    p.x, p.y
+                ))
+            })?;
+        avisa_quitados(quitados);
+        Ok(Cow::Owned(puntos))
+    }
+
+    pub fn to_vec(&self) -> Vec<Punto> {
+        datos_a_puntos(self.datos())
+    }
+}
+
+fn datos_a_puntos(datos: &[u8]) -> Vec<Punto> {
+    datos
+        .chunks_exact(BYTES_PUNTO)
+        .map(|chunk| Punto {
+            x: f64::from_le_bytes(chunk[..8].try_into().unwrap()),
+            y: f64::from_le_bytes(chunk[8..].try_into().unwrap()),
+        })
+        .collect()
+}
+
+// .pts completo en memoria (p.ej. ya descomprimido), con checksum
+pub fn parse_pts(datos: &[u8]) -> io::Result<Vec<Punto>> {
+    let cabecera = Cabecera::from_bytes(datos)?;
+    let datos = &datos[TAM_CABECERA..];
+
+    if Some(datos.len()) != (cabecera.count as usize).checked_mul(BYTES_PUNTO) {
+        return Err(invalido("tamaño de .pts no coincide con la cabecera"));
+    }
+    if checksum(datos) != cabecera.checksum {
+        return Err(invalido("checksum de .pts no valido"));
+    }
+
+    Ok(datos_a_puntos(datos))
+}
+
+// TSPLIB -> .pts, ordenando por x si `ordena`. Un fichero que no se puede
+// leer o con coordenadas no validas es un error, no un panic
+#[allow(unused)]
+pub fn convierte_tsp<I: AsRef<Path>, O: AsRef<Path>>(
+    entrada: I,
+    salida: O,
+    ordena: bool,
+    compresion: Compresion,
+) -> io::Result<usize> {
+    let mut puntos = crate::lector::intenta_read_points(entrada, &OpcionesLectura::default())?;
+    if ordena {
+        puntos.sort();
+    }
+
+    let ordenado = puntos.windows(2).all(|w| w[0].x <= w[1].x);
+    write_pts_compressed(salida, &puntos, ordenado, compresion)?;
+    Ok(puntos.len())
+}
@@ -1,34 +1,55 @@
 use std::cmp::Ordering;
 
+use crate::escalar::Escalar;
+use crate::stats::{self, Contador};
+
 #[derive(Clone, Debug, Default, Copy)]
-pub struct Punto {
-    pub x: f64,
-    pub y: f64,
+#[repr(C)]
+pub struct Punto<T = f64> {
+    pub x: T,
+    pub y: T,
 }
 
 pub type BestPoint = f64;
 
-impl Punto {
+impl<T: Escalar> Punto<T> {
     #[inline(always)]
-    pub fn distancia(&self, a: &Punto) -> f64 {
-        let diff_x = (a.x - self.x) * (a.x - self.x);
-        let diff_y = (a.y - self.y) * (a.y - self.y);
-        (diff_x + diff_y).sqrt()
+    pub fn distancia(&self, a: &Punto<T>) -> f64 {
+        stats::cuenta(Contador::Distancias);
+        T::distancia(self, a)
     }
 
     #[allow(unused)]
     #[inline]
-    pub fn distancia3(&self, a: &Punto, b: &Punto) -> f64 {
+    pub fn distancia3(&self, a: &Punto<T>, b: &Punto<T>) -> f64 {
         self.distancia(a) + self.distancia(b)
     }
 
-    pub fn total_cmp(&self, other: &Punto) -> bool {
-        self.x == other.x && self.y == other.y
+    // El mismo elemento del slice, no otro punto con las mismas coordenadas:
+    // los puntos repetidos son puntos distintos a distancia 0
+    #[inline(always)]
+    pub fn mismo(&self, other: &Punto<T>) -> bool {
+        std::ptr::eq(self, other)
+    }
+
+    #[allow(unused)]
+    pub fn es_finito(&self) -> bool {
+        self.x.es_finito() && self.y.es_finito()
+    }
+
+    // Punto fijo: las coordenadas multiplicadas por `escala`. None si alguna
+    // no cabe en T
+    #[allow(unused)]
+    pub fn desde(punto: &Punto, escala: f64) -> Option<Self> {
+        Some(Self {
+            x: T::desde_f64(punto.x * escala)?,
+            y: T::desde_f64(punto.y * escala)?,
+        })
     }
 
     #[inline]
     pub fn x_comparef64(&self, other: &f64) -> Ordering {
-        self.x.total_cmp(other)
+        self.x.to_f64().total_cmp(other)
             /*
         if self.x < other {
             Ordering::Less
@@ -43,28 +64,57 @@ impl Punto {
     pub fn x_compare(&self, other: &Self) -> Ordering {
         self.x.total_cmp(&other.x)
     }
-
-    pub fn x_eq(&self, other: &Self) -> bool {
-        self.x == other.x
-    }
 }
 
-impl PartialOrd for Punto {
+impl<T: Escalar> PartialOrd for Punto<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl PartialEq for Punto {
+impl<T: Escalar> PartialEq for Punto<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.x == other.x
+        self.cmp(other) == Ordering::Equal
     }
 }
 
-impl Eq for Punto {}
+impl<T: Escalar> Eq for Punto<T> {}
 
-impl Ord for Punto {
+// Orden lexicografico (x, y); los solvers solo necesitan que este ordenado por x
+impl<T: Escalar> Ord for Punto<T> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.x.total_cmp(&other.x)
+        self.x.total_cmp(&other.x).then(self.y.total_cmp(&other.y))
+    }
+}
+
+// Indices en `puntos` (ordenado por x) de los tres puntos de `buscados`, sin
+// repetir indice aunque haya puntos repetidos
+pub fn indices_de<T: Escalar>(puntos: &[Punto<T>], buscados: &[Punto<T>; 3]) -> [usize; 3] {
+    let mut indices = [usize::MAX; 3];
+    for (n, buscado) in buscados.iter().enumerate() {
+        let inicio = puntos.partition_point(|p| p.x_compare(buscado) == Ordering::Less);
+        // SAFETY:
+        // El punto que estoy buscando siempre va a existir
+        indices[n] = (inicio..puntos.len())
+            .take_while(|&i| puntos[i].x_compare(buscado) == Ordering::Equal)
+            .find(|&i| puntos[i] == *buscado && !indices[..n].contains(&i))
+            .unwrap();
+    }
+    indices
+}
+
+// Punto con una etiqueta o dato de usuario asociado
+#[derive(Clone, Debug, Default, Copy)]
+pub struct PuntoEtiquetado<T> {
+    pub punto: Punto,
+    pub etiqueta: T,
+}
+
+// Punto con peso o coste de visita
+pub type PuntoPesado = PuntoEtiquetado<f64>;
+
+impl<T> PuntoEtiquetado<T> {
+    pub fn new(punto: Punto, etiqueta: T) -> Self {
+        Self { punto, etiqueta }
     }
 }
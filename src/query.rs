@@ -0,0 +1,200 @@
+use crate::punto::*;
+
+const MAX: f64 = f64::MAX;
+const VECINOS_INICIALES: usize = 4;
+
+// Consultas sobre el mejor camino de 3 puntos que pasa por un punto dado.
+// Trabaja sobre el mismo slice ordenado por x que los solvers y va abriendo
+// una ventana [x - r, x + r] que dobla hasta que el resultado queda demostrado.
+#[allow(unused)]
+pub struct TripleQuery<'a> {
+    puntos: &'a [Punto],
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QueryResult {
+    // Camino i-q-k con el punto consultado en el centro
+    pub middle: Option<(BestPoint, [usize; 3])>,
+    // Camino q-j-k con el punto consultado en un extremo
+    pub endpoint: Option<(BestPoint, [usize; 3])>,
+}
+
+#[allow(unused)]
+impl<'a> TripleQuery<'a> {
+    pub fn new(puntos: &'a [Punto]) -> Self {
+        Self { puntos }
+    }
+
+    pub fn query(&self, index: usize) -> QueryResult {
+        QueryResult {
+            middle: self.best_as_middle(index),
+            endpoint: self.best_as_endpoint(index),
+        }
+    }
+
+    pub fn query_point(&self, punto: &Punto) -> Option<QueryResult> {
+        self.find_index(punto).map(|index| self.query(index))
+    }
+
+    pub fn query_batch(&self, indices: &[usize]) -> Vec<QueryResult> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = indices.len().div_ceil(threads).max(1);
+
+        std::thread::scope(|s| {
+            let handles: Vec<_> = indices
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    s.spawn(move || chunk.iter().map(|i| self.query(*i)).collect::<Vec<_>>())
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        })
+    }
+
+    pub fn find_index(&self, punto: &Punto) -> Option<usize> {
+        let start = self.puntos.partition_point(|p| p.x < punto.x);
+        self.puntos[start..]
+            .iter()
+            .take_while(|p| p.x == punto.x)
+            .position(|p| p.y == punto.y)
+            .map(|i| i + start)
+    }
+
+    pub fn best_as_middle(&self, index: usize) -> Option<(BestPoint, [usize; 3])> {
+        if self.puntos.len() < 3 {
+            return None;
+        }
+
+        let punto_q = &self.puntos[index];
+        let mut radio = self.radio_inicial(index);
+
+        loop {
+            let (start, end) = self.window(punto_q, radio);
+            let mut fuera_y = false;
+
+            let (mut first, mut second) = ((MAX, index), (MAX, index));
+            for (i, punto) in self.puntos[start..=end].iter().enumerate() {
+                let i = i + start;
+                if i == index {
+                    continue;
+                }
+                if (punto.y - punto_q.y).abs() > radio {
+                    fuera_y = true;
+                    continue;
+                }
+
+                let distancia = punto_q.distancia(punto);
+                if distancia < first.0 {
+                    second = first;
+                    first = (distancia, i);
+                } else if distancia < second.0 {
+                    second = (distancia, i);
+                }
+            }
+
+            // Cualquier punto fuera de la ventana (en x o en y) esta a mas de `radio`
+            if second.0 <= radio || (Self::covers_all(start, end, self.puntos.len()) && !fuera_y) {
+                return Some((first.0 + second.0, [first.1, index, second.1]));
+            }
+            radio *= 2.0;
+        }
+    }
+
+    pub fn best_as_endpoint(&self, index: usize) -> Option<(BestPoint, [usize; 3])> {
+        if self.puntos.len() < 3 {
+            return None;
+        }
+
+        let punto_q = &self.puntos[index];
+        let mut radio = self.radio_inicial(index);
+
+        loop {
+            let (start, end) = self.window(punto_q, radio);
+            // Solo los de la ventana que tambien estan a <= radio en y, en orden de x
+            let window: Vec<usize> = (start..=end)
+                .filter(|&i| i != index && (self.puntos[i].y - punto_q.y).abs() <= radio)
+                .collect();
+            let fuera_y = window.len() < end - start;
+
+            let mut best_option = MAX;
+            let mut best_points = [index; 3];
+
+            for (a, &j) in window.iter().enumerate() {
+                let punto_j = &self.puntos[j];
+
+                let distancia_qj = punto_q.distancia(punto_j);
+                if distancia_qj >= best_option {
+                    continue;
+                }
+
+                let mejor = best_option - distancia_qj;
+                let max_x = punto_j.x + mejor;
+                let min_x = punto_j.x - mejor;
+
+                for (b, &k) in window.iter().enumerate() {
+                    let punto_k = &self.puntos[k];
+                    if b == a || punto_k.x <= min_x {
+                        continue;
+                    }
+                    if punto_k.x >= max_x {
+                        break;
+                    }
+
+                    let distancia = distancia_qj + punto_j.distancia(punto_k);
+                    if distancia < best_option {
+                        best_option = distancia;
+                        best_points = [index, j, k];
+                    }
+                }
+            }
+
+            // Todos los puntos de un camino de longitud L que empieza en q
+            // estan a distancia <= L de q
+            if best_option <= radio || (Self::covers_all(start, end, self.puntos.len()) && !fuera_y)
+            {
+                return Some((best_option, best_points));
+            }
+            radio *= 2.0;
+        }
+    }
+
+    // El vecino mas cercano de los de al lado en x; si se queda corto se
+    // dobla. Con el mas lejano la primera ventana ya cubria casi toda la y.
+    fn radio_inicial(&self, index: usize) -> f64 {
+        let punto_q = &self.puntos[index];
+        let start = index.saturating_sub(VECINOS_INICIALES);
+        let end = (index + VECINOS_INICIALES + 1).min(self.puntos.len());
+
+        let radio = self.puntos[start..end]
+            .iter()
+            .map(|p| p.distancia(punto_q))
+            .filter(|&d| d > 0.0)
+            .fold(MAX, f64::min);
+
+        if radio < MAX {
+            radio
+        } else {
+            f64::MIN_POSITIVE
+        }
+    }
+
+    fn window(&self, punto: &Punto, radio: f64) -> (usize, usize) {
+        Self::get_points_between(punto.x - radio, punto.x + radio, self.puntos)
+    }
+
+    fn covers_all(start: usize, end: usize, len: usize) -> bool {
+        start == 0 && end + 1 == len
+    }
+
+    fn get_points_between(start: f64, end: f64, puntos: &[Punto]) -> (usize, usize) {
+        // partition_point para quedarse con el primer/ultimo punto aunque haya x repetidas
+        let start_index = puntos.partition_point(|p| p.x_comparef64(&start).is_lt());
+        let end_index = puntos.partition_point(|p| p.x_comparef64(&end).is_le());
+
+        (start_index, end_index - 1)
+    }
+}
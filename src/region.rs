@@ -0,0 +1,245 @@
+use crate::punto::*;
+
+const FIXED_POINTS: usize = 108;
+const MAX: f64 = f64::MAX;
+
+// Las dos son cerradas: un punto en el borde esta dentro. Un Rectangulo con
+// min > max se toma con los extremos cambiados, igual que rectangulo()
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub enum Region {
+    Rectangulo {
+        min_x: f64,
+        max_x: f64,
+        min_y: f64,
+        max_y: f64,
+    },
+    Poligono(Vec<Punto>),
+}
+
+#[allow(unused)]
+impl Region {
+    pub fn rectangulo(a: Punto, b: Punto) -> Self {
+        Region::Rectangulo {
+            min_x: a.x.min(b.x),
+            max_x: a.x.max(b.x),
+            min_y: a.y.min(b.y),
+            max_y: a.y.max(b.y),
+        }
+    }
+
+    // (min_x, max_x, min_y, max_y)
+    pub fn bounding_box(&self) -> (f64, f64, f64, f64) {
+        match self {
+            Region::Rectangulo {
+                min_x,
+                max_x,
+                min_y,
+                max_y,
+            } => (
+                min_x.min(*max_x),
+                min_x.max(*max_x),
+                min_y.min(*max_y),
+                min_y.max(*max_y),
+            ),
+            Region::Poligono(vertices) => vertices.iter().fold(
+                (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+                |(min_x, max_x, min_y, max_y), v| {
+                    (
+                        min_x.min(v.x),
+                        max_x.max(v.x),
+                        min_y.min(v.y),
+                        max_y.max(v.y),
+                    )
+                },
+            ),
+        }
+    }
+
+    pub fn contains(&self, punto: &Punto) -> bool {
+        match self {
+            Region::Rectangulo { .. } => {
+                let (min_x, max_x, min_y, max_y) = self.bounding_box();
+                (min_x..=max_x).contains(&punto.x) && (min_y..=max_y).contains(&punto.y)
+            }
+            Region::Poligono(vertices) => Self::point_in_polygon(vertices, punto),
+        }
+    }
+
+    // Ray casting: cuenta los cruces de la semirrecta horizontal hacia +x.
+    // Es semiabierto (un punto en el borde sale dentro o fuera segun el lado),
+    // asi que el borde se mira antes.
+    fn point_in_polygon(vertices: &[Punto], punto: &Punto) -> bool {
+        let mut dentro = false;
+        let mut j = vertices.len().wrapping_sub(1);
+
+        for (i, a) in vertices.iter().enumerate() {
+            let b = &vertices[j];
+            if Self::en_segmento(a, b, punto) {
+                return true;
+            }
+            if (a.y > punto.y) != (b.y > punto.y)
+                && punto.x < (b.x - a.x) * (punto.y - a.y) / (b.y - a.y) + a.x
+            {
+                dentro = !dentro;
+            }
+            j = i;
+        }
+
+        dentro
+    }
+
+    fn en_segmento(a: &Punto, b: &Punto, punto: &Punto) -> bool {
+        let cruz = (b.x - a.x) * (punto.y - a.y) - (b.y - a.y) * (punto.x - a.x);
+        cruz == 0.0
+            && (a.x.min(b.x)..=a.x.max(b.x)).contains(&punto.x)
+            && (a.y.min(b.y)..=a.y.max(b.y)).contains(&punto.y)
+    }
+}
+
+// Mejor camino de 3 puntos dentro de una region, sobre el slice ordenado por x.
+// Los indices devueltos son del slice original. No se copian los puntos: el
+// DyV va sobre el rango de x de la region y se salta los que quedan fuera.
+#[allow(unused)]
+pub struct RegionQuery<'a> {
+    puntos: &'a [Punto],
+    // Primer indice del rango y, para cada punto del rango, si esta dentro
+    inicio: usize,
+    dentro: Vec<bool>,
+    best_option: BestPoint,
+    best_points: [usize; 3],
+    pub fixed_points: usize,
+}
+
+#[allow(unused)]
+impl<'a> RegionQuery<'a> {
+    pub fn new(puntos: &'a [Punto]) -> Self {
+        Self {
+            puntos,
+            inicio: 0,
+            dentro: Vec::new(),
+            best_option: MAX,
+            best_points: [0; 3],
+            fixed_points: FIXED_POINTS,
+        }
+    }
+
+    // Indices (ordenados) de los puntos dentro de la region. Como se recorre
+    // un rango contiguo del slice ordenado el subconjunto sigue ordenado por x.
+    pub fn select(&self, region: &Region) -> Vec<usize> {
+        let (min_x, max_x, min_y, max_y) = region.bounding_box();
+        let (start, end) = Self::get_points_between(min_x, max_x, self.puntos);
+        if start >= end {
+            return Vec::new();
+        }
+
+        (start..end)
+            .filter(|i| {
+                let punto = &self.puntos[*i];
+                (min_y..=max_y).contains(&punto.y) && region.contains(punto)
+            })
+            .collect()
+    }
+
+    pub fn start(&mut self, region: &Region) -> BestPoint {
+        let (min_x, max_x, min_y, max_y) = region.bounding_box();
+        let (start, end) = Self::get_points_between(min_x, max_x, self.puntos);
+        self.best_option = MAX;
+        self.best_points = [0; 3];
+        // Poligono sin vertices: su bounding box es (MAX, MIN, ..) y no hay rango
+        if start >= end {
+            return MAX;
+        }
+
+        self.inicio = start;
+        self.dentro.clear();
+        self.dentro.extend(
+            self.puntos[start..end]
+                .iter()
+                .map(|punto| (min_y..=max_y).contains(&punto.y) && region.contains(punto)),
+        );
+
+        if self.dentro.iter().filter(|dentro| **dentro).count() >= 3 {
+            self.divide_venceras(start, end);
+        }
+        self.best_option
+    }
+
+    pub fn get_points(&self) -> [usize; 3] {
+        self.best_points
+    }
+
+    #[inline(always)]
+    fn esta(&self, i: usize) -> bool {
+        self.dentro[i - self.inicio]
+    }
+
+    // Triples en [start, end) cuyo punto mas a la izquierda esta en [start, mid)
+    fn calcula_fixed_range(&mut self, start: usize, end: usize, mid: usize) {
+        let puntos = self.puntos;
+        for i in start..mid {
+            if !self.esta(i) {
+                continue;
+            }
+            let punto_i = &puntos[i];
+
+            for j in i + 1..end {
+                let punto_j = &puntos[j];
+                if (punto_j.x - punto_i.x) >= self.best_option {
+                    break;
+                }
+                if !self.esta(j) || (punto_j.y - punto_i.y).abs() >= self.best_option {
+                    continue;
+                }
+
+                let distancia_ij = punto_i.distancia(punto_j);
+                for (k, punto_k) in puntos.iter().enumerate().take(end).skip(j + 1) {
+                    if (punto_k.x - punto_i.x) >= self.best_option {
+                        break;
+                    }
+                    if !self.esta(k) || (punto_k.y - punto_i.y).abs() >= self.best_option {
+                        continue;
+                    }
+
+                    let distancia_jk = punto_j.distancia(punto_k);
+                    let distancia_ki = punto_k.distancia(punto_i);
+                    for (distancia, camino) in [
+                        (distancia_ij + distancia_jk, [i, j, k]),
+                        (distancia_jk + distancia_ki, [j, k, i]),
+                        (distancia_ki + distancia_ij, [k, i, j]),
+                    ] {
+                        if distancia < self.best_option {
+                            self.best_option = distancia;
+                            self.best_points = camino;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn divide_venceras(&mut self, start: usize, end: usize) {
+        if end - start < self.fixed_points {
+            return self.calcula_fixed_range(start, end, end);
+        }
+
+        let mitad_index = start + (end - start) / 2;
+        self.divide_venceras(start, mitad_index);
+        self.divide_venceras(mitad_index, end);
+
+        let mitad = self.puntos[mitad_index].x;
+        let slice = &self.puntos[start..end];
+        let new_start = start + slice.partition_point(|p| p.x <= mitad - self.best_option);
+        let new_end = start + slice.partition_point(|p| p.x < mitad + self.best_option);
+
+        self.calcula_fixed_range(new_start, new_end, mitad_index.max(new_start));
+    }
+
+    // Rango semiabierto [start, end) con start <= x <= end
+    fn get_points_between(start: f64, end: f64, puntos: &[Punto]) -> (usize, usize) {
+        let start_index = puntos.partition_point(|p| p.x_comparef64(&start).is_lt());
+        let end_index = puntos.partition_point(|p| p.x_comparef64(&end).is_le());
+
+        (start_index, end_index)
+    }
+}
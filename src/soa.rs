@@ -0,0 +1,120 @@
+use std::marker::PhantomData;
+#[cfg(feature = "simd-nightly")]
+use std::simd::{Simd, SimdElement};
+
+use crate::escalar::Escalar;
+use crate::punto::Punto;
+
+// Una linea de cache. xs e ys empiezan alineados a 64 y los vectores de
+// cualquier tipo (16, 32 o 64 bytes) se cargan enteros sin cruzar lineas
+#[derive(Clone, Copy)]
+#[repr(C, align(64))]
+struct Linea([u8; 64]);
+
+// Los mismos puntos, ordenados por x, en dos arrays contiguos. El final se
+// rellena hasta completar la ultima linea; quien lea vectores tiene que
+// descartar los carriles desde len().
+pub struct PuntosSoa<T: Escalar = f64> {
+    xs: Vec<Linea>,
+    ys: Vec<Linea>,
+    len: usize,
+    tipo: PhantomData<T>,
+}
+
+#[allow(unused)]
+impl<T: Escalar> PuntosSoa<T> {
+    // `puntos` ya ordenado: se construye una vez, despues de ordenar
+    pub fn new(puntos: &[Punto<T>]) -> Self {
+        let por_linea = size_of::<Linea>() / size_of::<T>();
+        let lineas = puntos.len().div_ceil(por_linea);
+
+        let mut xs = vec![Linea([0; 64]); lineas];
+        let mut ys = vec![Linea([0; 64]); lineas];
+        for ((x, y), punto) in escalares_mut::<T>(&mut xs)
+            .iter_mut()
+            .zip(escalares_mut::<T>(&mut ys).iter_mut())
+            .zip(puntos)
+        {
+            *x = punto.x;
+            *y = punto.y;
+        }
+
+        Self {
+            xs,
+            ys,
+            len: puntos.len(),
+            tipo: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline(always)]
+    pub fn xs(&self) -> &[T] {
+        &escalares(&self.xs)[..self.len]
+    }
+
+    #[inline(always)]
+    pub fn ys(&self) -> &[T] {
+        &escalares(&self.ys)[..self.len]
+    }
+
+    #[inline(always)]
+    pub fn punto(&self, i: usize) -> Punto<T> {
+        Punto {
+            x: self.xs()[i],
+            y: self.ys()[i],
+        }
+    }
+
+    // De vuelta a array de structs
+    pub fn to_vec(&self) -> Vec<Punto<T>> {
+        (0..self.len).map(|i| self.punto(i)).collect()
+    }
+
+    // Vectores de N carriles, relleno incluido: el bloque b son los puntos
+    // b * N .. (b + 1) * N
+    #[cfg(feature = "simd-nightly")]
+    #[inline(always)]
+    pub fn vectores<const N: usize>(&self) -> (&[Simd<T, N>], &[Simd<T, N>])
+    where
+        T: SimdElement,
+    {
+        (vectores(&self.xs), vectores(&self.ys))
+    }
+}
+
+impl<T: Escalar> From<&[Punto<T>]> for PuntosSoa<T> {
+    fn from(puntos: &[Punto<T>]) -> Self {
+        Self::new(puntos)
+    }
+}
+
+// Los Escalar son numeros primitivos: cualquier patron de bits es valido y su
+// tamano divide 64, asi que las lineas se reinterpretan sin restos. Se llama
+// en el bucle interno, por eso un cast y no align_to.
+#[inline(always)]
+fn escalares<T: Escalar>(lineas: &[Linea]) -> &[T] {
+    let len = size_of_val(lineas) / size_of::<T>();
+    unsafe { std::slice::from_raw_parts(lineas.as_ptr().cast(), len) }
+}
+
+fn escalares_mut<T: Escalar>(lineas: &mut [Linea]) -> &mut [T] {
+    let len = size_of_val(lineas) / size_of::<T>();
+    unsafe { std::slice::from_raw_parts_mut(lineas.as_mut_ptr().cast(), len) }
+}
+
+// Los vectores miden como mucho una linea y estan alineados a su tamano
+#[cfg(feature = "simd-nightly")]
+#[inline(always)]
+fn vectores<T: Escalar + SimdElement, const N: usize>(lineas: &[Linea]) -> &[Simd<T, N>] {
+    debug_assert!(size_of::<Linea>().is_multiple_of(size_of::<Simd<T, N>>()));
+    let len = size_of_val(lineas) / size_of::<Simd<T, N>>();
+    unsafe { std::slice::from_raw_parts(lineas.as_ptr().cast(), len) }
+}
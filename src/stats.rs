@@ -0,0 +1,305 @@
+use std::fmt;
+
+use serde_json::{json, Map, Value};
+
+// Contadores de poda de los solvers. Solo cuentan con la feature `stats`; sin
+// ella todas las funciones de aqui son vacias y el compilador las quita.
+// Son globales al proceso: reinicia() antes de lo que se quiera medir. Cada
+// hilo cuenta en local y lo pasa a los globales con vuelca(), que los hilos
+// de DyVMT llaman al acabar cada hoja; lee() vuelca antes lo del hilo que lee.
+pub const ACTIVAS: bool = cfg!(feature = "stats");
+
+// Las hojas (calcula_fixed) y las franjas de recheck_actual_best
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fase {
+    Hojas,
+    Franja,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Contador {
+    // Candidatos que se llegan a mirar en cada posicion del camino
+    I,
+    J,
+    K,
+    // Llamadas a Punto::distancia
+    Distancias,
+    // j y k descartados por la y sin calcular distancias (en DyVSIMD tambien
+    // los que descarta el filtro vectorial de distancias)
+    RechazosY,
+    // Bucles de j que acaban por la x antes del final del rango
+    CortesX,
+    // Veces que baja best_option
+    Mejoras,
+    // recheck_actual_best: cuantos, puntos en franja y la franja mas grande
+    Franjas,
+    PuntosFranja,
+    MaxFranja,
+}
+
+impl Fase {
+    pub const TODAS: [Fase; 2] = [Fase::Hojas, Fase::Franja];
+
+    pub fn nombre(self) -> &'static str {
+        match self {
+            Fase::Hojas => "hojas",
+            Fase::Franja => "franja",
+        }
+    }
+}
+
+impl Contador {
+    pub const TODOS: [Contador; 10] = [
+        Contador::I,
+        Contador::J,
+        Contador::K,
+        Contador::Distancias,
+        Contador::RechazosY,
+        Contador::CortesX,
+        Contador::Mejoras,
+        Contador::Franjas,
+        Contador::PuntosFranja,
+        Contador::MaxFranja,
+    ];
+
+    pub fn nombre(self) -> &'static str {
+        match self {
+            Contador::I => "candidatos_i",
+            Contador::J => "candidatos_j",
+            Contador::K => "candidatos_k",
+            Contador::Distancias => "distancias",
+            Contador::RechazosY => "rechazos_y",
+            Contador::CortesX => "cortes_x",
+            Contador::Mejoras => "mejoras",
+            Contador::Franjas => "franjas",
+            Contador::PuntosFranja => "puntos_franja",
+            Contador::MaxFranja => "max_franja",
+        }
+    }
+}
+
+const FASES: usize = Fase::TODAS.len();
+const CONTADORES: usize = Contador::TODOS.len();
+
+// Lo contado hasta ahora, por fase
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Estadisticas {
+    valores: [[u64; CONTADORES]; FASES],
+}
+
+#[allow(unused)]
+impl Estadisticas {
+    pub fn get(&self, fase: Fase, contador: Contador) -> u64 {
+        self.valores[fase as usize][contador as usize]
+    }
+
+    // Sumando las dos fases
+    pub fn total(&self, contador: Contador) -> u64 {
+        let valores = Fase::TODAS.map(|fase| self.get(fase, contador));
+        match contador {
+            Contador::MaxFranja => valores.into_iter().max().unwrap_or(0),
+            _ => valores.into_iter().sum(),
+        }
+    }
+
+    pub fn a_json(&self) -> Value {
+        let mut raiz = Map::new();
+        for fase in Fase::TODAS {
+            let contadores: Map<String, Value> = Contador::TODOS
+                .iter()
+                .map(|&contador| {
+                    (
+                        contador.nombre().to_string(),
+                        json!(self.get(fase, contador)),
+                    )
+                })
+                .collect();
+            raiz.insert(fase.nombre().to_string(), Value::Object(contadores));
+        }
+        Value::Object(raiz)
+    }
+}
+
+impl fmt::Display for Estadisticas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>14} {:>14} {:>14}", "", "hojas", "franja")?;
+        for contador in Contador::TODOS {
+            writeln!(
+                f,
+                "{:>14} {:>14} {:>14}",
+                contador.nombre(),
+                self.get(Fase::Hojas, contador),
+                self.get(Fase::Franja, contador)
+            )?;
+        }
+        let franjas = self.total(Contador::Franjas).max(1);
+        write!(
+            f,
+            "{:>14} {:>14.1}",
+            "media_franja",
+            self.total(Contador::PuntosFranja) as f64 / franjas as f64
+        )
+    }
+}
+
+#[cfg(feature = "stats")]
+mod activas {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+    use super::*;
+
+    static VALORES: [[AtomicU64; CONTADORES]; FASES] =
+        [const { [const { AtomicU64::new(0) }; CONTADORES] }; FASES];
+
+    thread_local! {
+        static FASE: Cell<Fase> = const { Cell::new(Fase::Hojas) };
+        // Lo mismo que VALORES pero solo de este hilo
+        static DEL_HILO: Cell<[[u64; CONTADORES]; FASES]> =
+            const { Cell::new([[0; CONTADORES]; FASES]) };
+        // Lo de este hilo que aun no esta en VALORES
+        static PENDIENTE: Cell<[[u64; CONTADORES]; FASES]> =
+            const { Cell::new([[0; CONTADORES]; FASES]) };
+    }
+
+    #[inline(always)]
+    fn en_hilo(contador: Contador, f: impl Fn(u64) -> u64) {
+        let fase = FASE.with(Cell::get);
+        for local in [&DEL_HILO, &PENDIENTE] {
+            local.with(|hilo| {
+                let mut valores = hilo.get();
+                let valor = &mut valores[fase as usize][contador as usize];
+                *valor = f(*valor);
+                hilo.set(valores);
+            });
+        }
+    }
+
+    #[inline(always)]
+    pub fn suma(contador: Contador, n: u64) {
+        en_hilo(contador, |valor| valor + n);
+    }
+
+    pub fn franja(puntos: usize) {
+        suma(Contador::Franjas, 1);
+        suma(Contador::PuntosFranja, puntos as u64);
+        en_hilo(Contador::MaxFranja, |valor| valor.max(puntos as u64));
+    }
+
+    pub fn vuelca() {
+        let pendiente = PENDIENTE.with(|hilo| hilo.replace([[0; CONTADORES]; FASES]));
+        for (globales, locales) in VALORES.iter().zip(pendiente) {
+            for (contador, (global, local)) in globales.iter().zip(locales).enumerate() {
+                if contador == Contador::MaxFranja as usize {
+                    global.fetch_max(local, Relaxed);
+                } else if local != 0 {
+                    global.fetch_add(local, Relaxed);
+                }
+            }
+        }
+    }
+
+    pub struct Guarda(Fase);
+
+    pub fn en_fase(fase: Fase) -> Guarda {
+        Guarda(FASE.with(|actual| actual.replace(fase)))
+    }
+
+    impl Drop for Guarda {
+        fn drop(&mut self) {
+            FASE.with(|actual| actual.set(self.0));
+        }
+    }
+
+    pub fn reinicia() {
+        PENDIENTE.with(|hilo| hilo.set([[0; CONTADORES]; FASES]));
+        VALORES
+            .iter()
+            .flatten()
+            .for_each(|valor| valor.store(0, Relaxed));
+    }
+
+    pub fn lee() -> Estadisticas {
+        vuelca();
+        Estadisticas {
+            valores: VALORES
+                .each_ref()
+                .map(|fase| fase.each_ref().map(|valor| valor.load(Relaxed))),
+        }
+    }
+
+    pub fn reinicia_hilo() {
+        DEL_HILO.with(|hilo| hilo.set([[0; CONTADORES]; FASES]));
+    }
+
+    pub fn lee_hilo() -> Estadisticas {
+        Estadisticas {
+            valores: DEL_HILO.with(Cell::get),
+        }
+    }
+
+    // Lo de otro hilo, que ya esta en los globales
+    pub fn anade_hilo(otro: &Estadisticas) {
+        DEL_HILO.with(|hilo| {
+            let mut valores = hilo.get();
+            for (fase, otros) in valores.iter_mut().zip(&otro.valores) {
+                for (contador, (valor, otro)) in fase.iter_mut().zip(otros).enumerate() {
+                    if contador == Contador::MaxFranja as usize {
+                        *valor = (*valor).max(*otro);
+                    } else {
+                        *valor += otro;
+                    }
+                }
+            }
+            hilo.set(valores);
+        });
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+mod activas {
+    use super::*;
+
+    #[inline(always)]
+    pub fn suma(_: Contador, _: u64) {}
+
+    #[inline(always)]
+    pub fn franja(_: usize) {}
+
+    #[inline(always)]
+    pub fn vuelca() {}
+
+    pub struct Guarda;
+
+    #[inline(always)]
+    pub fn en_fase(_: Fase) -> Guarda {
+        Guarda
+    }
+
+    pub fn reinicia() {}
+
+    pub fn lee() -> Estadisticas {
+        Estadisticas::default()
+    }
+
+    pub fn reinicia_hilo() {}
+
+    pub fn lee_hilo() -> Estadisticas {
+        Estadisticas::default()
+    }
+
+    #[inline(always)]
+    pub fn anade_hilo(_: &Estadisticas) {}
+}
+
+// lee_hilo() es lo contado desde este hilo, sin lo de otros tests o hilos que
+// cuentan a la vez
+#[allow(unused)]
+pub use activas::{
+    anade_hilo, en_fase, franja, lee, lee_hilo, reinicia, reinicia_hilo, suma, vuelca, Guarda,
+};
+
+#[inline(always)]
+pub fn cuenta(contador: Contador) {
+    suma(contador, 1)
+}
@@ -1,14 +1,49 @@
 #[cfg(test)]
 mod tests {
+    use crate::punto::Punto;
+    use rand::{Rng, SeedableRng};
+
+    fn puntos_aleatorios(n: usize, seed: u64) -> Vec<Punto> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut puntos: Vec<Punto> = (0..n)
+            .map(|_| Punto {
+                x: rng.gen_range(-1000.0..1000.0),
+                y: rng.gen_range(-1000.0..1000.0),
+            })
+            .collect();
+        puntos.sort();
+        puntos
+    }
+
+    fn fuerza_bruta(puntos: &[Punto]) -> f64 {
+        let mut best = f64::MAX;
+        for (j, punto_j) in puntos.iter().enumerate() {
+            for (i, punto_i) in puntos.iter().enumerate() {
+                for (k, punto_k) in puntos.iter().enumerate().skip(i + 1) {
+                    if i != j && k != j {
+                        best = best.min(punto_i.distancia(punto_j) + punto_j.distancia(punto_k));
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    // Los ficheros se pasan a los solvers por el mismo camino que la linea de
+    // comandos, sin suponer que vienen ordenados
+    fn lee_ordenado(fichero: &str) -> Vec<Punto> {
+        let puntos = crate::read_points_from_file(fichero);
+        crate::orden::Ordenados::new(&puntos).puntos().to_vec()
+    }
 
     mod multi_thread {
 
         use crate::dyv_simd::DyVSIMD;
-        use crate::read_points_from_file;
+        use super::lee_ordenado;
         type MetodoCalculo<'a> = DyVSIMD<'a>;
         #[test]
         fn test_1() {
-            let puntos = read_points_from_file("point_files/puntos_800000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_800000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.03781606866923333);
@@ -16,7 +51,7 @@ mod tests {
 
         #[test]
         fn test_2() {
-            let puntos = read_points_from_file("point_files/puntos_500000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_500000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.05537844995897111);
@@ -25,14 +60,14 @@ mod tests {
         // [30_000, 50_000, 80_000, 150_000,
         #[test]
         fn test_3() {
-            let puntos = read_points_from_file("point_files/puntos_150000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_150000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.144052106804805);
         }
         #[test]
         fn test_4() {
-            let puntos = read_points_from_file("point_files/puntos_80000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_80000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.322497589363708);
@@ -40,14 +75,14 @@ mod tests {
 
         #[test]
         fn test_5() {
-            let puntos = read_points_from_file("point_files/puntos_50000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_50000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.30805303890637226);
         }
         #[test]
         fn test_6() {
-            let puntos = read_points_from_file("point_files/puntos_30000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_30000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.27095504920223346);
@@ -68,7 +103,7 @@ mod tests {
             for i in 0..5 {
                 let t = std::thread::spawn(move || {
                     let puntos =
-                        read_points_from_file(&format!("point_files/puntos_rand_{}.tsp", i));
+                        lee_ordenado(&format!("point_files/puntos_rand_{}.tsp", i));
                     let mut dyv = MetodoCalculo::new(&puntos);
                     let res = dyv.start();
                     assert_eq!(res, answers[i]);
@@ -96,7 +131,7 @@ mod tests {
             for i in 5..10 {
                 threads.push(std::thread::spawn(move || {
                     let puntos =
-                        read_points_from_file(&format!("point_files/puntos_rand_{}.tsp", i));
+                        lee_ordenado(&format!("point_files/puntos_rand_{}.tsp", i));
                     let mut dyv = MetodoCalculo::new(&puntos);
                     let res = dyv.start();
                     assert_eq!(res, answers[i - 5]);
@@ -110,7 +145,7 @@ mod tests {
 
         #[test]
         fn d657() {
-            let puntos = read_points_from_file("point_files/d657.tsp");
+            let puntos = lee_ordenado("point_files/d657.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 35.9210244842761);
@@ -119,11 +154,11 @@ mod tests {
 
     mod single_thread {
         use crate::dyv_simd::DyVSIMD;
-        use crate::read_points_from_file;
+        use super::lee_ordenado;
         type MetodoCalculo<'a> = DyVSIMD<'a>;
         #[test]
         fn test_1() {
-            let puntos = read_points_from_file("point_files/puntos_800000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_800000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.03781606866923333);
@@ -131,7 +166,7 @@ mod tests {
 
         #[test]
         fn test_2() {
-            let puntos = read_points_from_file("point_files/puntos_500000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_500000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.05537844995897111);
@@ -140,14 +175,14 @@ mod tests {
         // [30_000, 50_000, 80_000, 150_000,
         #[test]
         fn test_3() {
-            let puntos = read_points_from_file("point_files/puntos_150000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_150000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.144052106804805);
         }
         #[test]
         fn test_4() {
-            let puntos = read_points_from_file("point_files/puntos_80000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_80000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.322497589363708);
@@ -155,14 +190,14 @@ mod tests {
 
         #[test]
         fn test_5() {
-            let puntos = read_points_from_file("point_files/puntos_50000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_50000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.30805303890637226);
         }
         #[test]
         fn test_6() {
-            let puntos = read_points_from_file("point_files/puntos_30000.tsp");
+            let puntos = lee_ordenado("point_files/puntos_30000.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 0.27095504920223346);
@@ -183,7 +218,7 @@ mod tests {
             for i in 0..5 {
                 let t = std::thread::spawn(move || {
                     let puntos =
-                        read_points_from_file(&format!("point_files/puntos_rand_{}.tsp", i));
+                        lee_ordenado(&format!("point_files/puntos_rand_{}.tsp", i));
                     let mut dyv = MetodoCalculo::new(&puntos);
                     let res = dyv.start();
                     assert!(res.eq(&answers[i]));
@@ -211,7 +246,7 @@ mod tests {
             for i in 5..10 {
                 threads.push(std::thread::spawn(move || {
                     let puntos =
-                        read_points_from_file(&format!("point_files/puntos_rand_{}.tsp", i));
+                        lee_ordenado(&format!("point_files/puntos_rand_{}.tsp", i));
                     let mut dyv = MetodoCalculo::new(&puntos);
                     let res = dyv.start();
                     assert_eq!(res, answers[i - 5]);
@@ -225,7 +260,7 @@ mod tests {
 
         #[test]
         fn d657() {
-            let puntos = read_points_from_file("point_files/d657.tsp");
+            let puntos = lee_ordenado("point_files/d657.tsp");
             let mut dyv = MetodoCalculo::new(&puntos);
             let res = dyv.start();
             assert_eq!(res, 35.9210244842761);
@@ -247,11 +282,2318 @@ mod tests {
             ];
 
             for i in 0..10 {
-                let puntos = read_points_from_file(&format!("point_files/puntos_rand_small_{}.tsp", i));
+                let puntos = lee_ordenado(&format!("point_files/puntos_rand_small_{}.tsp", i));
                 let mut dyv = MetodoCalculo::new(&puntos);
                 let res = dyv.start();
                 assert_eq!(res, answers[i]);
             }
         }
     }
+
+    mod kdtree {
+        use super::{fuerza_bruta, puntos_aleatorios};
+        use crate::kdtree::{KdTree, TripleKd};
+
+        #[test]
+        fn nearest_and_radius() {
+            let puntos = puntos_aleatorios(2_000, 26);
+            let tree = KdTree::new(&puntos);
+
+            for query in puntos_aleatorios(50, 126).iter() {
+                let mut distancias: Vec<f64> = puntos.iter().map(|p| p.distancia(query)).collect();
+                distancias.sort_by(f64::total_cmp);
+
+                let vecinos = tree.nearest(query, 5);
+                assert_eq!(vecinos.len(), 5);
+                for (v, d) in vecinos.iter().zip(&distancias) {
+                    assert_eq!(v.1, *d);
+                    assert_eq!(puntos[v.0].distancia(query), *d);
+                }
+
+                let radio = distancias[20];
+                let dentro = tree.within_radius(query, radio);
+                assert_eq!(dentro.len(), distancias.iter().filter(|d| **d <= radio).count());
+            }
+        }
+
+        #[test]
+        fn triple_matches_brute_force() {
+            for seed in 0..5 {
+                let puntos = puntos_aleatorios(300, seed);
+                let mut solver = TripleKd::new(&puntos);
+                let res = solver.start();
+                assert_eq!(res, fuerza_bruta(&puntos));
+
+                let [i, j, k] = solver.get_points();
+                assert_eq!(res, puntos[i].distancia(&puntos[j]) + puntos[j].distancia(&puntos[k]));
+            }
+        }
+    }
+
+    mod query {
+        use super::puntos_aleatorios;
+        use crate::query::TripleQuery;
+
+        fn brute_force(puntos: &[crate::punto::Punto], q: usize) -> (f64, f64) {
+            let (mut middle, mut endpoint) = (f64::MAX, f64::MAX);
+            for i in 0..puntos.len() {
+                for k in 0..puntos.len() {
+                    if i == k || i == q || k == q {
+                        continue;
+                    }
+                    middle =
+                        middle.min(puntos[i].distancia(&puntos[q]) + puntos[q].distancia(&puntos[k]));
+                    endpoint =
+                        endpoint.min(puntos[q].distancia(&puntos[i]) + puntos[i].distancia(&puntos[k]));
+                }
+            }
+            (middle, endpoint)
+        }
+
+        #[test]
+        fn matches_brute_force() {
+            let puntos = puntos_aleatorios(400, 27);
+            let query = TripleQuery::new(&puntos);
+
+            for q in (0..puntos.len()).step_by(7) {
+                let res = query.query(q);
+                let (middle, endpoint) = brute_force(&puntos, q);
+
+                let (distancia, [i, j, k]) = res.middle.unwrap();
+                assert_eq!(distancia, middle);
+                assert_eq!(j, q);
+                assert_eq!(
+                    distancia,
+                    puntos[i].distancia(&puntos[j]) + puntos[j].distancia(&puntos[k])
+                );
+
+                let (distancia, [i, j, k]) = res.endpoint.unwrap();
+                assert_eq!(distancia, endpoint);
+                assert_eq!(i, q);
+                assert_eq!(
+                    distancia,
+                    puntos[i].distancia(&puntos[j]) + puntos[j].distancia(&puntos[k])
+                );
+            }
+        }
+
+        // Todas las x en una franja estrecha: la ventana en x cubre toda la
+        // entrada y solo el filtro por y la recorta. Con puntos repetidos.
+        #[test]
+        fn narrow_strip_and_duplicates() {
+            let mut puntos = puntos_aleatorios(300, 28);
+            for punto in puntos.iter_mut() {
+                punto.x /= 1000.0;
+            }
+            puntos.extend_from_within(..20);
+            puntos.sort();
+            let query = TripleQuery::new(&puntos);
+
+            for q in (0..puntos.len()).step_by(5) {
+                let res = query.query(q);
+                let (middle, endpoint) = brute_force(&puntos, q);
+                assert_eq!(res.middle.unwrap().0, middle);
+                assert_eq!(res.endpoint.unwrap().0, endpoint);
+            }
+        }
+
+        #[test]
+        fn batch_and_point_queries() {
+            let puntos = puntos_aleatorios(1_000, 127);
+            let query = TripleQuery::new(&puntos);
+
+            let indices: Vec<usize> = (0..puntos.len()).step_by(3).collect();
+            let batch = query.query_batch(&indices);
+            assert_eq!(batch.len(), indices.len());
+
+            for (index, res) in indices.iter().zip(&batch) {
+                assert_eq!(*res, query.query(*index));
+                assert_eq!(query.query_point(&puntos[*index]), Some(*res));
+            }
+        }
+    }
+
+    mod region {
+        use super::{fuerza_bruta, puntos_aleatorios};
+        use crate::dyv_st::DyV;
+        use crate::punto::Punto;
+        use crate::region::{Region, RegionQuery};
+
+        fn check(puntos: &[Punto], region: &Region) {
+            let subset: Vec<Punto> = puntos
+                .iter()
+                .filter(|p| region.contains(p))
+                .copied()
+                .collect();
+            let mut query = RegionQuery::new(puntos);
+            let res = query.start(region);
+
+            assert_eq!(res, DyV::new(&subset).start());
+            assert_eq!(res, fuerza_bruta(&subset));
+
+            let [i, j, k] = query.get_points().map(|i| puntos[i]);
+            assert!([i, j, k].iter().all(|p| region.contains(p)));
+            let (ij, jk, ki) = (i.distancia(&j), j.distancia(&k), k.distancia(&i));
+            assert_eq!(res, (ij + jk).min(jk + ki).min(ki + ij));
+        }
+
+        #[test]
+        fn rectangle() {
+            let puntos = puntos_aleatorios(5_000, 28);
+            check(
+                &puntos,
+                &Region::rectangulo(
+                    Punto {
+                        x: -200.0,
+                        y: 100.0,
+                    },
+                    Punto { x: 50.0, y: -150.0 },
+                ),
+            );
+            check(
+                &puntos,
+                &Region::rectangulo(Punto { x: 500.0, y: 500.0 }, Punto { x: 900.0, y: 990.0 }),
+            );
+        }
+
+        // Sin rango de x no hay triple, en vez de un slice al reves
+        #[test]
+        fn empty_polygon_and_swapped_rectangle() {
+            let puntos = puntos_aleatorios(2_000, 29);
+            let mut query = RegionQuery::new(&puntos);
+            assert_eq!(query.start(&Region::Poligono(vec![])), f64::MAX);
+            assert!(query.select(&Region::Poligono(vec![])).is_empty());
+
+            // Igual que Region::rectangulo con las esquinas al reves
+            let al_reves = Region::Rectangulo {
+                min_x: 200.0,
+                max_x: -200.0,
+                min_y: 150.0,
+                max_y: -150.0,
+            };
+            let a = Punto { x: -200.0, y: -150.0 };
+            let b = Punto { x: 200.0, y: 150.0 };
+            assert_eq!(query.select(&al_reves), query.select(&Region::rectangulo(a, b)));
+            check(&puntos, &al_reves);
+        }
+
+        #[test]
+        fn polygon() {
+            let puntos = puntos_aleatorios(5_000, 128);
+            let poligono = Region::Poligono(vec![
+                Punto {
+                    x: -300.0,
+                    y: -300.0,
+                },
+                Punto {
+                    x: 300.0,
+                    y: -250.0,
+                },
+                Punto { x: 0.0, y: 0.0 },
+                Punto { x: 250.0, y: 300.0 },
+                Punto {
+                    x: -300.0,
+                    y: 200.0,
+                },
+            ]);
+            check(&puntos, &poligono);
+            assert!(!poligono.contains(&Punto { x: 200.0, y: 0.0 }));
+            assert!(poligono.contains(&Punto { x: -200.0, y: 0.0 }));
+        }
+
+        // Las dos regiones son cerradas: los bordes y los vertices estan dentro
+        #[test]
+        fn boundary_is_inside() {
+            let p = |x, y| Punto { x, y };
+            let rectangulo = Region::rectangulo(p(0.0, 0.0), p(4.0, 2.0));
+            let cuadrado = Region::Poligono(vec![p(0.0, 0.0), p(4.0, 0.0), p(4.0, 2.0), p(0.0, 2.0)]);
+            let triangulo = Region::Poligono(vec![p(0.0, 0.0), p(4.0, 0.0), p(0.0, 4.0)]);
+
+            for x in -1..=5 {
+                for y in -1..=3 {
+                    let punto = p(x as f64, y as f64);
+                    assert_eq!(
+                        rectangulo.contains(&punto),
+                        cuadrado.contains(&punto),
+                        "{punto:?}"
+                    );
+                }
+            }
+            for punto in [
+                p(0.0, 0.0),
+                p(2.0, 0.0),
+                p(4.0, 0.0),
+                p(0.0, 3.0),
+                p(1.0, 3.0),
+                p(3.0, 1.0),
+            ] {
+                assert!(triangulo.contains(&punto), "{punto:?}");
+            }
+            assert!(!triangulo.contains(&p(3.0, 1.5)));
+
+            // Los puntos de los bordes cuentan tambien para el solve
+            let puntos = vec![p(0.0, 2.0), p(2.0, 0.0), p(4.0, 0.0), p(4.0, 3.0)];
+            let mut query = RegionQuery::new(&puntos);
+            assert_eq!(query.start(&cuadrado), query.start(&rectangulo));
+            assert_eq!(query.start(&cuadrado), 2.0 + 8f64.sqrt());
+        }
+
+        // Muchas regiones sobre los mismos puntos, con hojas pequeñas para que
+        // haya franjas
+        #[test]
+        fn repeated_queries() {
+            let puntos = puntos_aleatorios(3_000, 29);
+            let mut query = RegionQuery::new(&puntos);
+            query.fixed_points = 8;
+            for (a, b) in [(-900.0, -100.0), (-50.0, 400.0), (300.0, 310.0)] {
+                let region = Region::rectangulo(Punto { x: a, y: a }, Punto { x: b, y: b });
+                let subset: Vec<Punto> = puntos
+                    .iter()
+                    .filter(|p| region.contains(p))
+                    .copied()
+                    .collect();
+                let res = query.start(&region);
+                if subset.len() < 3 {
+                    assert_eq!(res, f64::MAX);
+                } else {
+                    assert_eq!(res, fuerza_bruta(&subset));
+                }
+            }
+        }
+    }
+
+    mod clases {
+        use super::puntos_aleatorios;
+        use crate::dyv_clases::{DyVClases, Orden};
+        use crate::formatos::NoFinitos::{Descartar, Rechazar, Recortar};
+        use crate::punto::PuntoEtiquetado;
+
+        fn etiquetados(n: usize, seed: u64) -> Vec<PuntoEtiquetado<char>> {
+            puntos_aleatorios(n, seed)
+                .into_iter()
+                .enumerate()
+                .map(|(i, p)| PuntoEtiquetado::new(p, ['a', 'h', 's', 'x'][(i * 7 + i / 3) % 4]))
+                .collect()
+        }
+
+        fn brute_force(puntos: &[PuntoEtiquetado<char>], clases: [char; 3], orden: Orden) -> f64 {
+            let mut best = f64::MAX;
+            for a in puntos.iter().filter(|p| p.etiqueta == clases[0]) {
+                for b in puntos.iter().filter(|p| p.etiqueta == clases[1]) {
+                    for c in puntos.iter().filter(|p| p.etiqueta == clases[2]) {
+                        let (ab, bc, ca) = (
+                            a.punto.distancia(&b.punto),
+                            b.punto.distancia(&c.punto),
+                            c.punto.distancia(&a.punto),
+                        );
+                        best = match orden {
+                            Orden::Fijo => best.min(ab + bc),
+                            Orden::Cualquiera => best.min(ab + bc).min(bc + ca).min(ca + ab),
+                        };
+                    }
+                }
+            }
+            best
+        }
+
+        #[test]
+        fn matches_brute_force() {
+            for seed in 0..4 {
+                let puntos = etiquetados(300, seed);
+                for orden in [Orden::Cualquiera, Orden::Fijo] {
+                    let clases = ['s', 'a', 'h'];
+                    let mut dyv = DyVClases::new_with_fixed(&puntos, clases, orden, 20);
+                    let res = dyv.start();
+
+                    let expected = brute_force(&puntos, clases, orden);
+                    assert!((res - expected).abs() <= expected * 1e-12);
+
+                    let [i, j, k] = dyv.get_points();
+                    let camino = [puntos[i], puntos[j], puntos[k]];
+                    assert_eq!(res, camino[0].punto.distancia(&camino[1].punto) + camino[1].punto.distancia(&camino[2].punto));
+                    if orden == Orden::Fijo {
+                        assert_eq!(camino.map(|p| p.etiqueta), clases);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn reads_label_column() {
+            let path = std::env::temp_dir().join("puntitos_labels.tsp");
+            std::fs::write(&path, "NAME : test\nNODE_COORD_SECTION\n1 0.5 1.5 3\n2 2 3\n3 -1 4.25 7\nEOF\n").unwrap();
+
+            let puntos = crate::read_labelled_points_from_file(&path, 0u32, Rechazar);
+            let etiquetas: Vec<u32> = puntos.iter().map(|p| p.etiqueta).collect();
+            assert_eq!(etiquetas, [3, 0, 7]);
+            assert_eq!((puntos[2].punto.x, puntos[2].punto.y), (-1.0, 4.25));
+        }
+
+        // La etiqueta se va con su punto al quitar o recortar no finitos
+        #[test]
+        fn labels_follow_non_finite_policy() {
+            let path = std::env::temp_dir().join("puntitos_labels_nan.tsp");
+            let tsp = "NODE_COORD_SECTION\n1 0 0 1\n2 nan 1 2\n3 2 inf 3\n4 1 1 4\nEOF\n";
+            std::fs::write(&path, tsp).unwrap();
+
+            let puntos = crate::read_labelled_points_from_file(&path, 0u32, Descartar);
+            assert_eq!(puntos.iter().map(|p| p.etiqueta).collect::<Vec<_>>(), [1, 4]);
+
+            let puntos = crate::read_weighted_points_from_file(&path, 0.0, Recortar);
+            assert_eq!(puntos.iter().map(|p| p.etiqueta).collect::<Vec<_>>(), [1.0, 3.0, 4.0]);
+            assert_eq!((puntos[1].punto.x, puntos[1].punto.y), (2.0, 1.0));
+
+            let error = std::panic::catch_unwind(|| {
+                crate::read_labelled_points_from_file(&path, 0u32, Rechazar)
+            });
+            assert!(error.is_err());
+        }
+
+        // El lector de etiquetas pasa por formatos: CSV, comprimidos y errores con su linea
+        #[test]
+        fn labels_in_any_format() {
+            use crate::compresion::{crea_fichero, Compresion};
+            use crate::formatos::{lee_etiquetados, Formato, OpcionesLectura};
+            use std::io::Write;
+
+            let path = std::env::temp_dir().join("puntitos_labels.csv");
+            std::fs::write(&path, "x,y,label\n0.5,1.5,3\n2,3,\n-1,4.25,7\n").unwrap();
+            let puntos = crate::read_labelled_points_from_file(&path, 0u32, Rechazar);
+            assert_eq!(puntos.iter().map(|p| p.etiqueta).collect::<Vec<_>>(), [3, 0, 7]);
+            assert_eq!((puntos[2].punto.x, puntos[2].punto.y), (-1.0, 4.25));
+
+            let path = std::env::temp_dir().join("puntitos_labels.tsp.gz");
+            let mut fichero = crea_fichero(&path, Compresion::Gzip).unwrap();
+            fichero.write_all(b"NODE_COORD_SECTION\n1 0 0 a\n2 1 1\n3 2 2 c\nEOF\n").unwrap();
+            fichero.finish().unwrap();
+            let puntos = crate::read_labelled_points_from_file(&path, 'z', Rechazar);
+            assert_eq!(puntos.iter().map(|p| p.etiqueta).collect::<Vec<_>>(), ['a', 'z', 'c']);
+
+            let tsp = b"NODE_COORD_SECTION\n1 0 0 3\n2 1 1 x\n";
+            let error = lee_etiquetados(tsp, Formato::Tsplib, &OpcionesLectura::default(), 0u32);
+            let error = error.unwrap_err().to_string();
+            assert!(error.starts_with("linea 3, nodo 2: etiqueta no valida: x"), "{error}");
+        }
+
+        #[test]
+        fn repeated_classes_are_rejected() {
+            let puntos = etiquetados(20, 0);
+            let repetidas =
+                std::panic::catch_unwind(|| DyVClases::new(&puntos, ['a', 'h', 'a'], Orden::Fijo));
+            assert!(repetidas.is_err());
+        }
+    }
+
+    mod pesos {
+        use super::puntos_aleatorios;
+        use crate::dyv_pesos::{DyVPesos, Objetivo};
+        use crate::punto::{PuntoEtiquetado, PuntoPesado};
+        use rand::{Rng, SeedableRng};
+
+        fn pesados(n: usize, seed: u64) -> Vec<PuntoPesado> {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            puntos_aleatorios(n, seed)
+                .into_iter()
+                .map(|p| PuntoEtiquetado::new(p, rng.gen_range(0.5..40.0)))
+                .collect()
+        }
+
+        fn brute_force(puntos: &[PuntoPesado], objetivo: Objetivo) -> f64 {
+            let arista = |a: &PuntoPesado, b: &PuntoPesado| match objetivo {
+                Objetivo::Aditivo => a.punto.distancia(&b.punto),
+                Objetivo::Escalado => a.punto.distancia(&b.punto) * (a.etiqueta + b.etiqueta) / 2.0,
+            };
+
+            let mut best = f64::MAX;
+            for (j, b) in puntos.iter().enumerate() {
+                for (i, a) in puntos.iter().enumerate() {
+                    for (k, c) in puntos.iter().enumerate().skip(i + 1) {
+                        if i == j || k == j {
+                            continue;
+                        }
+                        let vertices = match objetivo {
+                            Objetivo::Aditivo => a.etiqueta + b.etiqueta + c.etiqueta,
+                            Objetivo::Escalado => 0.0,
+                        };
+                        best = best.min(arista(a, b) + arista(b, c) + vertices);
+                    }
+                }
+            }
+            best
+        }
+
+        #[test]
+        fn matches_brute_force() {
+            for seed in 0..4 {
+                let puntos = pesados(250, seed);
+                for objetivo in [Objetivo::Aditivo, Objetivo::Escalado] {
+                    let mut dyv = DyVPesos::new_with_fixed(&puntos, objetivo, 16);
+                    let res = dyv.start();
+                    let expected = brute_force(&puntos, objetivo);
+                    assert!((res - expected).abs() <= expected * 1e-12, "{res} {expected}");
+                }
+            }
+        }
+
+        #[test]
+        fn zero_weights_match_unweighted() {
+            let puntos = puntos_aleatorios(2_000, 30);
+            let pesados: Vec<PuntoPesado> = puntos.iter().map(|p| PuntoEtiquetado::new(*p, 0.0)).collect();
+
+            let mut dyv = DyVPesos::new(&pesados, Objetivo::Aditivo);
+            assert_eq!(dyv.start(), crate::kdtree::TripleKd::new(&puntos).start());
+        }
+    }
+
+    mod aprox {
+        use super::puntos_aleatorios;
+        use crate::dyv_aprox::DyVAprox;
+        use crate::dyv_st::DyV;
+        use crate::kdtree::TripleKd;
+
+        #[test]
+        fn within_eps_of_optimum() {
+            for seed in 0..5 {
+                let puntos = puntos_aleatorios(20_000, seed);
+                let optimo = TripleKd::new(&puntos).start();
+
+                for eps in [0.0, 0.01, 0.5] {
+                    let mut dyv = DyVAprox::new(&puntos, eps);
+                    let res = dyv.start();
+
+                    assert!(dyv.lower_bound() <= optimo);
+                    assert!(res >= optimo && res <= optimo * (1.0 + eps));
+                    if eps == 0.0 {
+                        assert_eq!(res, optimo);
+                    }
+
+                    let [i, j, k] = dyv.get_points();
+                    assert_eq!(
+                        res,
+                        puntos[i].distancia(&puntos[j]) + puntos[j].distancia(&puntos[k])
+                    );
+                }
+            }
+        }
+
+        // Cumulos con puntos repetidos y sin ellos: la cota inferior tiene que
+        // quedarse a (1 + eps) del resultado
+        #[test]
+        fn clusters_certify_the_bound() {
+            let mut puntos = crate::generador::genera(
+                crate::generador::Distribucion::por_nombre("clusters", 30_000, 1000.0).unwrap(),
+                30_000,
+                31,
+            );
+            puntos.sort();
+            let mut repetidos = puntos.clone();
+            repetidos.extend_from_within(..10);
+            repetidos.sort();
+
+            for puntos in [puntos, repetidos] {
+                let optimo = TripleKd::new(&puntos).start();
+                for eps in [0.0, 0.01, 0.5] {
+                    let mut dyv = DyVAprox::new(&puntos, eps);
+                    let res = dyv.start();
+                    assert!(dyv.lower_bound() <= optimo);
+                    assert!(res <= dyv.lower_bound() * (1.0 + eps));
+                    let [i, j, k] = dyv.get_points();
+                    assert_eq!(
+                        res,
+                        puntos[i].distancia(&puntos[j]) + puntos[j].distancia(&puntos[k])
+                    );
+                }
+            }
+        }
+
+        // Entradas que llenan bloques o franjas: la cota sigue valiendo, sea por
+        // la cuadricula o por el exacto cuando se pasa de presupuesto
+        #[test]
+        fn adversarial_inputs() {
+            for (caso, puntos) in crate::generador::adversarios(20_000, 33) {
+                let optimo = DyV::new(&puntos).start();
+                let mut dyv = DyVAprox::new(&puntos, 0.01);
+                let res = dyv.start();
+                assert!(dyv.lower_bound() <= optimo, "{caso}");
+                assert!(res <= dyv.lower_bound() * 1.01, "{caso}");
+            }
+        }
+
+        // Con puntos uniformes casi ningun bloque de la cuadricula tiene 3
+        // puntos: solo se mira una parte pequeña como central
+        #[test]
+        fn grid_skips_most_points() {
+            let puntos = puntos_aleatorios(200_000, 32);
+            let mut dyv = DyVAprox::new(&puntos, 0.01);
+            dyv.start();
+            assert!(dyv.centrales() < puntos.len() / 100, "{}", dyv.centrales());
+        }
+    }
+
+    mod externo {
+        use std::io::Write;
+
+        use rand::{Rng, SeedableRng};
+
+        use super::puntos_aleatorios;
+        use crate::externo::{lee_bloque, ordena_externo, DyVExterno, Temporales};
+        use crate::formatos::NoFinitos;
+        use crate::kdtree::TripleKd;
+        use crate::punto::Punto;
+
+        // Se borran aunque quien los tiene acabe en panic
+        #[test]
+        fn temporales_removed_on_panic() {
+            let path = std::env::temp_dir().join("puntitos_temporal.bin");
+            std::fs::write(&path, b"x").unwrap();
+            let guardado = path.clone();
+            let res = std::panic::catch_unwind(move || {
+                let _temporales = Temporales(vec![guardado]);
+                panic!("a medias");
+            });
+            assert!(res.is_err());
+            assert!(!path.exists());
+        }
+
+        #[test]
+        fn matches_in_memory() {
+            let dir = std::env::temp_dir();
+            let (entrada, salida) = (
+                dir.join("puntitos_externo.tsp"),
+                dir.join("puntitos_externo.bin"),
+            );
+
+            let mut puntos = puntos_aleatorios(30_000, 32);
+            // el fichero de entrada no tiene por que estar ordenado
+            puntos.reverse();
+            crate::write_points_with_name(&entrada, &puntos);
+            puntos.sort();
+
+            assert_eq!(
+                ordena_externo(&entrada, &salida, 1_000, NoFinitos::Rechazar).unwrap(),
+                puntos.len()
+            );
+            let ordenados = lee_bloque(&mut std::fs::File::open(&salida).unwrap(), usize::MAX).unwrap();
+            assert!(ordenados.windows(2).all(|w| w[0].x <= w[1].x));
+
+            let optimo = TripleKd::new(&ordenados).start();
+            for max_puntos in [30, 1_000, 100_000] {
+                let mut dyv = DyVExterno::new(&salida, max_puntos);
+                assert_eq!(dyv.start().unwrap(), optimo);
+
+                let [i, j, k] = dyv.get_points().map(|i| ordenados[i]);
+                let (ij, jk, ki) = (i.distancia(&j), j.distancia(&k), k.distancia(&i));
+                assert_eq!(optimo, (ij + jk).min(jk + ki).min(ki + ij));
+            }
+        }
+
+        // Con runs de 100 puntos hay mas runs que MAX_RUNS y se mezclan en
+        // dos pasadas; los intermedios no se quedan en el directorio
+        #[test]
+        fn merges_in_batches() {
+            let dir = std::env::temp_dir().join("puntitos_externo_lotes");
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let (entrada, salida) = (dir.join("entrada.tsp"), dir.join("salida.bin"));
+
+            let mut puntos = puntos_aleatorios(30_000, 34);
+            puntos.reverse();
+            crate::write_points_with_name(&entrada, &puntos);
+            puntos.sort();
+
+            assert_eq!(
+                ordena_externo(&entrada, &salida, 100, NoFinitos::Rechazar).unwrap(),
+                puntos.len()
+            );
+            let ordenados = lee_bloque(&mut std::fs::File::open(&salida).unwrap(), usize::MAX).unwrap();
+            assert_eq!(ordenados, puntos);
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        }
+
+        // Una coordenada no finita es un error y los runs ya escritos se borran
+        #[test]
+        fn rejects_non_finite_and_cleans_runs() {
+            let dir = std::env::temp_dir().join("puntitos_externo_nan");
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let (entrada, salida) = (dir.join("entrada.tsp"), dir.join("salida.bin"));
+
+            let mut puntos = puntos_aleatorios(1_000, 35);
+            puntos[700].y = f64::NAN;
+            crate::write_points_with_name(&entrada, &puntos);
+
+            let error = ordena_externo(&entrada, &salida, 100, NoFinitos::Rechazar).unwrap_err();
+            assert!(error.to_string().contains("no finita"), "{error}");
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        }
+
+        // Recortar usa los extremos de todo el fichero aunque el infinito y
+        // el maximo caigan en trozos distintos; el NaN se quita
+        #[test]
+        fn clamps_with_global_range() {
+            let dir = std::env::temp_dir().join("puntitos_externo_recorta");
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let (entrada, salida) = (dir.join("entrada.tsp"), dir.join("salida.bin"));
+
+            let mut puntos = puntos_aleatorios(1_000, 37);
+            let max_y = puntos.iter().map(|p| p.y).fold(f64::MIN, f64::max);
+            puntos[10].y = f64::INFINITY;
+            puntos[20].x = f64::NAN;
+            crate::write_points_with_name(&entrada, &puntos);
+
+            assert_eq!(
+                ordena_externo(&entrada, &salida, 100, NoFinitos::Recortar).unwrap(),
+                999
+            );
+            let ordenados = lee_bloque(&mut std::fs::File::open(&salida).unwrap(), usize::MAX).unwrap();
+            assert!(ordenados.iter().all(|p| p.x.is_finite() && p.y.is_finite()));
+            assert!(ordenados.contains(&Punto { x: puntos[10].x, y: max_y }));
+        }
+
+        // Todos los puntos en una banda mas estrecha que el optimo: la franja
+        // es todo lo leido, no cabe en memoria y se resuelve por trozos
+        #[test]
+        fn strip_larger_than_memory() {
+            let path = std::env::temp_dir().join("puntitos_externo_franja.bin");
+            let mut rng = rand::rngs::StdRng::seed_from_u64(36);
+            let mut puntos: Vec<Punto> = (0..300)
+                .map(|_| Punto {
+                    x: rng.gen_range(0.0..1.0),
+                    y: rng.gen_range(0.0..100_000.0),
+                })
+                .collect();
+            puntos.sort();
+            let mut writer = std::fs::File::create(&path).unwrap();
+            for punto in &puntos {
+                writer.write_all(&punto.x.to_le_bytes()).unwrap();
+                writer.write_all(&punto.y.to_le_bytes()).unwrap();
+            }
+            drop(writer);
+
+            let optimo = TripleKd::new(&puntos).start();
+            for max_puntos in [30, 1_000] {
+                let mut dyv = DyVExterno::new(&path, max_puntos);
+                assert_eq!(dyv.start().unwrap(), optimo);
+                let [i, j, k] = dyv.get_points().map(|i| puntos[i]);
+                assert_eq!(optimo, i.distancia(&j) + j.distancia(&k));
+            }
+        }
+    }
+
+    mod pts {
+        use super::puntos_aleatorios;
+        use crate::compresion::Compresion;
+        use crate::pts::{convierte_tsp, write_pts, PtsFile};
+
+        #[test]
+        fn roundtrip_zero_copy() {
+            let path = std::env::temp_dir().join("puntitos_roundtrip.pts");
+            let puntos = puntos_aleatorios(10_000, 33);
+            write_pts(&path, &puntos, true).unwrap();
+
+            let file = PtsFile::open(&path).unwrap();
+            assert!(file.verify());
+            assert!(file.cabecera().ordenado());
+            assert_eq!(file.cabecera().count, puntos.len() as u64);
+
+            // En big endian puntos() es un error y se copia
+            assert_eq!(file.puntos().is_ok(), cfg!(target_endian = "little"));
+            let mapeados = file.puntos_o_copia();
+            if let Ok(sin_copia) = file.puntos() {
+                assert_eq!(sin_copia.as_ptr() as *const u8, file.datos().as_ptr());
+                assert_eq!(mapeados.as_ptr(), sin_copia.as_ptr());
+            }
+            for (a, b) in mapeados.iter().zip(&puntos) {
+                assert_eq!((a.x.to_bits(), a.y.to_bits()), (b.x.to_bits(), b.y.to_bits()));
+            }
+            assert_eq!(file.to_vec(), puntos);
+        }
+
+        #[test]
+        fn convert_and_detect_corruption() {
+            let dir = std::env::temp_dir();
+            let (tsp, pts) = (dir.join("puntitos_convert.tsp"), dir.join("puntitos_convert.pts"));
+
+            let mut puntos = puntos_aleatorios(1_000, 133);
+            puntos.reverse();
+            crate::write_points_with_name(&tsp, &puntos);
+
+            assert_eq!(convierte_tsp(&tsp, &pts, false, Compresion::Ninguna).unwrap(), puntos.len());
+            assert!(!PtsFile::open(&pts).unwrap().cabecera().ordenado());
+
+            convierte_tsp(&tsp, &pts, true, Compresion::Ninguna).unwrap();
+            let file = PtsFile::open(&pts).unwrap();
+            assert!(file.cabecera().ordenado() && file.verify());
+            assert_eq!(file.to_vec().len(), puntos.len());
+            drop(file);
+
+            let mut bytes = std::fs::read(&pts).unwrap();
+            bytes[100] ^= 1;
+            std::fs::write(&pts, &bytes).unwrap();
+            assert!(!PtsFile::open(&pts).unwrap().verify());
+
+            bytes.truncate(bytes.len() - 8);
+            std::fs::write(&pts, &bytes).unwrap();
+            assert!(PtsFile::open(&pts).is_err());
+        }
+
+        // Lo que no se puede leer vuelve como error de convierte_tsp
+        #[test]
+        fn convert_reports_errors() {
+            let dir = std::env::temp_dir();
+            let (tsp, pts) = (dir.join("puntitos_convert_mal.tsp"), dir.join("puntitos_mal.pts"));
+
+            let no_existe = dir.join("puntitos_no_existe.tsp");
+            assert!(convierte_tsp(&no_existe, &pts, false, Compresion::Ninguna).is_err());
+
+            std::fs::write(&tsp, "NODE_COORD_SECTION\n1 0 0\n2 1 x\nEOF\n").unwrap();
+            let error = convierte_tsp(&tsp, &pts, false, Compresion::Ninguna).unwrap_err();
+            assert_eq!(error.to_string(), "coordenada no valida: x");
+
+            std::fs::write(&tsp, "NODE_COORD_SECTION\n1 0 0\n2 1 nan\nEOF\n").unwrap();
+            let error = convierte_tsp(&tsp, &pts, false, Compresion::Ninguna).unwrap_err();
+            assert!(error.to_string().starts_with("linea 3, nodo 2"), "{error}");
+        }
+    }
+
+    mod lector {
+        use super::puntos_aleatorios;
+        use crate::punto::Punto;
+        use std::io::BufRead;
+
+        // Lector linea a linea de antes, como referencia
+        fn read_points_by_line(file_name: &std::path::Path) -> Vec<Punto> {
+            let mut points = Vec::new();
+            let mut buffer = String::new();
+            let mut reader = std::io::BufReader::new(std::fs::File::open(file_name).unwrap());
+
+            while buffer.trim() != "NODE_COORD_SECTION" {
+                buffer.clear();
+                reader.read_line(&mut buffer).unwrap();
+            }
+            buffer.clear();
+
+            while reader.read_line(&mut buffer).unwrap() != 0 {
+                let values: Vec<&str> = buffer.trim().split(' ').collect();
+                points.push(Punto {
+                    x: values[1].parse().unwrap(),
+                    y: values[2].parse().unwrap(),
+                });
+                buffer.clear()
+            }
+
+            points
+        }
+
+        #[test]
+        fn same_values_as_line_reader() {
+            let path = std::env::temp_dir().join("puntitos_lector.tsp");
+            // suficientes puntos para que se parsee en varios bloques
+            let mut puntos = puntos_aleatorios(200_000, 34);
+            puntos.extend([Punto { x: 1e-300, y: -0.0 }, Punto { x: 123456789.12345679, y: 5e300 }]);
+            crate::write_points_with_name(&path, &puntos);
+
+            let rapidos = crate::lector::read_points_fast(&path);
+            let lentos = read_points_by_line(&path);
+            assert_eq!(rapidos.len(), puntos.len());
+            for ((a, b), c) in rapidos.iter().zip(&lentos).zip(&puntos) {
+                assert_eq!((a.x.to_bits(), a.y.to_bits()), (b.x.to_bits(), b.y.to_bits()));
+                assert_eq!((a.x.to_bits(), a.y.to_bits()), (c.x.to_bits(), c.y.to_bits()));
+            }
+        }
+
+        #[test]
+        fn header_and_trailer() {
+            let datos = b"NAME : d\r\nTYPE : TSP\r\nNODE_COORD_SECTION\r\n1 1.5 2\r\n2\t-3   4e2\r\nEOF\r\n";
+            let puntos = crate::lector::parse_tsp(datos);
+            assert_eq!(puntos.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>(), [(1.5, 2.0), (-3.0, 400.0)]);
+        }
+    }
+
+    mod compresion {
+        use std::io::Write;
+
+        use super::puntos_aleatorios;
+        use crate::compresion::Compresion;
+
+        #[test]
+        fn reads_compressed_tsp_and_pts() {
+            let dir = std::env::temp_dir();
+            let puntos = puntos_aleatorios(5_000, 35);
+
+            for compresion in [Compresion::Ninguna, Compresion::Gzip, Compresion::Zstd, Compresion::Xz] {
+                let tsp = dir.join(format!("puntitos_compresion.tsp{}", compresion.extension()));
+                crate::write_points_compressed(&tsp, &puntos, compresion);
+                assert_eq!(Compresion::detecta(&std::fs::read(&tsp).unwrap()), compresion);
+
+                let pts = dir.join(format!("puntitos_compresion.pts{}", compresion.extension()));
+                crate::pts::write_pts_compressed(&pts, &puntos, true, compresion).unwrap();
+
+                for leidos in [crate::read_points_from_file(&tsp), crate::read_points_from_file(&pts)] {
+                    assert_eq!(leidos.len(), puntos.len());
+                    assert!(leidos.iter().zip(&puntos).all(|(a, b)| a.x == b.x && a.y == b.y));
+                }
+            }
+        }
+
+        // Un disco lleno: cualquier escritura falla
+        struct Lleno;
+
+        impl Write for Lleno {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disco lleno"))
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // Lo que falla al terminar el stream llega a quien llama a finish()
+        #[test]
+        fn finish_reports_errors() {
+            for compresion in [Compresion::Ninguna, Compresion::Gzip, Compresion::Zstd, Compresion::Xz] {
+                let resultado = compresion.encoder(Lleno).and_then(|mut encoder| {
+                    encoder.write_all(&[7; 4096])?;
+                    encoder.finish()
+                });
+                assert!(resultado.is_err(), "{compresion:?}");
+
+                let mut salida = Vec::new();
+                let mut encoder = compresion.encoder(&mut salida).unwrap();
+                encoder.write_all(b"NODE_COORD_SECTION\n1 1 2\n").unwrap();
+                encoder.finish().unwrap();
+                assert_eq!(
+                    crate::compresion::descomprime(&salida).unwrap(),
+                    b"NODE_COORD_SECTION\n1 1 2\n"
+                );
+            }
+        }
+    }
+
+    mod formatos {
+        use super::puntos_aleatorios;
+        use crate::compresion::Compresion;
+        use crate::formatos::*;
+        use crate::punto::Punto;
+
+        fn iguales(a: &[Punto], b: &[Punto]) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.x == b.x && a.y == b.y)
+        }
+
+        #[test]
+        fn roundtrip_all_formats() {
+            let dir = std::env::temp_dir();
+            let puntos = puntos_aleatorios(2_000, 36);
+
+            for ext in [
+                "tsp", "pts", "csv", "geojson", "wkt", "npy", "csv.gz", "npy.zst",
+            ] {
+                let path = dir.join(format!("puntitos_formatos.{ext}"));
+                let formato = Formato::por_extension(&path).unwrap();
+                let compresion = if ext.contains('.') {
+                    Compresion::Gzip
+                } else {
+                    Compresion::Ninguna
+                };
+                escribe(&path, &puntos, formato, compresion).unwrap();
+
+                assert!(
+                    iguales(&crate::read_points_from_file(&path), &puntos),
+                    "{ext}"
+                );
+            }
+        }
+
+        #[test]
+        fn csv_columns_and_quotes() {
+            let csv =
+                "id,\"nombre, con coma\",lat,lon\n1,\"a\",2.5,-1\n2,\"b \"\"c\"\"\",3,4e2\n\n";
+            let opciones = OpcionesCsv {
+                x: Columna::Nombre("LON".to_string()),
+                y: "2".parse().unwrap(),
+                ..Default::default()
+            };
+            let puntos = lee_csv(csv.as_bytes(), &opciones).unwrap();
+            assert!(iguales(
+                &puntos,
+                &[Punto { x: -1.0, y: 2.5 }, Punto { x: 400.0, y: 3.0 }]
+            ));
+
+            // Sin cabecera con columnas por posicion
+            let opciones = OpcionesCsv {
+                separador: b';',
+                x: Columna::Indice(0),
+                y: Columna::Indice(1),
+                ..Default::default()
+            };
+            let puntos = lee_csv(b"1;2\n3;4\n", &opciones).unwrap();
+            assert!(iguales(
+                &puntos,
+                &[Punto { x: 1.0, y: 2.0 }, Punto { x: 3.0, y: 4.0 }]
+            ));
+
+            assert!(lee_csv(b"a,b\n1,2\n", &OpcionesCsv::default()).is_err());
+            assert!(lee_csv(b"x,y\n1,2\n3,nope\n", &OpcionesCsv::default()).is_err());
+        }
+
+        #[test]
+        fn geojson_properties_and_triple() {
+            let geojson = r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.5, 2]},
+                 "properties": {"nombre": "a", "peso": 3}},
+                {"type": "Feature", "geometry": {"type": "MultiPoint", "coordinates": [[0, 0], [4, 5, 6]]},
+                 "properties": null}
+            ]}"#;
+            let puntos = lee_geojson(geojson.as_bytes()).unwrap();
+            assert_eq!(puntos.len(), 3);
+            assert_eq!(puntos[0].etiqueta["nombre"], "a");
+            assert_eq!(puntos[0].etiqueta["peso"], 3);
+            assert!(puntos[2].etiqueta.is_empty());
+            assert!((puntos[2].punto.x, puntos[2].punto.y) == (4.0, 5.0));
+
+            let mut salida = Vec::new();
+            escribe_geojson(&mut salida, puntos.clone()).unwrap();
+            let leidos = lee_geojson(&salida).unwrap();
+            assert_eq!(leidos[0].etiqueta, puntos[0].etiqueta);
+
+            let solo: Vec<Punto> = puntos.iter().map(|p| p.punto).collect();
+            let triple = triple_geojson(&solo, [1, 0, 2], 7.5);
+            assert_eq!(triple["geometry"]["type"], "LineString");
+            assert_eq!(triple["geometry"]["coordinates"][0][0], 0.0);
+            assert_eq!(triple["geometry"]["coordinates"][1][1], 2.0);
+            assert_eq!(triple["properties"]["distancia"], 7.5);
+        }
+
+        #[test]
+        fn wkt_variants() {
+            let wkt = "POINT (1 2)\nMULTIPOINT ((3 4), (5 6))\nMULTIPOINT (7 8, 9 10)\nPOINT Z (11 12 13)\n";
+            let puntos = lee_wkt(wkt.as_bytes()).unwrap();
+            let esperado: Vec<Punto> = (0..6)
+                .map(|i| Punto {
+                    x: (2 * i + 1) as f64,
+                    y: (2 * i + 2) as f64,
+                })
+                .collect();
+            assert!(iguales(&puntos, &esperado));
+            assert!(lee_wkt(b"POINT (1)").is_err());
+        }
+
+        #[test]
+        fn npy_f4_and_fortran_order() {
+            fn npy(descr: &str, fortran: bool, forma: &str, cuerpo: &[u8]) -> Vec<u8> {
+                let fortran = if fortran { "True" } else { "False" };
+                let cabecera = format!(
+                    "{{'descr': '{descr}', 'fortran_order': {fortran}, 'shape': {forma}, }}\n"
+                );
+                let mut datos = b"\x93NUMPY\x01\x00".to_vec();
+                datos.extend((cabecera.len() as u16).to_le_bytes());
+                datos.extend(cabecera.as_bytes());
+                datos.extend(cuerpo);
+                datos
+            }
+
+            // (2, 3) en f4: se ignora la tercera columna
+            let cuerpo: Vec<u8> = [1f32, 2., 9., 3., 4., 9.]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect();
+            let puntos = lee_npy(&npy("<f4", false, "(2, 3)", &cuerpo)).unwrap();
+            assert!(iguales(
+                &puntos,
+                &[Punto { x: 1.0, y: 2.0 }, Punto { x: 3.0, y: 4.0 }]
+            ));
+
+            let cuerpo: Vec<u8> = [1f64, 3., 2., 4.]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect();
+            let puntos = lee_npy(&npy("<f8", true, "(2, 2)", &cuerpo)).unwrap();
+            assert!(iguales(
+                &puntos,
+                &[Punto { x: 1.0, y: 2.0 }, Punto { x: 3.0, y: 4.0 }]
+            ));
+
+            assert!(lee_npy(&npy("<i8", false, "(2, 2)", &cuerpo)).is_err());
+            assert!(lee_npy(&npy("<f8", false, "(4,)", &cuerpo)).is_err());
+            assert!(lee_npy(&npy("<f8", false, "(3, 2)", &cuerpo)).is_err());
+            // n * k * 8 no cabe en usize
+            let enorme = format!("({}, 2)", usize::MAX / 4);
+            let error = lee_npy(&npy("<f8", false, &enorme, &cuerpo)).unwrap_err();
+            assert!(error.to_string().contains("demasiado grande"), "{error}");
+        }
+
+        // Las propiedades pasan por lee, convert (ordenando) y escribe
+        #[test]
+        fn geojson_properties_survive_roundtrip() {
+            let geojson = r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1, 2]},
+                 "properties": {"nombre": "a"}},
+                {"type": "Feature", "geometry": {"type": "MultiPoint", "coordinates": [[0, 5], [3, 1]]},
+                 "properties": {"nombre": "b"}}
+            ]}"#;
+            let opciones = OpcionesLectura::default();
+            let (puntos, propiedades) =
+                lee_con_propiedades(geojson.as_bytes(), Formato::GeoJson, &opciones).unwrap();
+            assert_eq!(puntos.len(), 3);
+            assert_eq!(propiedades[2]["nombre"], "b");
+
+            let dir = std::env::temp_dir();
+            let (entrada, salida) = (
+                dir.join("puntitos_prop.geojson"),
+                dir.join("puntitos_prop_ord.geojson"),
+            );
+            escribe_con_propiedades(
+                &entrada,
+                &puntos,
+                &propiedades,
+                Formato::GeoJson,
+                Compresion::Ninguna,
+            )
+            .unwrap();
+            let (leidos, releidas) =
+                crate::lector::read_points_con_propiedades(&entrada, &opciones);
+            assert!(iguales(&leidos, &puntos));
+            assert_eq!(releidas, propiedades);
+
+            let mut args: Vec<String> = [&entrada, &salida].iter().map(|p| p.display().to_string()).collect();
+            args.push("--sort".to_string());
+            crate::convert(&args);
+            let (ordenados, nombres) =
+                crate::lector::read_points_con_propiedades(&salida, &opciones);
+            assert!(iguales(
+                &ordenados,
+                &[
+                    Punto { x: 0.0, y: 5.0 },
+                    Punto { x: 1.0, y: 2.0 },
+                    Punto { x: 3.0, y: 1.0 }
+                ]
+            ));
+            let nombres: Vec<_> = nombres
+                .iter()
+                .map(|p| p["nombre"].as_str().unwrap())
+                .collect();
+            assert_eq!(nombres, ["b", "a", "b"]);
+        }
+    }
+
+    mod stdin {
+        use super::puntos_aleatorios;
+        use crate::compresion::Compresion;
+        use crate::formatos::{self, Formato, OpcionesLectura};
+        use crate::lector::read_points_from_reader;
+
+        #[test]
+        fn sniffs_format() {
+            let casos: [(&[u8], Formato); 8] = [
+                (
+                    b"NAME : prueba\nTYPE : TSP\nNODE_COORD_SECTION\n1 2 3\n",
+                    Formato::Tsplib,
+                ),
+                (b"\nNODE_COORD_SECTION\n1 2 3\n", Formato::Tsplib),
+                (b"x,y\n1,2\n", Formato::Csv),
+                (b"lon;lat\n1;2\n", Formato::Csv),
+                (b"  {\"type\": \"FeatureCollection\"}", Formato::GeoJson),
+                (b"multipoint ((1 2))", Formato::Wkt),
+                (b"PUNTOS\0\0", Formato::Pts),
+                (b"\x93NUMPY\x01\x00", Formato::Npy),
+            ];
+            for (datos, formato) in casos {
+                assert_eq!(
+                    Formato::detecta(datos),
+                    formato,
+                    "{}",
+                    String::from_utf8_lossy(datos)
+                );
+            }
+        }
+
+        #[test]
+        fn reads_any_format_from_stream() {
+            let dir = std::env::temp_dir();
+            let puntos = puntos_aleatorios(3_000, 37);
+
+            for (ext, compresion) in [
+                ("tsp", Compresion::Ninguna),
+                ("pts", Compresion::Ninguna),
+                ("csv", Compresion::Zstd),
+                ("geojson", Compresion::Ninguna),
+                ("wkt", Compresion::Gzip),
+                ("npy", Compresion::Xz),
+            ] {
+                // sin extension reconocible, para que solo cuente el contenido
+                let path = dir.join(format!("puntitos_stream_{ext}"));
+                let formato = Formato::por_extension(format!("a.{ext}")).unwrap();
+                formatos::escribe(&path, &puntos, formato, compresion).unwrap();
+
+                let bytes = std::fs::read(&path).unwrap();
+                let leidos = read_points_from_reader(&bytes[..], &OpcionesLectura::default()).unwrap();
+                assert_eq!(leidos.len(), puntos.len(), "{ext}");
+                assert!(
+                    leidos
+                        .iter()
+                        .zip(&puntos)
+                        .all(|(a, b)| a.x == b.x && a.y == b.y),
+                    "{ext}"
+                );
+                assert_eq!(
+                    crate::read_points_from_file(&path).len(),
+                    puntos.len(),
+                    "{ext}"
+                );
+            }
+
+            assert!(read_points_from_reader(&b""[..], &OpcionesLectura::default())
+                .unwrap()
+                .is_empty());
+        }
+    }
+
+    mod generador {
+        use crate::generador::{genera, Distribucion, Generador};
+        use crate::punto::Punto;
+
+        fn todas(n: usize) -> Vec<Distribucion> {
+            [
+                "caja",
+                "disco",
+                "clusters",
+                "poisson",
+                "reticula",
+                "linea",
+                "circulo",
+                "duplicados",
+            ]
+            .iter()
+            .map(|nombre| Distribucion::por_nombre(nombre, n, 100.0).unwrap())
+            .collect()
+        }
+
+        #[test]
+        fn same_seed_same_points() {
+            for distribucion in todas(2_000) {
+                let a = genera(distribucion.clone(), 2_000, 38);
+                let b = genera(distribucion.clone(), 2_000, 38);
+                let c = genera(distribucion.clone(), 2_000, 39);
+                assert_eq!(a.len(), 2_000, "{distribucion:?}");
+                assert!(a.iter().zip(&b).all(|(p, q)| p.x == q.x && p.y == q.y));
+                assert!(a.iter().zip(&c).any(|(p, q)| p.x != q.x || p.y != q.y));
+            }
+        }
+
+        #[test]
+        fn random_uses_bounds() {
+            let puntos = crate::genera_random(10_000, 5.0, -3.0, 1);
+            assert!(puntos
+                .iter()
+                .all(|p| (-3.0..5.0).contains(&p.x) && (-3.0..5.0).contains(&p.y)));
+            assert!(puntos.iter().any(|p| p.x != puntos[0].x));
+        }
+
+        #[test]
+        fn shapes() {
+            let centro = Punto { x: 1.0, y: -1.0 };
+            let disco = genera(Distribucion::Disco { centro, radio: 2.0 }, 5_000, 1);
+            assert!(disco.iter().all(|p| p.distancia(&centro) <= 2.0));
+
+            let circulo = genera(Distribucion::Circulo { centro, radio: 2.0 }, 5_000, 1);
+            assert!(circulo
+                .iter()
+                .all(|p| (p.distancia(&centro) - 2.0).abs() < 1e-12));
+
+            let (inicio, fin) = (Punto { x: 0.0, y: 0.0 }, Punto { x: 3.0, y: 6.0 });
+            let linea = genera(Distribucion::Linea { inicio, fin }, 5_000, 1);
+            assert!(linea
+                .iter()
+                .all(|p| (p.y - 2.0 * p.x).abs() < 1e-12 && (0.0..=3.0).contains(&p.x)));
+
+            let reticula = Distribucion::Reticula {
+                columnas: 10,
+                paso: 1.0,
+                ruido: 0.0,
+            };
+            let puntos = genera(reticula, 25, 1);
+            assert_eq!((puntos[13].x, puntos[13].y), (3.0, 1.0));
+
+            let mut duplicados = genera(
+                Distribucion::Duplicados {
+                    distintos: 7,
+                    lado: 1.0,
+                },
+                5_000,
+                1,
+            );
+            duplicados.sort();
+            duplicados.dedup_by(|a, b| a.x == b.x && a.y == b.y);
+            assert_eq!(duplicados.len(), 7);
+        }
+
+        #[test]
+        fn poisson_min_distance() {
+            let puntos = genera(
+                Distribucion::PoissonDisk {
+                    lado: 10.0,
+                    radio: 0.5,
+                },
+                usize::MAX,
+                38,
+            );
+            // se llena antes de usize::MAX y nunca hay dos puntos a menos del radio
+            assert!(puntos.len() > 100);
+            for (i, p) in puntos.iter().enumerate() {
+                assert!(puntos[i + 1..].iter().all(|q| p.distancia(q) >= 0.5));
+            }
+        }
+
+        #[test]
+        fn writes_from_iterator() {
+            let dir = std::env::temp_dir();
+            let (a, b) = (
+                dir.join("puntitos_gen_iter.tsp"),
+                dir.join("puntitos_gen_vec.tsp"),
+            );
+            let distribucion = Distribucion::caja(-10.0, 10.0);
+
+            crate::write_points_with_name(&a, Generador::new(distribucion.clone(), 7).take(1_000));
+            let puntos = genera(distribucion, 1_000, 7);
+            crate::write_points_with_name(&b, puntos.as_slice());
+            assert_eq!(std::fs::read(&a).unwrap(), std::fs::read(&b).unwrap());
+        }
+    }
+
+    mod adversarios {
+        use super::fuerza_bruta;
+        use crate::dyv_it::DyVIT;
+        use crate::dyv_mt::DyVMT;
+        use crate::dyv_simd::DyVSIMD;
+        use crate::dyv_st::DyV;
+        use crate::generador::adversarios;
+        use crate::kdtree::TripleKd;
+        use crate::punto::Punto;
+        #[cfg(feature = "stats")]
+        use crate::stats::{self, Contador};
+
+        fn solvers(puntos: &[Punto]) -> Vec<(&'static str, f64, [usize; 3])> {
+            let mut dyv = DyV::new(puntos);
+            let mut dyv_mt = DyVMT::new(puntos);
+            let mut dyv_it = DyVIT::new(puntos);
+            let mut dyv_simd = DyVSIMD::new(puntos);
+            let mut kd = TripleKd::new(puntos);
+            #[allow(unused_mut)]
+            let mut resultados = vec![
+                ("DyV", dyv.start(), dyv.get_points()),
+                ("DyVMT", dyv_mt.start(), dyv_mt.get_points()),
+                ("DyVIT", dyv_it.start(), dyv_it.get_points()),
+                ("DyVSIMD", dyv_simd.start(), dyv_simd.get_points()),
+                ("TripleKd", kd.start(), kd.get_points()),
+            ];
+            #[cfg(feature = "simd-nightly")]
+            for fixed in [3, 16, 130] {
+                let mut dyv_alt = crate::dyv_alt::DyVAlt::new_with_fixed(puntos, fixed);
+                resultados.push(("DyVAlt", dyv_alt.start(), dyv_alt.get_points()));
+            }
+            resultados
+        }
+
+        // Tres indices distintos y en orden de camino: [i, j, k] con j el central
+        fn comprueba(puntos: &[Punto], esperado: f64, caso: &str) {
+            for (solver, res, [i, j, k]) in solvers(puntos) {
+                assert_eq!(res, esperado, "{solver} en {caso}");
+                assert!(
+                    i != j && j != k && i != k,
+                    "{solver} en {caso}: {:?}",
+                    [i, j, k]
+                );
+                let camino = puntos[i].distancia(&puntos[j]) + puntos[j].distancia(&puntos[k]);
+                assert_eq!(camino, res, "{solver} en {caso}: {:?}", [i, j, k]);
+            }
+        }
+
+        fn puntos(coordenadas: &[(f64, f64)]) -> Vec<Punto> {
+            let mut puntos: Vec<Punto> = coordenadas.iter().map(|&(x, y)| Punto { x, y }).collect();
+            puntos.sort();
+            puntos
+        }
+
+        #[test]
+        fn all_solvers_match_brute_force() {
+            for seed in 0..3 {
+                for (caso, puntos) in adversarios(250, seed) {
+                    let esperado = fuerza_bruta(&puntos);
+                    comprueba(&puntos, esperado, &format!("{caso} (seed {seed})"));
+                }
+            }
+        }
+
+        // Con la feature stats: cada solver mira O(n log n) candidatos en todas
+        // las entradas adversarias, no n^2. En vertical (la franja es siempre
+        // todo) DyV saca unos 7 n log n con n = 8000; n^2 seria 60 veces mas.
+        #[cfg(feature = "stats")]
+        type Resuelve = fn(&[Punto]) -> f64;
+
+        #[cfg(feature = "stats")]
+        fn acotados() -> Vec<(&'static str, Resuelve)> {
+            #[allow(unused_mut)]
+            let mut resolvedores: Vec<(&'static str, Resuelve)> = vec![
+                ("DyV", |p| DyV::new_with_fixed(p, 108).start()),
+                ("DyVMT", |p| DyVMT::new_with_fixed(p, 108).start()),
+                ("DyVIT", |p| DyVIT::new_with_fixed(p, 108).start()),
+                ("DyVSIMD", |p| DyVSIMD::new_with_fixed(p, 108).start()),
+            ];
+            #[cfg(feature = "simd-nightly")]
+            resolvedores.push(("DyVAlt", |p| {
+                crate::dyv_alt::DyVAlt::new_with_fixed(p, 108).start()
+            }));
+            resolvedores
+        }
+
+        #[test]
+        #[cfg(feature = "stats")]
+        fn strip_work_is_bounded() {
+            for n in [2_000, 8_000] {
+                let cota = 16 * n as u64 * (n as f64).log2() as u64;
+                for (caso, puntos) in adversarios(n, 7) {
+                    for (solver, resuelve) in acotados() {
+                        stats::reinicia_hilo();
+                        resuelve(&puntos);
+                        let contado = stats::lee_hilo();
+                        for contador in [Contador::J, Contador::K, Contador::Distancias] {
+                            let total = contado.total(contador);
+                            assert!(
+                                total <= cota,
+                                "{solver} en {caso} con n = {n}: {} = {total}",
+                                contador.nombre()
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn coincident_points_are_distinct() {
+            // Tres copias del mismo punto: camino de longitud 0
+            let mut coordenadas = vec![(3.0, 4.0); 3];
+            coordenadas.extend((0..20).map(|i| (i as f64 * 10.0, 100.0)));
+            comprueba(&puntos(&coordenadas), 0.0, "tres iguales");
+
+            // Dos copias: el camino es ir y volver al vecino mas cercano,
+            // o el par repetido mas su vecino
+            let coordenadas = [(0.0, 0.0), (0.0, 0.0), (0.0, 3.0), (10.0, 10.0)];
+            comprueba(&puntos(&coordenadas), 3.0, "dos iguales");
+        }
+
+        #[test]
+        fn equal_x_different_y() {
+            let coordenadas: Vec<(f64, f64)> = (0..300)
+                .map(|i| ((i % 3) as f64 * 100.0, (i * 7 % 300) as f64))
+                .collect();
+            let puntos = puntos(&coordenadas);
+            comprueba(&puntos, fuerza_bruta(&puntos), "tres columnas");
+
+            let puntos = puntos_columna();
+            comprueba(&puntos, 2.0, "una columna");
+        }
+
+        fn puntos_columna() -> Vec<Punto> {
+            puntos(&[(1.0, 9.0), (1.0, 0.0), (1.0, 1.0), (1.0, 2.0), (1.0, 5.0)])
+        }
+
+        #[test]
+        fn lexicographic_order() {
+            let puntos = puntos_columna();
+            assert_eq!(
+                puntos.iter().map(|p| p.y).collect::<Vec<_>>(),
+                vec![0.0, 1.0, 2.0, 5.0, 9.0]
+            );
+            assert_ne!(puntos[0], puntos[1]);
+            assert_eq!(puntos[0], Punto { x: 1.0, y: 0.0 });
+            assert!(Punto { x: 0.0, y: 9.0 } < Punto { x: 1.0, y: 0.0 });
+        }
+    }
+
+    mod no_finitos {
+        use crate::formatos::{filtra_no_finitos, lee, Columna, Formato, NoFinitos, OpcionesLectura};
+        use crate::punto::Punto;
+
+        const TSP: &[u8] = b"NAME : nan\nNODE_COORD_SECTION\n1 0 0\n2 1 1\n\n7 nan 2\n8 3 inf\nEOF\n";
+        const CSV: &[u8] = b"x,y\n0,0\n\n1,-inf\n2,2\n";
+
+        fn con(politica: NoFinitos) -> OpcionesLectura {
+            OpcionesLectura {
+                no_finitos: politica,
+                ..Default::default()
+            }
+        }
+
+        fn coordenadas(puntos: &[Punto]) -> Vec<(f64, f64)> {
+            puntos.iter().map(|p| (p.x, p.y)).collect()
+        }
+
+        #[test]
+        fn reject_reports_line_and_node() {
+            let error = lee(TSP, Formato::Tsplib, &con(NoFinitos::Rechazar)).unwrap_err();
+            assert!(error.to_string().starts_with("linea 6, nodo 7:"), "{error}");
+
+            let error = lee(CSV, Formato::Csv, &con(NoFinitos::Rechazar)).unwrap_err();
+            assert!(error.to_string().starts_with("linea 4:"), "{error}");
+
+            // sin cabecera, con columnas por posicion
+            let mut opciones = con(NoFinitos::Rechazar);
+            opciones.csv.x = Columna::Indice(0);
+            opciones.csv.y = Columna::Indice(1);
+            let error = lee(b"0,0\n1,nan\n", Formato::Csv, &opciones).unwrap_err();
+            assert!(error.to_string().starts_with("linea 2:"), "{error}");
+        }
+
+        #[test]
+        fn drop_and_clamp() {
+            let puntos = lee(TSP, Formato::Tsplib, &con(NoFinitos::Descartar)).unwrap();
+            assert_eq!(coordenadas(&puntos), vec![(0.0, 0.0), (1.0, 1.0)]);
+
+            // el NaN se quita, inf pasa al maximo finito de y
+            let puntos = lee(TSP, Formato::Tsplib, &con(NoFinitos::Recortar)).unwrap();
+            assert_eq!(
+                coordenadas(&puntos),
+                vec![(0.0, 0.0), (1.0, 1.0), (3.0, 2.0)]
+            );
+
+            let puntos = lee(CSV, Formato::Csv, &con(NoFinitos::Recortar)).unwrap();
+            assert_eq!(
+                coordenadas(&puntos),
+                vec![(0.0, 0.0), (1.0, 0.0), (2.0, 2.0)]
+            );
+
+            let infinitos = vec![Punto {
+                x: f64::INFINITY,
+                y: f64::NEG_INFINITY,
+            }];
+            let puntos = filtra_no_finitos(infinitos, NoFinitos::Recortar).unwrap();
+            assert_eq!(coordenadas(&puntos), vec![(f64::MAX, f64::MIN)]);
+        }
+
+        #[test]
+        fn policy_from_str() {
+            assert_eq!("drop".parse(), Ok(NoFinitos::Descartar));
+            assert_eq!("recortar".parse(), Ok(NoFinitos::Recortar));
+            assert!("ignorar".parse::<NoFinitos>().is_err());
+        }
+    }
+
+    mod certificado {
+        use super::puntos_aleatorios;
+        use crate::certificado::{certifica, DobleDoble, TOLERANCIA};
+        use crate::dyv_simd::DyVSIMD;
+        use crate::dyv_st::DyV;
+        use crate::generador::{genera, Distribucion};
+        use crate::punto::Punto;
+
+        fn en_recta(xs: &[f64]) -> Vec<Punto> {
+            xs.iter().map(|&x| Punto { x, y: 0.0 }).collect()
+        }
+
+        #[test]
+        fn doble_doble_sqrt() {
+            let raiz = DobleDoble::new(2.0).raiz();
+            assert_eq!(raiz.hi, std::f64::consts::SQRT_2);
+            assert!((raiz.lo + 9.667293313452913e-17).abs() < 1e-31);
+        }
+
+        #[test]
+        fn near_tie_is_not_unique() {
+            // 0-1-2 mide 2 y 10-11-12' un ulp de 12 mas
+            let puntos = en_recta(&[0.0, 1.0, 2.0, 10.0, 11.0, 12.0 + 8.0 * f64::EPSILON]);
+            let cert = certifica(&puntos, 2.0, TOLERANCIA).unwrap();
+            assert_eq!(cert.indices, [0, 1, 2]);
+            assert_eq!(cert.distancia, 2.0);
+            assert_eq!(cert.candidatos, 2);
+            assert!(cert.margen.unwrap() > 0.0);
+            assert!(!cert.unico);
+
+            let cert = certifica(&puntos, 2.0, 0.0).unwrap();
+            assert!(cert.unico);
+        }
+
+        #[test]
+        fn exact_tie_on_grid() {
+            let reticula = Distribucion::Reticula {
+                columnas: 5,
+                paso: 1.0,
+                ruido: 0.0,
+            };
+            let mut puntos = genera(reticula, 25, 0);
+            puntos.sort();
+            let res = DyV::new(&puntos).start();
+            let cert = certifica(&puntos, res, TOLERANCIA).unwrap();
+            assert_eq!(cert.distancia, 2.0);
+            assert_eq!(cert.margen, Some(0.0));
+            assert!(!cert.unico);
+        }
+
+        #[test]
+        fn agrees_with_solvers() {
+            for seed in 0..4 {
+                let puntos = puntos_aleatorios(2_000, seed);
+                let mut dyv = DyV::new(&puntos);
+                let res = dyv.start();
+                let cert = certifica(&puntos, res, TOLERANCIA).unwrap();
+
+                assert!((cert.distancia - res).abs() <= 4.0 * f64::EPSILON * res);
+                assert!(cert.unico, "seed {seed}");
+                let [i, j, k] = dyv.get_points();
+                assert_eq!(cert.indices, [i.min(k), j, i.max(k)]);
+                assert_eq!(DyVSIMD::new(&puntos).start(), res);
+
+                // Un best por debajo del real no tiene camino que lo certifique
+                assert!(certifica(&puntos, res * 0.5, TOLERANCIA).is_none());
+            }
+        }
+
+        #[test]
+        fn duplicate_heavy() {
+            // 3000 copias de un punto, con -0.0 y 0.0 en la y
+            let mut puntos = vec![Punto { x: 5.0, y: -0.0 }; 3_000];
+            puntos[1].y = 0.0;
+            let cert = certifica(&puntos, 0.0, TOLERANCIA).unwrap();
+            let [i, j, k] = cert.indices;
+            assert!(i != j && j != k && i != k);
+            assert_eq!((cert.distancia, cert.margen, cert.unico), (0.0, Some(0.0), false));
+
+            // Con un best demasiado alto saldrian 3000^3 / 2 caminos sin
+            // quitar copias; sigue encontrando el de 0
+            let cert = certifica(&puntos, 1.0, TOLERANCIA).unwrap();
+            assert_eq!(cert.distancia, 0.0);
+            assert!(cert.candidatos <= puntos.len(), "{}", cert.candidatos);
+
+            // Cada punto dos veces: 0-0-3 mide 3 y su copia empata
+            let puntos = en_recta(&[0.0, 0.0, 3.0, 3.0, 7.0, 7.0, 12.0, 12.0]);
+            let res = DyV::new(&puntos).start();
+            assert_eq!(res, 3.0);
+            let cert = certifica(&puntos, res, TOLERANCIA).unwrap();
+            assert_eq!((cert.distancia, cert.margen, cert.unico), (3.0, Some(0.0), false));
+        }
+    }
+
+    mod escalar {
+        use super::{fuerza_bruta, puntos_aleatorios};
+        use crate::dyv_it::DyVIT;
+        use crate::dyv_mt::DyVMT;
+        use crate::dyv_simd::DyVSIMD;
+        use crate::dyv_st::DyV;
+        use crate::escalar::Escalar;
+        use crate::lector::parse_tsp_como;
+        use crate::punto::Punto;
+
+        // Todos los solvers con coordenadas T, con fixed pequeno para que
+        // haya varios niveles de recursion
+        const SOLVERS: usize = 5 + cfg!(feature = "simd-nightly") as usize;
+
+        fn resultados<T: Escalar>(puntos: &[Punto<T>]) -> Vec<f64> {
+            #[allow(unused_mut)]
+            let mut resultados = vec![
+                DyV::new(puntos).start(),
+                DyV::new_with_fixed(puntos, 4).start(),
+                DyVIT::new_with_fixed(puntos, 4).start(),
+                DyVSIMD::new_with_fixed(puntos, 4).start(),
+                DyVMT::new_with_fixed(puntos, 4).start(),
+            ];
+            #[cfg(feature = "simd-nightly")]
+            resultados.push(crate::dyv_alt::DyVAlt::new_with_fixed(puntos, 4).start());
+            resultados
+        }
+
+        fn como<T: Escalar>(puntos: &[Punto]) -> Vec<Punto<T>> {
+            let mut puntos: Vec<Punto<T>> =
+                puntos.iter().map(|p| Punto::desde(p, 1.0).unwrap()).collect();
+            puntos.sort();
+            puntos
+        }
+
+        #[test]
+        fn integer_grid_matches_f64() {
+            for seed in 0..4 {
+                // Coordenadas enteras, representables en todos los tipos
+                let enteros: Vec<Punto> = puntos_aleatorios(400, seed)
+                    .iter()
+                    .map(|p| Punto {
+                        x: p.x.round(),
+                        y: p.y.round(),
+                    })
+                    .collect();
+                let esperado = fuerza_bruta(&enteros);
+
+                assert_eq!(
+                    resultados(&como::<f64>(&enteros)),
+                    [esperado; SOLVERS],
+                    "f64 seed {seed}"
+                );
+                assert_eq!(
+                    resultados(&como::<f32>(&enteros)),
+                    [esperado; SOLVERS],
+                    "f32 seed {seed}"
+                );
+                assert_eq!(
+                    resultados(&como::<i32>(&enteros)),
+                    [esperado; SOLVERS],
+                    "i32 seed {seed}"
+                );
+                assert_eq!(
+                    resultados(&como::<i64>(&enteros)),
+                    [esperado; SOLVERS],
+                    "i64 seed {seed}"
+                );
+            }
+        }
+
+        #[test]
+        fn fixed_point() {
+            let punto = Punto { x: 1.2345, y: -0.5 };
+            let fijo = Punto::<i32>::desde(&punto, 1000.0).unwrap();
+            assert_eq!((fijo.x, fijo.y), (1235, -500));
+
+            let puntos = como::<f64>(&puntos_aleatorios(300, 7));
+            let fijos: Vec<Punto<i64>> = {
+                let mut fijos: Vec<_> =
+                    puntos.iter().map(|p| Punto::desde(p, 1e6).unwrap()).collect();
+                fijos.sort();
+                fijos
+            };
+            let res = DyV::new(&fijos).start() / 1e6;
+            assert!((res - DyV::new(&puntos).start()).abs() < 1e-5);
+        }
+
+        #[test]
+        fn extreme_integers_saturate() {
+            // Diferencias que no caben en i32/i64; la ventana satura
+            let puntos = [
+                (i32::MIN, i32::MIN),
+                (i32::MIN, i32::MAX),
+                (0, 0),
+                (1, 0),
+                (3, 0),
+                (i32::MAX, 0),
+            ]
+            .map(|(x, y)| Punto { x, y });
+            assert_eq!(resultados(&puntos), [3.0; SOLVERS]);
+
+            let puntos = [
+                (i64::MIN, 0),
+                (-1, i64::MAX),
+                (0, 0),
+                (2, 0),
+                (3, 0),
+                (i64::MAX, i64::MIN),
+            ]
+            .map(|(x, y)| Punto { x, y });
+            assert_eq!(resultados(&puntos), [3.0; SOLVERS]);
+            assert_eq!(
+                puntos[0].distancia(&puntos[5]),
+                (u64::MAX as f64).hypot(i64::MAX as f64 + 1.0)
+            );
+        }
+
+        #[test]
+        fn parse_tsplib_as() {
+            let datos = b"NODE_COORD_SECTION\n1 3 4\n2 5.0 -6\nEOF\n";
+            let puntos = parse_tsp_como::<i32>(datos);
+            assert_eq!(
+                puntos.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>(),
+                [(3, 4), (5, -6)]
+            );
+            let puntos = parse_tsp_como::<f32>(datos);
+            assert_eq!(puntos[1].y, -6.0);
+
+            assert_eq!(i32::parse(b"12.0"), Some(12));
+            assert_eq!(i32::parse(b"12.5"), None);
+            assert_eq!(i32::parse(b"3e10"), None);
+            assert_eq!(i64::parse(b"3e10"), Some(30_000_000_000));
+        }
+
+        // Lo que no cabe en T es un error con su linea, no inf ni i32::MAX
+        #[test]
+        fn out_of_range_is_reported() {
+            use crate::formatos::{lee_como, Formato, NoFinitos, OpcionesLectura};
+
+            assert_eq!(f32::desde_f64(1e39), None);
+            assert_eq!(i32::desde_f64(3e9), None);
+            assert_eq!(i32::desde_f64(-2147483648.4), Some(i32::MIN));
+            assert_eq!(i64::desde_f64(9.3e18), None);
+            assert_eq!(f64::desde_f64(f64::NAN), None);
+
+            let mut opciones = OpcionesLectura::default();
+            let csv = b"x,y\n0,0\n1e39,0\n2,0\n";
+            let error = lee_como::<f32>(csv, Formato::Csv, &opciones, 1.0).unwrap_err();
+            let esperado = "linea 3: coordenada fuera de rango para f32";
+            assert!(error.to_string().starts_with(esperado), "{error}");
+
+            let tsp: &[u8] =
+                b"NODE_COORD_SECTION\n1 3000000000 0\n2 3000000001 0\n3 3000000002 0\nEOF\n";
+            let error = lee_como::<i32>(tsp, Formato::Tsplib, &opciones, 1.0).unwrap_err();
+            let esperado = "linea 2, nodo 1: coordenada fuera de rango para i32";
+            assert!(error.to_string().starts_with(esperado), "{error}");
+            assert_eq!(lee_como::<i64>(tsp, Formato::Tsplib, &opciones, 1.0).unwrap().len(), 3);
+
+            opciones.no_finitos = NoFinitos::Descartar;
+            let puntos = lee_como::<f32>(csv, Formato::Csv, &opciones, 1.0).unwrap();
+            assert_eq!(puntos.iter().map(|p| p.x).collect::<Vec<_>>(), [0.0, 2.0]);
+            // Recortar solo lleva los infinitos a los extremos finitos
+            opciones.no_finitos = NoFinitos::Recortar;
+            assert!(lee_como::<i32>(tsp, Formato::Tsplib, &opciones, 1.0).is_err());
+
+            // read_points_como por el TSPLIB: el rapido no puede con 3e9 en i32
+            let path = std::env::temp_dir().join("puntitos_fuera_de_rango.tsp");
+            std::fs::write(&path, tsp).unwrap();
+            let opciones = OpcionesLectura::default();
+            let leido = std::panic::catch_unwind(|| {
+                crate::lector::read_points_como::<i32, _>(&path, &opciones, 1.0)
+            });
+            assert!(leido.is_err());
+            let puntos = crate::lector::read_points_como::<i64, _>(&path, &opciones, 1.0);
+            assert_eq!(DyV::new(&puntos).start(), 2.0);
+        }
+    }
+
+    mod soa {
+        use super::{fuerza_bruta, puntos_aleatorios};
+        use crate::dyv_simd::DyVSIMD;
+        use crate::escalar::Escalar;
+        use crate::punto::Punto;
+        use crate::soa::PuntosSoa;
+
+        fn como<T: Escalar>(puntos: &[Punto]) -> Vec<Punto<T>> {
+            let mut puntos: Vec<Punto<T>> =
+                puntos.iter().map(|p| Punto::desde(p, 1.0).unwrap()).collect();
+            puntos.sort();
+            puntos
+        }
+
+        // Lo mismo que la ventana vectorizada, punto a punto
+        // Los j que recorre el solver, punto a punto
+        fn ventana<T: Escalar>(
+            puntos: &[Punto<T>],
+            centro: usize,
+            radio: f64,
+            start: usize,
+            end: usize,
+        ) -> Vec<usize> {
+            let centro = &puntos[centro];
+            (start..end)
+                .take_while(|&k| puntos[k].x.diferencia(centro.x) < radio)
+                .filter(|&k| puntos[k].y.diferencia(centro.y).abs() < radio)
+                .collect()
+        }
+
+        // Lo mismo llamando a la ventana vectorizada hasta que devuelve None.
+        // Los que pasan del corte en x se descartan, como en el solver.
+        fn ventana_soa<T: Escalar, const N: usize>(
+            soa: &PuntosSoa<T>,
+            puntos: &[Punto<T>],
+            centro: usize,
+            radio: f64,
+            start: usize,
+            end: usize,
+        ) -> Vec<usize> {
+            let mut encontrados = Vec::new();
+            let mut siguiente = start;
+            while let Some(j) = T::siguiente_en_ventana::<N>(soa, centro, radio, siguiente, end) {
+                assert!(j >= siguiente && j < end);
+                if puntos[j].x.diferencia(puntos[centro].x) < radio {
+                    encontrados.push(j);
+                }
+                siguiente = j + 1;
+            }
+            encontrados
+        }
+
+        fn compara_ventanas<T: Escalar, const N: usize>(puntos: &[Punto<T>]) {
+            let soa = PuntosSoa::new(puntos);
+            for (centro, radio) in [
+                (10, 30.0),
+                (100, 5.5),
+                (250, 120.0),
+                (480, 1.0),
+                (3, f64::MAX),
+            ] {
+                for start in [centro + 1, centro + 7, centro + 20] {
+                    for end in [start + 3, start + 50, puntos.len()] {
+                        let end = end.min(puntos.len());
+                        if start >= end {
+                            continue;
+                        }
+                        assert_eq!(
+                            ventana_soa::<T, N>(&soa, puntos, centro, radio, start, end),
+                            ventana(puntos, centro, radio, start, end),
+                            "{centro} {radio} {start} {end}"
+                        );
+
+                        // Los k visitados estan cerca en y de centro o de start, y
+                        // estan todos los que bajarian best
+                        let mut visitados = Vec::new();
+                        T::candidatos_k::<N>(&soa, centro, start, radio, start, end, |k| {
+                            visitados.push(k);
+                            radio
+                        });
+                        let cerca = |k: usize| {
+                            [centro, start]
+                                .iter()
+                                .any(|&c| puntos[k].y.diferencia(puntos[c].y).abs() < radio)
+                        };
+                        let distancia_ij = puntos[centro].distancia(&puntos[start]);
+                        let mejora = |k: usize| {
+                            puntos[start].distancia(&puntos[k]) < radio - distancia_ij
+                                || distancia_ij + puntos[centro].distancia(&puntos[k]) < radio
+                        };
+                        assert!(visitados.iter().all(|&k| cerca(k)));
+                        assert!((start..end)
+                            .filter(|&k| mejora(k))
+                            .all(|k| visitados.contains(&k)));
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn round_trip_and_alignment() {
+            let puntos = puntos_aleatorios(37, 0);
+            let soa = PuntosSoa::from(&puntos[..]);
+            assert_eq!(soa.len(), 37);
+            assert_eq!(soa.to_vec(), puntos);
+            assert_eq!(soa.xs().as_ptr() as usize % 64, 0);
+            assert_eq!(soa.ys().as_ptr() as usize % 64, 0);
+
+            // Bloques completos de 4, con relleno al final
+            #[cfg(feature = "simd-nightly")]
+            {
+                let (xs, ys) = soa.vectores::<4>();
+                assert_eq!((xs.len(), ys.len()), (10, 10));
+                assert_eq!(xs[9][0], puntos[36].x);
+            }
+        }
+
+        #[test]
+        fn window_matches_scalar() {
+            // Con los carriles de 256 y de 512 bits
+            let puntos = como::<f64>(&puntos_aleatorios(500, 3));
+            compara_ventanas::<_, 4>(&puntos);
+            compara_ventanas::<_, 8>(&puntos);
+            let puntos32 = como::<f32>(&puntos);
+            compara_ventanas::<_, 8>(&puntos32);
+            compara_ventanas::<_, 16>(&puntos32);
+            let enteros = como::<i32>(&puntos);
+            compara_ventanas::<_, 8>(&enteros);
+            compara_ventanas::<_, 16>(&enteros);
+            let enteros = como::<i64>(&puntos);
+            compara_ventanas::<_, 4>(&enteros);
+            compara_ventanas::<_, 8>(&enteros);
+        }
+
+        // Con f64 y f32 solo se visitan los k que bajarian best, comparado con
+        // las mismas cuentas hechas k a k
+        #[cfg(feature = "simd-nightly")]
+        fn compara_distancias<T: Escalar, const N: usize>(puntos: &[Punto<T>]) {
+            let soa = PuntosSoa::new(puntos);
+            for (i, j) in [(0, 1), (17, 18), (40, 45), (200, 199), (300, 320)] {
+                let distancia_ij = puntos[i].distancia(&puntos[j]);
+                for best in [distancia_ij * 1.5, distancia_ij * 3.0, 200.0] {
+                    for start in (i + 1..puntos.len()).step_by(37) {
+                        let end = puntos.len().min(start + 40);
+                        let mut visitados = Vec::new();
+                        T::candidatos_k::<N>(&soa, i, j, best, start, end, |k| {
+                            visitados.push(k);
+                            best
+                        });
+                        let esperados: Vec<usize> = (start..end)
+                            .filter(|&k| {
+                                puntos[j].distancia(&puntos[k]) < best - distancia_ij
+                                    || distancia_ij + puntos[i].distancia(&puntos[k]) < best
+                            })
+                            .collect();
+                        assert_eq!(visitados, esperados, "{i} {j} {best} {start}");
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "simd-nightly")]
+        #[test]
+        fn kernel_matches_scalar() {
+            let puntos = como::<f64>(&puntos_aleatorios(400, 5));
+            compara_distancias::<_, 4>(&puntos);
+            compara_distancias::<_, 8>(&puntos);
+            let puntos32 = como::<f32>(&puntos);
+            compara_distancias::<_, 8>(&puntos32);
+            compara_distancias::<_, 16>(&puntos32);
+        }
+
+        #[cfg(feature = "simd-nightly")]
+        #[test]
+        fn kernel_updates_in_order() {
+            // Varios k del mismo bloque mejoran: hay que quedarse con el primero
+            // que baja best y descartar los que dejan de mejorar despues
+            let puntos: Vec<Punto> = [
+                (0.0, 0.0),
+                (1.0, 0.0),
+                (1.5, 0.0),
+                (1.75, 0.0),
+                (1.875, 0.0),
+                (10.0, 0.0),
+            ]
+            .map(|(x, y)| Punto { x, y })
+            .to_vec();
+            let soa = PuntosSoa::new(&puntos);
+            let mut best = 10.0;
+            let mut visitados = Vec::new();
+            f64::candidatos_k::<4>(&soa, 0, 1, best, 1, puntos.len(), |k| {
+                visitados.push(k);
+                if k != 1 {
+                    best = best.min(1.0 + puntos[1].distancia(&puntos[k]));
+                }
+                best
+            });
+            // 3 va en el bloque de 2 y se visita aunque ya no mejora; 4, en el
+            // siguiente, ya se filtra con best = 1.5
+            assert_eq!(best, 1.5);
+            assert_eq!(visitados, [1, 2, 3]);
+            assert_eq!(
+                DyVSIMD::new_with_fixed(&puntos, 6).start(),
+                fuerza_bruta(&puntos)
+            );
+        }
+
+        #[test]
+        fn partial_blocks() {
+            // Tamanos que no llenan el ultimo bloque, con franjas cortas
+            for n in 3..40 {
+                let puntos = puntos_aleatorios(n, n as u64);
+                let esperado = fuerza_bruta(&puntos);
+                for fixed in [3, 5, 16] {
+                    let mut dyv = DyVSIMD::new_with_fixed(&puntos, fixed);
+                    assert_eq!(dyv.start(), esperado, "n {n} fixed {fixed}");
+                    let [i, j, k] = dyv.get_points();
+                    assert_eq!(puntos[j].distancia3(&puntos[i], &puntos[k]), esperado);
+                }
+                assert_eq!(DyVSIMD::new(&puntos).start(), esperado, "n {n}");
+            }
+        }
+    }
+
+    mod kernel {
+        use super::{fuerza_bruta, puntos_aleatorios};
+        use crate::dyv_simd::DyVSIMD;
+        use crate::kernel::Kernel;
+        use crate::punto::Punto;
+
+        #[test]
+        fn names_round_trip() {
+            for kernel in Kernel::TODOS {
+                assert_eq!(kernel.to_string().parse::<Kernel>(), Ok(kernel));
+            }
+            assert_eq!("AVX-512".parse::<Kernel>(), Ok(Kernel::Avx512));
+            assert!("neon".parse::<Kernel>().is_err());
+            assert!(Kernel::Sse2.disponible());
+            assert!(Kernel::detecta().disponible());
+        }
+
+        #[test]
+        fn forced_kernels_agree() {
+            // Cada kernel que tenga la CPU da el mismo resultado, con f64 y f32
+            for n in [3, 17, 90, 400] {
+                let puntos = puntos_aleatorios(n, n as u64 + 11);
+                let esperado = fuerza_bruta(&puntos);
+                let mut puntos32: Vec<Punto<f32>> =
+                    puntos.iter().map(|p| Punto::desde(p, 1.0).unwrap()).collect();
+                puntos32.sort();
+                let esperado32 = DyVSIMD::new_with_fixed(&puntos32, 16).start();
+
+                for kernel in Kernel::TODOS.into_iter().filter(|k| k.disponible()) {
+                    for fixed in [3, 16] {
+                        let mut dyv = DyVSIMD::new_with_fixed(&puntos, fixed);
+                        dyv.kernel = kernel;
+                        assert_eq!(dyv.start(), esperado, "{kernel} n {n} fixed {fixed}");
+
+                        let mut dyv = DyVSIMD::new_with_fixed(&puntos32, fixed);
+                        dyv.kernel = kernel;
+                        assert_eq!(dyv.start(), esperado32, "{kernel} f32 n {n} fixed {fixed}");
+                    }
+                }
+            }
+        }
+    }
+
+    mod orden {
+        use super::{fuerza_bruta, puntos_aleatorios};
+        use crate::dyv_st::DyV;
+        use crate::escalar::Escalar;
+        use crate::orden::{esta_ordenado, permutacion, Ordenados};
+        use crate::punto::Punto;
+        use rand::seq::SliceRandom;
+        use rand::SeedableRng;
+
+        fn desordena(puntos: &[Punto], seed: u64) -> Vec<Punto> {
+            let mut puntos = puntos.to_vec();
+            puntos.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed));
+            puntos
+        }
+
+        fn como<T: Escalar>(puntos: &[Punto], escala: f64) -> Vec<Punto<T>> {
+            puntos.iter().map(|p| Punto::desde(p, escala).unwrap()).collect()
+        }
+
+        // El orden por x de sort(), con los empates en el orden de la entrada
+        fn compara<T: Escalar>(puntos: &[Punto<T>]) {
+            let mut esperado: Vec<usize> = (0..puntos.len()).collect();
+            esperado.sort_by(|&a, &b| puntos[a].x_compare(&puntos[b]));
+            for hilos in [1, 3, 8] {
+                assert_eq!(permutacion(puntos, hilos), esperado, "{hilos} hilos");
+            }
+        }
+
+        #[test]
+        fn sorted_input_is_borrowed() {
+            let puntos = puntos_aleatorios(100, 1);
+            let ordenados = Ordenados::new(&puntos);
+            assert!(ordenados.ya_ordenado());
+            assert!(std::ptr::eq(ordenados.puntos(), &puntos[..]));
+            assert_eq!(ordenados.originales([4, 0, 99]), [4, 0, 99]);
+        }
+
+        #[test]
+        fn radix_matches_sort() {
+            let puntos = desordena(&puntos_aleatorios(5000, 2), 2);
+            assert!(!esta_ordenado(&puntos));
+            compara(&puntos);
+
+            // Signos, ceros, infinitos y x repetidas
+            let mut raros: Vec<Punto> = [-0.0, 0.0, -1.5, f64::INFINITY, 1e-300, -f64::MAX, 3.0]
+                .iter()
+                .cycle()
+                .take(40)
+                .enumerate()
+                .map(|(i, &x)| Punto { x, y: i as f64 })
+                .collect();
+            compara(&raros);
+            raros.reverse();
+            compara(&raros);
+
+            // Los demas tipos, con negativos y casi todo su rango
+            compara(&como::<f32>(&puntos, 1.0));
+            compara(&como::<i32>(&puntos, 1e6));
+            compara(&como::<i64>(&puntos, 1e15));
+            compara::<f64>(&[]);
+        }
+
+        #[test]
+        fn indices_refer_to_input() {
+            let puntos = desordena(&puntos_aleatorios(300, 3), 3);
+            let ordenados = Ordenados::new(&puntos);
+            assert!(!ordenados.ya_ordenado());
+            assert!(esta_ordenado(ordenados.puntos()));
+            for (i, punto) in ordenados.puntos().iter().enumerate() {
+                assert_eq!(*punto, puntos[ordenados.original(i)]);
+            }
+
+            let mut dyv = DyV::new(ordenados.puntos());
+            let res = dyv.start();
+            assert_eq!(res, fuerza_bruta(&puntos));
+            let [i, j, k] = ordenados.originales(dyv.get_points());
+            assert_eq!(puntos[j].distancia3(&puntos[i], &puntos[k]), res);
+        }
+    }
+
+    mod perfil {
+        use super::puntos_aleatorios;
+        use crate::perfil::{afina, clave, iguales, muestra, variantes, Perfil};
+
+        #[test]
+        fn save_and_load() {
+            let path = std::env::temp_dir().join("puntitos_perfil/host.json");
+            let perfil = Perfil {
+                host: "host".to_string(),
+                fixed_points: [("dyv_st".to_string(), 96), ("dyv_mt".to_string(), 144)].into(),
+            };
+            perfil.guarda(&path).unwrap();
+            assert_eq!(Perfil::lee(&path).unwrap(), perfil);
+
+            std::fs::write(&path, r#"{"host": "host", "fixed_points": {"dyv_st": 1}}"#).unwrap();
+            assert!(Perfil::lee(&path).is_err());
+            std::fs::write(&path, r#"{"fixed_points": {}}"#).unwrap();
+            assert!(Perfil::lee(&path).is_err());
+            std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        }
+
+        // Cada tipo de coordenada tiene su entrada; las de antes, sin tipo,
+        // solo valen para f64
+        #[test]
+        fn entries_by_coordinate_type() {
+            let perfil = Perfil {
+                host: "host".to_string(),
+                fixed_points: [
+                    ("dyv_st".to_string(), 96),
+                    (clave::<i32>("dyv_st"), 40),
+                    (clave::<f64>("dyv_mt"), 144),
+                ]
+                .into(),
+            };
+            assert_eq!(clave::<f32>("dyv_st"), "dyv_st:f32");
+            assert_eq!(perfil.fixed_points::<f64>("dyv_st", 108), 96);
+            assert_eq!(perfil.fixed_points::<i32>("dyv_st", 108), 40);
+            assert_eq!(perfil.fixed_points::<f32>("dyv_st", 108), 108);
+            assert_eq!(perfil.fixed_points::<f64>("dyv_mt", 126), 144);
+            assert_eq!(perfil.fixed_points::<i64>("dyv_mt", 126), 126);
+            assert_eq!(perfil.fixed_points::<f64>("dyv_it", 108), 108);
+        }
+
+        #[test]
+        fn sweep_picks_a_candidate() {
+            let puntos = muestra(&puntos_aleatorios(3000, 4), 1000, 4);
+            let valores = [8, 40, 120];
+            let mut medidas = Vec::new();
+            let perfil = afina(&puntos, &valores, 1, |variante, fixed, ms| {
+                medidas.push((variante.to_string(), fixed, ms))
+            });
+
+            assert_eq!(medidas.len(), variantes().len() * valores.len());
+            assert_eq!(perfil.fixed_points.len(), variantes().len());
+            for (variante, _) in variantes() {
+                // El elegido es el de menor tiempo de su variante
+                let mejor = medidas
+                    .iter()
+                    .filter(|(v, ..)| v == variante)
+                    .min_by(|a, b| a.2.total_cmp(&b.2))
+                    .unwrap();
+                assert_eq!(mejor.1, perfil.fixed_points[&clave::<f64>(variante)], "{variante}");
+            }
+        }
+
+        // Todas las variantes, tambien dyv_alt con simd-nightly, dan lo mismo
+        // que dyv_st; si no, afina no les deja entrada
+        #[test]
+        fn every_variant_gets_an_entry() {
+            for (caso, puntos) in crate::generador::adversarios(1000, 7) {
+                let referencia = variantes()[0].1(&puntos, 3)();
+                for (variante, solver) in variantes() {
+                    for fixed in [3, 16, 130] {
+                        let res = solver(&puntos, fixed)();
+                        assert!(iguales(res, referencia), "{variante} en {caso}: {res}");
+                    }
+                }
+            }
+            let (_, puntos) = crate::generador::adversarios(1000, 7).swap_remove(0);
+            let perfil = afina(&puntos, &[16, 130], 1, |_, _, _| ());
+            assert_eq!(perfil.fixed_points.len(), variantes().len());
+        }
+
+        #[test]
+        fn sample_keeps_order() {
+            let puntos = puntos_aleatorios(500, 5);
+            let elegidos = muestra(&puntos, 100, 5);
+            assert_eq!(elegidos.len(), 100);
+            assert!(elegidos.windows(2).all(|par| par[0] <= par[1]));
+            assert!(elegidos.iter().all(|p| puntos.contains(p)));
+            assert_eq!(muestra(&puntos, 600, 5), puntos);
+        }
+    }
+
+    mod stats {
+        use super::puntos_aleatorios;
+        use crate::perfil::variantes;
+        use crate::stats::{self, Contador, Fase};
+
+        #[test]
+        #[cfg(not(feature = "stats"))]
+        fn compiled_out() {
+            let puntos = puntos_aleatorios(2000, 6);
+            for (_, solver) in variantes() {
+                solver(&puntos, 16)();
+            }
+            assert_eq!(stats::lee(), Default::default());
+        }
+
+        // DyVMT cuenta en sus hilos y lo vuelca a los globales al acabar
+        // cada hoja, tambien los rechazos por y y los cortes por x
+        #[test]
+        #[cfg(feature = "stats")]
+        fn multi_thread_flushes_its_counts() {
+            let puntos = puntos_aleatorios(20_000, 8);
+            let antes = stats::lee();
+            crate::dyv_mt::DyVMT::new(&puntos).start();
+            let despues = stats::lee();
+            let suma = |contador| despues.total(contador) - antes.total(contador);
+
+            assert!(suma(Contador::I) >= puntos.len() as u64);
+            assert!(suma(Contador::RechazosY) > 0);
+            assert!(suma(Contador::CortesX) > 0);
+        }
+
+        // DyVSIMD cuenta los cortes por x donde se corta, en siguiente_en_ventana,
+        // tanto en los bloques como en los ultimos de uno en uno
+        #[test]
+        #[cfg(feature = "stats")]
+        fn window_counts_its_cuts() {
+            use crate::escalar::Escalar;
+            use crate::punto::Punto;
+            use crate::soa::PuntosSoa;
+
+            let mut puntos: Vec<Punto> =
+                (0..40).map(|i| Punto { x: (i / 4) as f64, y: 10.0 * (i % 4) as f64 }).collect();
+            puntos.push(Punto { x: 30.0, y: 0.0 });
+            let soa = PuntosSoa::new(&puntos);
+            let cortes = |start: usize, end: usize| {
+                stats::reinicia_hilo();
+                let res = f64::siguiente_en_ventana::<4>(&soa, 0, 2.5, start, end);
+                (res, stats::lee_hilo().total(Contador::CortesX))
+            };
+
+            // En el primer bloque de 2 * N: x = 5 ya esta lejos
+            assert_eq!(cortes(20, 41), (None, 1));
+            // Menos de 2 * N, uno a uno: x = 9 ya corta
+            assert_eq!(cortes(37, 41), (None, 1));
+            // Hay j o se acaba el rango: sin corte
+            assert_eq!(cortes(1, 41), (Some(4), 0));
+            assert_eq!(cortes(1, 4), (None, 0));
+        }
+
+        // Los contadores son globales y los tests van en paralelo: solo se
+        // puede mirar lo que como poco ha sumado cada solver
+        #[test]
+        #[cfg(feature = "stats")]
+        fn every_solver_reports() {
+            let puntos = puntos_aleatorios(2000, 6);
+            for (variante, solver) in variantes() {
+                let antes = stats::lee();
+                solver(&puntos, 16)();
+                let despues = stats::lee();
+                let suma = |fase, contador| despues.get(fase, contador) - antes.get(fase, contador);
+
+                assert!(
+                    suma(Fase::Hojas, Contador::I) >= puntos.len() as u64 / 2,
+                    "{variante}"
+                );
+                assert!(suma(Fase::Franja, Contador::Franjas) >= 1, "{variante}");
+                assert!(
+                    despues.total(Contador::Mejoras) > antes.total(Contador::Mejoras),
+                    "{variante}"
+                );
+                assert!(
+                    despues.total(Contador::Distancias) > antes.total(Contador::Distancias),
+                    "{variante}"
+                );
+                assert!(
+                    despues.get(Fase::Franja, Contador::MaxFranja) >= 1,
+                    "{variante}"
+                );
+            }
+        }
+
+        #[test]
+        fn json_has_every_counter() {
+            let json = stats::lee().a_json();
+            for fase in Fase::TODAS {
+                for contador in Contador::TODOS {
+                    assert!(json[fase.nombre()][contador.nombre()].is_u64());
+                }
+            }
+        }
+    }
 }